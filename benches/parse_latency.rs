@@ -0,0 +1,53 @@
+// per-tick parse latency of the previous lossy-string + serde path against the byte scanner:
+// cargo bench --bench parse_latency
+// the binary crate has no library target; the parser modules are compiled in by path,
+// and their warnings are reported against the binary.
+#![allow(dead_code, unused_imports, clippy::upper_case_acronyms)]
+
+#[path = "../src/exchange"]
+mod exchange {
+    pub mod binary;
+}
+
+#[path = "../src/market_data"]
+mod market_data {
+    pub mod fix_md;
+    pub mod parser;
+    pub mod scan;
+    pub mod symbol;
+}
+
+use market_data::parser::{MarketDataFormat, MarketDataParser};
+use std::hint::black_box;
+use std::time::Instant;
+
+#[derive(serde::Deserialize)]
+struct JsonTick {
+    symbol: Option<String>,
+    price: f64,
+    volume: u64,
+    timestamp: Option<u64>,
+    seq: Option<u64>,
+}
+
+const ITERATIONS: u32 = 1_000_000;
+
+fn main() {
+    let data: &[u8] = br#"{"symbol":"AAPL","price":100.5,"volume":10,"timestamp":1700000000000000000,"seq":2}"#;
+    let parser = MarketDataParser::new(MarketDataFormat::JSON);
+
+    let start = Instant::now();
+    for _ in 0..ITERATIONS {
+        let text = String::from_utf8_lossy(black_box(data)).to_string();
+        black_box(serde_json::from_str::<JsonTick>(&text).unwrap());
+    }
+    let legacy = start.elapsed() / ITERATIONS;
+
+    let start = Instant::now();
+    for _ in 0..ITERATIONS {
+        black_box(parser.parse_bytes(black_box(data)).unwrap());
+    }
+    let scanned = start.elapsed() / ITERATIONS;
+
+    println!("json tick parse: legacy {:?}/tick, zero-copy {:?}/tick", legacy, scanned);
+}
@@ -0,0 +1,379 @@
+use crate::config::{CalendarConfig, SessionConfig};
+use chrono::{DateTime, Datelike, NaiveDate, NaiveTime, Utc, Weekday};
+use chrono_tz::Tz;
+use log::info;
+use serde::Deserialize;
+use std::collections::{HashMap, HashSet};
+use std::fmt;
+use std::time::Duration;
+
+// part of the trading day an instrument is in
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SessionPhase {
+    Closed,
+    PreOpen,
+    OpeningAuction,
+    Continuous,
+    ClosingAuction,
+    PostClose,
+}
+
+impl fmt::Display for SessionPhase {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            SessionPhase::Closed => "closed",
+            SessionPhase::PreOpen => "pre-open",
+            SessionPhase::OpeningAuction => "opening auction",
+            SessionPhase::Continuous => "continuous",
+            SessionPhase::ClosingAuction => "closing auction",
+            SessionPhase::PostClose => "post-close",
+        };
+        f.write_str(name)
+    }
+}
+
+// session phase transition delivered to strategies
+#[derive(Debug, Clone, PartialEq)]
+pub struct PhaseChange {
+    pub session: String,
+    pub from: SessionPhase,
+    pub to: SessionPhase,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct PhaseWindow {
+    phase: SessionPhase,
+    start: NaiveTime,
+    end: NaiveTime,
+}
+
+#[derive(Debug)]
+struct Session {
+    name: String,
+    timezone: Tz,
+    weekends: bool,
+    holidays: HashSet<NaiveDate>,
+    half_days: HashMap<NaiveDate, NaiveTime>,
+    // sorted by start time
+    phases: Vec<PhaseWindow>,
+}
+
+fn parse_time(value: &str) -> Result<NaiveTime, Box<dyn std::error::Error>> {
+    NaiveTime::parse_from_str(value, "%H:%M:%S")
+        .or_else(|_| NaiveTime::parse_from_str(value, "%H:%M"))
+        .map_err(|e| format!("Invalid time {}: {}", value, e).into())
+}
+
+fn parse_date(value: &str) -> Result<NaiveDate, Box<dyn std::error::Error>> {
+    NaiveDate::parse_from_str(value, "%Y-%m-%d").map_err(|e| format!("Invalid date {}: {}", value, e).into())
+}
+
+impl Session {
+    fn from_config(config: &SessionConfig) -> Result<Self, Box<dyn std::error::Error>> {
+        let timezone: Tz = config
+            .timezone
+            .parse()
+            .map_err(|e| format!("Session {}: invalid timezone {}: {}", config.name, config.timezone, e))?;
+        let mut phases = config
+            .phases
+            .iter()
+            .map(|p| {
+                Ok(PhaseWindow {
+                    phase: p.phase,
+                    start: parse_time(&p.start)?,
+                    end: parse_time(&p.end)?,
+                })
+            })
+            .collect::<Result<Vec<_>, Box<dyn std::error::Error>>>()?;
+        phases.sort_by_key(|p| p.start);
+        for window in &phases {
+            if window.start >= window.end {
+                return Err(format!("Session {}: {} phase ends before it starts", config.name, window.phase).into());
+            }
+        }
+        if phases.windows(2).any(|pair| pair[0].end > pair[1].start) {
+            return Err(format!("Session {}: overlapping phases", config.name).into());
+        }
+        if !phases.iter().any(|p| p.phase == SessionPhase::Continuous) {
+            return Err(format!("Session {}: no continuous phase", config.name).into());
+        }
+
+        Ok(Session {
+            name: config.name.clone(),
+            timezone,
+            weekends: config.weekends,
+            holidays: config.holidays.iter().map(|d| parse_date(d)).collect::<Result<_, _>>()?,
+            half_days: config
+                .half_days
+                .iter()
+                .map(|h| Ok((parse_date(&h.date)?, parse_time(&h.close)?)))
+                .collect::<Result<_, Box<dyn std::error::Error>>>()?,
+            phases,
+        })
+    }
+
+    // phase windows for a local date, `None` when the market does not open
+    fn schedule(&self, date: NaiveDate) -> Option<Vec<PhaseWindow>> {
+        let weekend = matches!(date.weekday(), Weekday::Sat | Weekday::Sun);
+        if (weekend && !self.weekends) || self.holidays.contains(&date) {
+            return None;
+        }
+        let Some(&early_close) = self.half_days.get(&date) else {
+            return Some(self.phases.clone());
+        };
+
+        // continuous trading ends early; later phases keep their length and move up
+        let regular_close = self.regular_close();
+        let shift = regular_close - early_close;
+        let mut phases = Vec::with_capacity(self.phases.len());
+        for window in &self.phases {
+            if window.start >= regular_close {
+                phases.push(PhaseWindow { start: window.start - shift, end: window.end - shift, ..*window });
+            } else if window.start < early_close {
+                phases.push(PhaseWindow { end: window.end.min(early_close), ..*window });
+            }
+        }
+        Some(phases)
+    }
+
+    fn regular_close(&self) -> NaiveTime {
+        self.phases
+            .iter()
+            .filter(|p| p.phase == SessionPhase::Continuous)
+            .map(|p| p.end)
+            .max()
+            .expect("validated to have a continuous phase")
+    }
+
+    fn phase_at(&self, now: DateTime<Utc>) -> SessionPhase {
+        let local = now.with_timezone(&self.timezone);
+        let time = local.time();
+        self.schedule(local.date_naive())
+            .and_then(|phases| phases.into_iter().find(|p| p.start <= time && time < p.end))
+            .map_or(SessionPhase::Closed, |p| p.phase)
+    }
+
+    // time left until continuous trading ends today, `None` once it has ended or on closed days
+    fn time_to_close(&self, now: DateTime<Utc>) -> Option<Duration> {
+        let local = now.with_timezone(&self.timezone);
+        let close = self
+            .schedule(local.date_naive())?
+            .iter()
+            .filter(|p| p.phase == SessionPhase::Continuous)
+            .map(|p| p.end)
+            .max()?;
+        (close - local.time()).to_std().ok().filter(|left| !left.is_zero())
+    }
+}
+
+// per-instrument trading calendar with session phases, holidays and early closes
+pub struct TradingCalendar {
+    sessions: Vec<Session>,
+    // symbol -> index into `sessions`
+    by_symbol: HashMap<String, usize>,
+    default_session: Option<usize>,
+    order_phases: HashSet<SessionPhase>,
+    flatten_before_close: Option<Duration>,
+    last_phases: Vec<Option<SessionPhase>>,
+    flattened_on: Vec<Option<NaiveDate>>,
+}
+
+impl TradingCalendar {
+    pub fn from_config(config: &CalendarConfig) -> Result<Self, Box<dyn std::error::Error>> {
+        let sessions = config.sessions.iter().map(Session::from_config).collect::<Result<Vec<_>, _>>()?;
+        let mut by_symbol = HashMap::new();
+        let mut default_session = None;
+        for (index, session) in config.sessions.iter().enumerate() {
+            if session.symbols.is_empty() && default_session.replace(index).is_some() {
+                return Err("More than one session without symbols".into());
+            }
+            for symbol in &session.symbols {
+                if by_symbol.insert(symbol.clone(), index).is_some() {
+                    return Err(format!("{} is listed in more than one session", symbol).into());
+                }
+            }
+        }
+        let count = sessions.len();
+        Ok(TradingCalendar {
+            sessions,
+            by_symbol,
+            default_session,
+            order_phases: config.order_phases.iter().copied().collect(),
+            flatten_before_close: config.flatten_before_close_min.map(|min| Duration::from_secs(min * 60)),
+            last_phases: vec![None; count],
+            flattened_on: vec![None; count],
+        })
+    }
+
+    fn session_for(&self, symbol: &str) -> Option<&Session> {
+        let index = self.by_symbol.get(symbol).copied().or(self.default_session)?;
+        Some(&self.sessions[index])
+    }
+
+    pub fn session_name(&self, symbol: &str) -> Option<&str> {
+        self.session_for(symbol).map(|s| s.name.as_str())
+    }
+
+    /// current phase of an instrument; instruments without a session trade around the clock
+    pub fn phase(&self, symbol: &str, now: DateTime<Utc>) -> SessionPhase {
+        match self.session_for(symbol) {
+            Some(session) => session.phase_at(now),
+            None => SessionPhase::Continuous,
+        }
+    }
+
+    pub fn in_flatten_window(&self, symbol: &str, now: DateTime<Utc>) -> bool {
+        match (self.flatten_before_close, self.session_for(symbol)) {
+            (Some(lead), Some(session)) => session.time_to_close(now).is_some_and(|left| left <= lead),
+            _ => false,
+        }
+    }
+
+    /// whether new orders may be sent: a permitted phase and not within the pre-close flatten window
+    pub fn can_trade(&self, symbol: &str, now: DateTime<Utc>) -> bool {
+        self.order_phases.contains(&self.phase(symbol, now)) && !self.in_flatten_window(symbol, now)
+    }
+
+    /// phase transitions since the previous poll. the first poll reports the initial phases.
+    pub fn poll(&mut self, now: DateTime<Utc>) -> Vec<PhaseChange> {
+        let mut changes = Vec::new();
+        for (session, last) in self.sessions.iter().zip(self.last_phases.iter_mut()) {
+            let phase = session.phase_at(now);
+            if *last != Some(phase) {
+                let from = last.unwrap_or(SessionPhase::Closed);
+                info!("Session {} phase: {} -> {}", session.name, from, phase);
+                changes.push(PhaseChange { session: session.name.clone(), from, to: phase });
+                *last = Some(phase);
+            }
+        }
+        changes
+    }
+
+    /// sessions that just entered the flatten window, reported once per trading day
+    pub fn flatten_due(&mut self, now: DateTime<Utc>) -> Vec<String> {
+        let Some(lead) = self.flatten_before_close else {
+            return Vec::new();
+        };
+        let mut due = Vec::new();
+        for (session, flattened_on) in self.sessions.iter().zip(self.flattened_on.iter_mut()) {
+            let today = now.with_timezone(&session.timezone).date_naive();
+            if *flattened_on != Some(today) && session.time_to_close(now).is_some_and(|left| left <= lead) {
+                *flattened_on = Some(today);
+                due.push(session.name.clone());
+            }
+        }
+        due
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::{HalfDayConfig, PhaseConfig};
+    use chrono::TimeZone;
+
+    fn phase(phase: SessionPhase, start: &str, end: &str) -> PhaseConfig {
+        PhaseConfig { phase, start: start.to_string(), end: end.to_string() }
+    }
+
+    fn calendar() -> TradingCalendar {
+        let config = CalendarConfig {
+            sessions: vec![SessionConfig {
+                name: "XNYS".to_string(),
+                timezone: "America/New_York".to_string(),
+                symbols: vec!["AAPL".to_string()],
+                weekends: false,
+                holidays: vec!["2024-07-04".to_string()],
+                half_days: vec![HalfDayConfig { date: "2024-07-03".to_string(), close: "13:00".to_string() }],
+                phases: vec![
+                    phase(SessionPhase::PreOpen, "04:00", "09:28"),
+                    phase(SessionPhase::OpeningAuction, "09:28", "09:30"),
+                    phase(SessionPhase::Continuous, "09:30", "16:00"),
+                    phase(SessionPhase::ClosingAuction, "16:00", "16:05"),
+                ],
+            }],
+            order_phases: vec![SessionPhase::Continuous],
+            flatten_before_close_min: Some(5),
+        };
+        TradingCalendar::from_config(&config).unwrap()
+    }
+
+    // New York is UTC-4 in July
+    fn ny(day: u32, hour: u32, minute: u32) -> DateTime<Utc> {
+        Utc.with_ymd_and_hms(2024, 7, day, hour + 4, minute, 0).unwrap()
+    }
+
+    #[test]
+    fn test_phases_holidays_and_half_days() {
+        let calendar = calendar();
+        assert_eq!(calendar.phase("AAPL", ny(2, 3, 59)), SessionPhase::Closed);
+        assert_eq!(calendar.phase("AAPL", ny(2, 9, 0)), SessionPhase::PreOpen);
+        assert_eq!(calendar.phase("AAPL", ny(2, 9, 29)), SessionPhase::OpeningAuction);
+        assert_eq!(calendar.phase("AAPL", ny(2, 12, 0)), SessionPhase::Continuous);
+        assert_eq!(calendar.phase("AAPL", ny(2, 16, 1)), SessionPhase::ClosingAuction);
+        assert_eq!(calendar.phase("AAPL", ny(2, 16, 5)), SessionPhase::Closed);
+
+        // independence day and a saturday
+        assert_eq!(calendar.phase("AAPL", ny(4, 12, 0)), SessionPhase::Closed);
+        assert_eq!(calendar.phase("AAPL", ny(6, 12, 0)), SessionPhase::Closed);
+
+        // early close: the closing auction moves up with it
+        assert_eq!(calendar.phase("AAPL", ny(3, 12, 59)), SessionPhase::Continuous);
+        assert_eq!(calendar.phase("AAPL", ny(3, 13, 2)), SessionPhase::ClosingAuction);
+        assert_eq!(calendar.phase("AAPL", ny(3, 14, 0)), SessionPhase::Closed);
+
+        // instruments without a session are never restricted
+        assert_eq!(calendar.phase("BTCUSD", ny(6, 3, 0)), SessionPhase::Continuous);
+        assert_eq!(calendar.session_name("AAPL"), Some("XNYS"));
+    }
+
+    #[test]
+    fn test_order_gating_and_flatten_window() {
+        let mut calendar = calendar();
+        assert!(!calendar.can_trade("AAPL", ny(2, 9, 0)));
+        assert!(calendar.can_trade("AAPL", ny(2, 15, 54)));
+        assert!(!calendar.can_trade("AAPL", ny(2, 15, 56)));
+        assert!(calendar.in_flatten_window("AAPL", ny(2, 15, 56)));
+
+        assert!(calendar.flatten_due(ny(2, 15, 54)).is_empty());
+        assert_eq!(calendar.flatten_due(ny(2, 15, 56)), vec!["XNYS".to_string()]);
+        assert!(calendar.flatten_due(ny(2, 15, 57)).is_empty());
+        // the early close moves the flatten window too
+        assert_eq!(calendar.flatten_due(ny(3, 12, 56)), vec!["XNYS".to_string()]);
+    }
+
+    #[test]
+    fn test_phase_change_events() {
+        let mut calendar = calendar();
+        assert_eq!(calendar.poll(ny(2, 9, 29))[0].to, SessionPhase::OpeningAuction);
+        assert!(calendar.poll(ny(2, 9, 29)).is_empty());
+        assert_eq!(
+            calendar.poll(ny(2, 9, 30)),
+            vec![PhaseChange {
+                session: "XNYS".to_string(),
+                from: SessionPhase::OpeningAuction,
+                to: SessionPhase::Continuous,
+            }]
+        );
+    }
+
+    #[test]
+    fn test_invalid_sessions() {
+        let mut config = CalendarConfig::default();
+        config.sessions.push(SessionConfig {
+            name: "BAD".to_string(),
+            timezone: "Mars/Olympus".to_string(),
+            symbols: Vec::new(),
+            weekends: false,
+            holidays: Vec::new(),
+            half_days: Vec::new(),
+            phases: vec![phase(SessionPhase::Continuous, "09:30", "16:00")],
+        });
+        assert!(TradingCalendar::from_config(&config).is_err());
+
+        config.sessions[0].timezone = "UTC".to_string();
+        config.sessions[0].phases.push(phase(SessionPhase::ClosingAuction, "15:55", "16:05"));
+        assert!(TradingCalendar::from_config(&config).is_err());
+    }
+}
@@ -1,6 +1,7 @@
 use serde::Deserialize;
-use std::{path::PathBuf};
-use anyhow::{Context, Result};
+use std::{collections::BTreeMap, path::PathBuf};
+use anyhow::{bail, Context, Result};
+use crate::calendar::SessionPhase;
 
 #[derive(Debug, Deserialize)]
 pub struct Config {
@@ -10,11 +11,30 @@ pub struct Config {
     pub risk: RiskConfig,
     pub order_execution: OrderExecutionConfig,
     pub logging: LoggingConfig,
+    #[serde(default)]
+    pub storage: Option<StorageConfig>,
+    #[serde(default)]
+    pub warmup: Option<WarmupConfig>,
+    #[serde(default)]
+    pub data_quality: DataQualityConfig,
+    #[serde(default)]
+    pub trade_tape: TradeTapeConfig,
+    #[serde(default)]
+    pub instruments: Option<InstrumentsConfig>,
+    #[serde(default)]
+    pub calendar: CalendarConfig,
+    #[serde(default)]
+    pub execution: ExecutionConfig,
+    #[serde(default)]
+    pub reload: ReloadConfig,
 }
 
 #[derive(Debug, Deserialize)]
 pub struct ExchangeConfig {
     pub address: String,
+    // venue name the connection's quotes are attributed to in the consolidated book
+    #[serde(default = "default_venue")]
+    pub venue: String,
     pub protocol: String,
     pub heartbeat_interval: u64,
     pub max_retries: u32,
@@ -23,17 +43,70 @@ pub struct ExchangeConfig {
 
 #[derive(Debug, Deserialize)]
 pub struct MarketDataConfig {
+    // csv, json, fix_snapshot, fix_incremental, binary or auto
     pub format: String,
     pub aggregation_window: usize,
+    // when set, the aggregation window covers this many milliseconds of event time
+    // instead of `aggregation_window` ticks
+    #[serde(default)]
+    pub aggregation_window_ms: Option<u64>,
     pub use_wma: bool,
+    #[serde(default)]
+    pub indicators: Vec<IndicatorConfig>,
+}
+
+// a named indicator computed by the market data aggregator, e.g.
+// [[market_data.indicators]]
+// name = "ema_fast"
+// symbol = "AAPL"
+// kind = "ema"
+// period = 12
+#[derive(Debug, Clone, Deserialize)]
+pub struct IndicatorConfig {
+    pub name: String,
+    // instrument whose ticks feed the indicator; every tick when unset, for feeds of a single instrument
+    #[serde(default)]
+    pub symbol: Option<String>,
+    #[serde(flatten)]
+    pub kind: IndicatorKind,
 }
 
+#[derive(Debug, Clone, Deserialize, PartialEq)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum IndicatorKind {
+    Sma { period: usize },
+    Ema { period: usize },
+    Wma { period: usize },
+    Vwap { period: usize },
+    StdDev { period: usize },
+    ZScore { period: usize },
+    Bollinger {
+        period: usize,
+        #[serde(default = "default_bollinger_k")]
+        k: f64,
+    },
+    Atr { period: usize },
+    Rsi { period: usize },
+    Macd { fast: usize, slow: usize, signal: usize },
+}
+
+// `name` selects the strategy; its parameters live in a table of the same name,
+// e.g. `[strategy.sma]`, and are checked by the strategy registry
 #[derive(Debug, Deserialize)]
 pub struct StrategyConfig {
     pub name: String,
-    pub long_period: usize,    // for TrendFollowingStrategy
-    pub short_period: usize,   // for TrendFollowingStrategy
-    pub window_size: usize,    // for SimpleMovingAverageStrategy
+    #[serde(default = "default_bar_interval_ms")]
+    pub bar_interval_ms: u64,  // length of the bars passed to `on_bar`
+    #[serde(default = "default_timer_interval_ms")]
+    pub timer_interval_ms: u64, // period of `on_timer`
+    #[serde(default)]
+    pub snapshot_path: Option<PathBuf>, // strategy state kept across restarts, off when unset
+    #[serde(default = "default_snapshot_interval_secs")]
+    pub snapshot_interval_secs: u64,
+    #[serde(default = "default_snapshot_max_age_secs")]
+    pub snapshot_max_age_secs: u64, // older snapshots are not restored
+    #[serde(flatten)]
+    pub params: BTreeMap<String, toml::Value>,
 }
 
 #[derive(Debug, Deserialize)]
@@ -44,6 +117,25 @@ pub struct RiskConfig {
     pub initial_capital: f64,
 }
 
+impl RiskConfig {
+    /// limits the risk manager can work with
+    pub fn validate(&self) -> Result<()> {
+        if self.max_position_size == 0 {
+            bail!("max_position_size must be positive");
+        }
+        if !(self.max_loss_per_trade.is_finite() && self.max_loss_per_trade > 0.0) {
+            bail!("max_loss_per_trade must be positive, got {}", self.max_loss_per_trade);
+        }
+        if !(self.stop_loss_percentage.is_finite() && (0.0..1.0).contains(&self.stop_loss_percentage)) {
+            bail!("stop_loss_percentage must be in [0, 1), got {}", self.stop_loss_percentage);
+        }
+        if !(self.initial_capital.is_finite() && self.initial_capital > 0.0) {
+            bail!("initial_capital must be positive, got {}", self.initial_capital);
+        }
+        Ok(())
+    }
+}
+
 #[derive(Debug, Deserialize)]
 pub struct OrderExecutionConfig {
     pub address: String,
@@ -52,6 +144,193 @@ pub struct OrderExecutionConfig {
     pub response_timeout_secs: u64,
 }
 
+// guards applied to every tick before it reaches the strategies
+#[derive(Debug, Clone, Deserialize)]
+pub struct DataQualityConfig {
+    // an instrument without updates for this long is untradeable
+    #[serde(default = "default_stale_after_ms")]
+    pub stale_after_ms: u64,
+    // maximum relative move from the last good price, e.g. 0.05 = 5%
+    #[serde(default = "default_max_price_deviation")]
+    pub max_price_deviation: f64,
+    // consecutive ticks needed to accept a move beyond the band as a new price level
+    #[serde(default = "default_spike_confirmations")]
+    pub spike_confirmations: u32,
+    // treat bid == ask as bad data, not just bid > ask
+    #[serde(default)]
+    pub reject_locked_books: bool,
+}
+
+impl Default for DataQualityConfig {
+    fn default() -> Self {
+        DataQualityConfig {
+            stale_after_ms: default_stale_after_ms(),
+            max_price_deviation: default_max_price_deviation(),
+            spike_confirmations: default_spike_confirmations(),
+            reject_locked_books: false,
+        }
+    }
+}
+
+// time and sales with rolling trade-flow metrics
+#[derive(Debug, Clone, Deserialize)]
+pub struct TradeTapeConfig {
+    // prints kept per instrument
+    #[serde(default = "default_tape_capacity")]
+    pub capacity: usize,
+    // window for buy/sell volume, imbalance and intensity
+    #[serde(default = "default_flow_window_ms")]
+    pub flow_window_ms: u64,
+}
+
+impl Default for TradeTapeConfig {
+    fn default() -> Self {
+        TradeTapeConfig {
+            capacity: default_tape_capacity(),
+            flow_window_ms: default_flow_window_ms(),
+        }
+    }
+}
+
+// instrument reference data file (.toml with [[instrument]] tables or .csv with a header row)
+#[derive(Debug, Deserialize)]
+pub struct InstrumentsConfig {
+    pub path: PathBuf,
+}
+
+// trading sessions; without sessions the bot trades whenever ticks arrive
+#[derive(Debug, Clone, Deserialize)]
+pub struct CalendarConfig {
+    #[serde(default)]
+    pub sessions: Vec<SessionConfig>,
+    // phases in which new orders may be sent
+    #[serde(default = "default_order_phases")]
+    pub order_phases: Vec<SessionPhase>,
+    // close positions and stop opening new ones this many minutes before the close
+    #[serde(default)]
+    pub flatten_before_close_min: Option<u64>,
+}
+
+impl Default for CalendarConfig {
+    fn default() -> Self {
+        CalendarConfig {
+            sessions: Vec::new(),
+            order_phases: default_order_phases(),
+            flatten_before_close_min: None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct SessionConfig {
+    pub name: String,
+    // IANA timezone name, e.g. "America/New_York"
+    pub timezone: String,
+    // instruments following this session; a session without symbols applies to all others
+    #[serde(default)]
+    pub symbols: Vec<String>,
+    // trade on saturdays and sundays too
+    #[serde(default)]
+    pub weekends: bool,
+    // "YYYY-MM-DD" dates without trading
+    #[serde(default)]
+    pub holidays: Vec<String>,
+    #[serde(default)]
+    pub half_days: Vec<HalfDayConfig>,
+    pub phases: Vec<PhaseConfig>,
+}
+
+// local "HH:MM" times, the end is exclusive
+#[derive(Debug, Clone, Deserialize)]
+pub struct PhaseConfig {
+    pub phase: SessionPhase,
+    pub start: String,
+    pub end: String,
+}
+
+// early close: continuous trading ends at `close`, later phases move up accordingly
+#[derive(Debug, Clone, Deserialize)]
+pub struct HalfDayConfig {
+    pub date: String,
+    pub close: String,
+}
+
+// execution algorithms and emulated order types working strategy parent orders as child orders
+#[derive(Debug, Clone, Deserialize)]
+pub struct ExecutionConfig {
+    // relative volume per equal bucket of the UTC day (e.g. 48 half hours), followed by VWAP
+    #[serde(default)]
+    pub volume_curve: Vec<f64>,
+    // a child still working after this long is cancelled and re-sliced
+    #[serde(default = "default_child_timeout_ms")]
+    pub child_timeout_ms: u64,
+    // consecutive child rejects after which the parent order is abandoned
+    #[serde(default = "default_max_child_rejects")]
+    pub max_child_rejects: u32,
+    // pegged orders are amended at most this often
+    #[serde(default = "default_peg_amend_interval_ms")]
+    pub peg_amend_interval_ms: u64,
+    // and only when the pegged price moved at least this far
+    #[serde(default)]
+    pub peg_min_price_change: f64,
+}
+
+impl Default for ExecutionConfig {
+    fn default() -> Self {
+        ExecutionConfig {
+            volume_curve: Vec::new(),
+            child_timeout_ms: default_child_timeout_ms(),
+            max_child_rejects: default_max_child_rejects(),
+            peg_amend_interval_ms: default_peg_amend_interval_ms(),
+            peg_min_price_change: 0.0,
+        }
+    }
+}
+
+// on-disk tick store fed by the live market data stream
+#[derive(Debug, Deserialize)]
+pub struct StorageConfig {
+    pub tick_store_path: PathBuf,
+    #[serde(default = "default_block_size")]
+    pub block_size: usize,
+}
+
+// edits to `[strategy]` and `[risk]` applied while running; SIGHUP reloads regardless of `watch`
+#[derive(Debug, Clone, Deserialize)]
+pub struct ReloadConfig {
+    #[serde(default = "default_reload_watch")]
+    pub watch: bool, // poll the config file for changes
+    #[serde(default = "default_reload_check_interval_ms")]
+    pub check_interval_ms: u64,
+}
+
+impl Default for ReloadConfig {
+    fn default() -> Self {
+        ReloadConfig { watch: default_reload_watch(), check_interval_ms: default_reload_check_interval_ms() }
+    }
+}
+
+// recorded market data replayed through indicators and the strategy before going live
+#[derive(Debug, Deserialize)]
+pub struct WarmupConfig {
+    // recorded file; the `[storage]` tick store when unset
+    pub path: Option<PathBuf>,
+    // any `[market_data] format` for `path`, that of the live feed when unset
+    pub format: Option<String>,
+    pub last: usize, // per symbol, in `unit`s
+    #[serde(default)]
+    pub unit: WarmupUnit,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum WarmupUnit {
+    #[default]
+    Ticks,
+    // bars of `[strategy] bar_interval_ms`
+    Bars,
+}
+
 #[derive(Debug, Deserialize)]
 pub struct LoggingConfig {
     #[serde(default = "default_console_level")]
@@ -64,6 +343,10 @@ pub struct LoggingConfig {
     pub file_path: Option<PathBuf>,
 }
 
+fn default_venue() -> String {
+    "primary".to_string()
+}
+
 fn default_console_level() -> String {
     "info".to_string()
 }
@@ -72,6 +355,74 @@ fn default_file_level() -> String {
     "debug".to_string()
 }
 
+fn default_stale_after_ms() -> u64 {
+    5_000
+}
+
+fn default_max_price_deviation() -> f64 {
+    0.05
+}
+
+fn default_spike_confirmations() -> u32 {
+    3
+}
+
+fn default_tape_capacity() -> usize {
+    1000
+}
+
+fn default_flow_window_ms() -> u64 {
+    1000
+}
+
+fn default_bar_interval_ms() -> u64 {
+    60_000
+}
+
+fn default_timer_interval_ms() -> u64 {
+    1_000
+}
+
+fn default_snapshot_interval_secs() -> u64 {
+    60
+}
+
+fn default_snapshot_max_age_secs() -> u64 {
+    300
+}
+
+fn default_reload_watch() -> bool {
+    true
+}
+
+fn default_reload_check_interval_ms() -> u64 {
+    1_000
+}
+
+fn default_order_phases() -> Vec<SessionPhase> {
+    vec![SessionPhase::Continuous]
+}
+
+fn default_child_timeout_ms() -> u64 {
+    5_000
+}
+
+fn default_max_child_rejects() -> u32 {
+    3
+}
+
+fn default_peg_amend_interval_ms() -> u64 {
+    100
+}
+
+fn default_block_size() -> usize {
+    1024
+}
+
+fn default_bollinger_k() -> f64 {
+    2.0
+}
+
 impl Config {
     pub fn load(path: &str) -> Result<Self> {
         let config_content = std::fs::read_to_string(path)
@@ -2,6 +2,9 @@ use log::{debug, error};
 use std::io::{Cursor, Read, Write};
 use byteorder::{ReadBytesExt, WriteBytesExt, LittleEndian};
 
+// message type of a market data tick on the binary feed
+pub const MARKET_DATA_TICK: u8 = 2;
+
 #[derive(Debug, Clone)]
 pub struct BinaryMessage {
     pub message_type: u8,
@@ -7,10 +7,14 @@ use tokio::time::sleep;
 
 use crate::exchange::{binary};
 
+const READ_BUFFER_SIZE: usize = 1024;
+
 pub struct ExchangeConnection {
     stream: TcpStream,
     address: String,
     protocol: String,
+    // reused by `receive_bytes` so the market data path does not allocate per message
+    read_buffer: Vec<u8>,
 }
 
 impl ExchangeConnection {
@@ -18,7 +22,12 @@ impl ExchangeConnection {
         info!("Connecting to exchange at: {} using {} protocol", address, protocol);
         let stream = Self::connect(address).await?;
         info!("Connected to exchange.");
-        Ok(Self { stream, address: address.to_string(), protocol: protocol.to_string() })
+        Ok(Self {
+            stream,
+            address: address.to_string(),
+            protocol: protocol.to_string(),
+            read_buffer: vec![0; READ_BUFFER_SIZE],
+        })
     }
 
     async fn connect(address: &str) -> Result<TcpStream, Box<dyn std::error::Error>> {
@@ -109,6 +118,12 @@ impl ExchangeConnection {
         let n = self.stream.read(&mut buffer).await?;
         Ok(String::from_utf8_lossy(&buffer[..n]).to_string())
     }
+
+    // raw bytes of the next read, borrowed from the connection's read buffer
+    pub async fn receive_bytes(&mut self) -> anyhow::Result<&[u8]> {
+        let n = self.stream.read(&mut self.read_buffer).await?;
+        Ok(&self.read_buffer[..n])
+    }
 }
 
 
@@ -147,8 +162,9 @@ mod tests {
 
         tokio::time::sleep(tokio::time::Duration::from_millis(100)).await;
 
+        // send_message terminates every message with a newline
         let received = connection.receive_message().await?;
-        assert_eq!(received, message);
+        assert_eq!(received, format!("{}\n", message));
 
         Ok(())
     }
@@ -0,0 +1,88 @@
+pub mod moving_average;
+pub mod volatility;
+pub mod oscillators;
+
+use crate::config::IndicatorKind;
+use crate::market_data::parser::Tick;
+
+// common interface for incremental technical indicators.
+// every implementation keeps running state so that `update` is O(1) per tick.
+pub trait Indicator {
+    /// feeds a new tick and returns the updated value once the indicator is warmed up
+    fn update(&mut self, tick: &Tick) -> Option<f64>;
+
+    /// current value, `None` until enough data has been seen
+    fn value(&self) -> Option<f64>;
+
+    /// named output for indicators that produce more than one series
+    /// (e.g. "upper"/"lower" for bollinger bands). "value" is always the primary output.
+    fn output(&self, field: &str) -> Option<f64> {
+        match field {
+            "value" => self.value(),
+            _ => None,
+        }
+    }
+
+    /// fields `output` answers to, "value" first
+    fn outputs(&self) -> &'static [&'static str] {
+        &["value"]
+    }
+}
+
+// builds an indicator from its config description
+pub fn from_config(kind: &IndicatorKind) -> Result<Box<dyn Indicator>, Box<dyn std::error::Error>> {
+    let indicator: Box<dyn Indicator> = match *kind {
+        IndicatorKind::Sma { period } => Box::new(Sma::new(check_period(period)?)),
+        IndicatorKind::Ema { period } => Box::new(Ema::new(check_period(period)?)),
+        IndicatorKind::Wma { period } => Box::new(Wma::new(check_period(period)?)),
+        IndicatorKind::Vwap { period } => Box::new(Vwap::new(check_period(period)?)),
+        IndicatorKind::StdDev { period } => Box::new(RollingStdDev::new(check_period(period)?)),
+        IndicatorKind::ZScore { period } => Box::new(ZScore::new(check_period(period)?)),
+        IndicatorKind::Bollinger { period, k } => {
+            Box::new(BollingerBands::new(check_period(period)?, k))
+        }
+        IndicatorKind::Atr { period } => Box::new(Atr::new(check_period(period)?)),
+        IndicatorKind::Rsi { period } => Box::new(Rsi::new(check_period(period)?)),
+        IndicatorKind::Macd { fast, slow, signal } => {
+            if fast >= slow {
+                return Err("MACD fast period must be shorter than the slow period".into());
+            }
+            Box::new(Macd::new(
+                check_period(fast)?,
+                check_period(slow)?,
+                check_period(signal)?,
+            ))
+        }
+    };
+    Ok(indicator)
+}
+
+fn check_period(period: usize) -> Result<usize, Box<dyn std::error::Error>> {
+    if period == 0 {
+        return Err("Indicator period must be greater than zero".into());
+    }
+    Ok(period)
+}
+
+// re-export indicators for easy access
+pub use moving_average::{Ema, Sma, Vwap, Wma};
+pub use oscillators::{Macd, Rsi};
+pub use volatility::{Atr, BollingerBands, RollingStdDev, ZScore};
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_from_config() {
+        let mut sma = from_config(&IndicatorKind::Sma { period: 2 }).unwrap();
+        sma.update(&Tick { price: 10.0, volume: 1, ..Default::default() });
+        sma.update(&Tick { price: 12.0, volume: 1, ..Default::default() });
+        assert_eq!(sma.value(), Some(11.0));
+        assert_eq!(sma.output("value"), Some(11.0));
+        assert_eq!(sma.output("upper"), None);
+
+        assert!(from_config(&IndicatorKind::Ema { period: 0 }).is_err());
+        assert!(from_config(&IndicatorKind::Macd { fast: 26, slow: 12, signal: 9 }).is_err());
+    }
+}
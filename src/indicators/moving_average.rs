@@ -0,0 +1,229 @@
+use super::Indicator;
+use crate::market_data::parser::Tick;
+use std::collections::VecDeque;
+
+// simple moving average with a running sum
+pub struct Sma {
+    period: usize,
+    window: VecDeque<f64>,
+    sum: f64,
+}
+
+impl Sma {
+    pub fn new(period: usize) -> Self {
+        Sma {
+            period,
+            window: VecDeque::with_capacity(period + 1),
+            sum: 0.0,
+        }
+    }
+
+    pub fn push(&mut self, price: f64) -> Option<f64> {
+        self.window.push_back(price);
+        self.sum += price;
+        if self.window.len() > self.period {
+            if let Some(oldest) = self.window.pop_front() {
+                self.sum -= oldest;
+            }
+        }
+        self.value()
+    }
+
+    // prices in the window, oldest first
+    pub fn window(&self) -> impl Iterator<Item = f64> + '_ {
+        self.window.iter().copied()
+    }
+}
+
+impl Indicator for Sma {
+    fn update(&mut self, tick: &Tick) -> Option<f64> {
+        self.push(tick.price)
+    }
+
+    fn value(&self) -> Option<f64> {
+        if self.window.len() < self.period {
+            return None;
+        }
+        Some(self.sum / self.period as f64)
+    }
+}
+
+// exponential moving average, seeded with the SMA of the first `period` values
+pub struct Ema {
+    period: usize,
+    alpha: f64,
+    count: usize,
+    seed_sum: f64,
+    ema: Option<f64>,
+}
+
+impl Ema {
+    pub fn new(period: usize) -> Self {
+        Ema {
+            period,
+            alpha: 2.0 / (period as f64 + 1.0),
+            count: 0,
+            seed_sum: 0.0,
+            ema: None,
+        }
+    }
+
+    pub fn push(&mut self, value: f64) -> Option<f64> {
+        match self.ema {
+            Some(prev) => {
+                self.ema = Some(prev + self.alpha * (value - prev));
+            }
+            None => {
+                self.count += 1;
+                self.seed_sum += value;
+                if self.count >= self.period {
+                    self.ema = Some(self.seed_sum / self.period as f64);
+                }
+            }
+        }
+        self.ema
+    }
+}
+
+impl Indicator for Ema {
+    fn update(&mut self, tick: &Tick) -> Option<f64> {
+        self.push(tick.price)
+    }
+
+    fn value(&self) -> Option<f64> {
+        self.ema
+    }
+}
+
+// linearly weighted moving average (newest price has weight `period`, oldest has weight 1).
+// keeps both the plain and the weighted sum so that sliding the window is O(1).
+pub struct Wma {
+    period: usize,
+    window: VecDeque<f64>,
+    sum: f64,
+    weighted_sum: f64,
+}
+
+impl Wma {
+    pub fn new(period: usize) -> Self {
+        Wma {
+            period,
+            window: VecDeque::with_capacity(period + 1),
+            sum: 0.0,
+            weighted_sum: 0.0,
+        }
+    }
+
+    pub fn push(&mut self, price: f64) -> Option<f64> {
+        if self.window.len() == self.period {
+            // every remaining price loses one unit of weight, the new one enters with full weight
+            self.weighted_sum += self.period as f64 * price - self.sum;
+            if let Some(oldest) = self.window.pop_front() {
+                self.sum -= oldest;
+            }
+        } else {
+            self.weighted_sum += (self.window.len() + 1) as f64 * price;
+        }
+        self.window.push_back(price);
+        self.sum += price;
+        self.value()
+    }
+}
+
+impl Indicator for Wma {
+    fn update(&mut self, tick: &Tick) -> Option<f64> {
+        self.push(tick.price)
+    }
+
+    fn value(&self) -> Option<f64> {
+        if self.window.len() < self.period {
+            return None;
+        }
+        let total_weight = (self.period * (self.period + 1)) as f64 / 2.0;
+        Some(self.weighted_sum / total_weight)
+    }
+}
+
+// volume weighted average price over the last `period` ticks
+pub struct Vwap {
+    period: usize,
+    window: VecDeque<(f64, u64)>,
+    price_volume: f64,
+    volume: u64,
+}
+
+impl Vwap {
+    pub fn new(period: usize) -> Self {
+        Vwap {
+            period,
+            window: VecDeque::with_capacity(period + 1),
+            price_volume: 0.0,
+            volume: 0,
+        }
+    }
+}
+
+impl Indicator for Vwap {
+    fn update(&mut self, tick: &Tick) -> Option<f64> {
+        self.window.push_back((tick.price, tick.volume));
+        self.price_volume += tick.price * tick.volume as f64;
+        self.volume += tick.volume;
+        if self.window.len() > self.period {
+            if let Some((price, volume)) = self.window.pop_front() {
+                self.price_volume -= price * volume as f64;
+                self.volume -= volume;
+            }
+        }
+        self.value()
+    }
+
+    fn value(&self) -> Option<f64> {
+        if self.volume == 0 {
+            return None;
+        }
+        Some(self.price_volume / self.volume as f64)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn assert_close(actual: Option<f64>, expected: f64) {
+        let actual = actual.expect("indicator should be ready");
+        assert!((actual - expected).abs() < 1e-9, "expected {}, got {}", expected, actual);
+    }
+
+    #[test]
+    fn test_sma_and_wma() {
+        let mut sma = Sma::new(3);
+        let mut wma = Wma::new(3);
+        for price in [10.0, 11.0] {
+            assert_eq!(sma.push(price), None);
+            assert_eq!(wma.push(price), None);
+        }
+        assert_close(sma.push(12.0), 11.0);
+        assert_close(wma.push(12.0), (10.0 + 22.0 + 36.0) / 6.0);
+        assert_close(sma.push(13.0), 12.0);
+        assert_close(wma.push(13.0), (11.0 + 24.0 + 39.0) / 6.0);
+    }
+
+    #[test]
+    fn test_ema() {
+        let mut ema = Ema::new(3);
+        assert_eq!(ema.push(10.0), None);
+        assert_eq!(ema.push(11.0), None);
+        assert_close(ema.push(12.0), 11.0); // seeded with the SMA
+        assert_close(ema.push(13.0), 12.0); // 11 + 0.5 * (13 - 11)
+    }
+
+    #[test]
+    fn test_vwap() {
+        let mut vwap = Vwap::new(2);
+        vwap.update(&Tick { price: 10.0, volume: 100, ..Default::default() });
+        vwap.update(&Tick { price: 11.0, volume: 300, ..Default::default() });
+        assert_close(vwap.value(), 10.75);
+        vwap.update(&Tick { price: 12.0, volume: 100, ..Default::default() });
+        assert_close(vwap.value(), 11.25); // first tick has left the window
+    }
+}
@@ -0,0 +1,162 @@
+use super::moving_average::Ema;
+use super::Indicator;
+use crate::market_data::parser::Tick;
+
+// relative strength index with wilder smoothing of average gains and losses
+pub struct Rsi {
+    period: usize,
+    prev_price: Option<f64>,
+    count: usize,
+    avg_gain: f64,
+    avg_loss: f64,
+    ready: bool,
+}
+
+impl Rsi {
+    pub fn new(period: usize) -> Self {
+        Rsi {
+            period,
+            prev_price: None,
+            count: 0,
+            avg_gain: 0.0,
+            avg_loss: 0.0,
+            ready: false,
+        }
+    }
+
+    pub fn push(&mut self, price: f64) -> Option<f64> {
+        let prev = self.prev_price.replace(price)?;
+        let change = price - prev;
+        let (gain, loss) = (change.max(0.0), (-change).max(0.0));
+        let n = self.period as f64;
+
+        if self.ready {
+            self.avg_gain = (self.avg_gain * (n - 1.0) + gain) / n;
+            self.avg_loss = (self.avg_loss * (n - 1.0) + loss) / n;
+        } else {
+            // the first average is a plain mean of `period` changes
+            self.count += 1;
+            self.avg_gain += gain / n;
+            self.avg_loss += loss / n;
+            self.ready = self.count >= self.period;
+        }
+        self.value()
+    }
+}
+
+impl Indicator for Rsi {
+    fn update(&mut self, tick: &Tick) -> Option<f64> {
+        self.push(tick.price)
+    }
+
+    fn value(&self) -> Option<f64> {
+        if !self.ready {
+            return None;
+        }
+        if self.avg_loss == 0.0 {
+            return Some(if self.avg_gain == 0.0 { 50.0 } else { 100.0 });
+        }
+        let rs = self.avg_gain / self.avg_loss;
+        Some(100.0 - 100.0 / (1.0 + rs))
+    }
+}
+
+// moving average convergence/divergence.
+// the primary value is the MACD line (fast EMA - slow EMA); "signal" and "histogram" are extra outputs.
+pub struct Macd {
+    fast: Ema,
+    slow: Ema,
+    signal: Ema,
+    macd: Option<f64>,
+}
+
+impl Macd {
+    pub fn new(fast_period: usize, slow_period: usize, signal_period: usize) -> Self {
+        Macd {
+            fast: Ema::new(fast_period),
+            slow: Ema::new(slow_period),
+            signal: Ema::new(signal_period),
+            macd: None,
+        }
+    }
+
+    pub fn push(&mut self, price: f64) -> Option<f64> {
+        let fast = self.fast.push(price);
+        let slow = self.slow.push(price);
+        if let (Some(fast), Some(slow)) = (fast, slow) {
+            let macd = fast - slow;
+            self.macd = Some(macd);
+            self.signal.push(macd);
+        }
+        self.macd
+    }
+
+    pub fn signal(&self) -> Option<f64> {
+        self.signal.value()
+    }
+
+    pub fn histogram(&self) -> Option<f64> {
+        Some(self.macd? - self.signal()?)
+    }
+}
+
+impl Indicator for Macd {
+    fn update(&mut self, tick: &Tick) -> Option<f64> {
+        self.push(tick.price)
+    }
+
+    fn value(&self) -> Option<f64> {
+        self.macd
+    }
+
+    fn output(&self, field: &str) -> Option<f64> {
+        match field {
+            "value" | "macd" => self.value(),
+            "signal" => self.signal(),
+            "histogram" => self.histogram(),
+            _ => None,
+        }
+    }
+
+    fn outputs(&self) -> &'static [&'static str] {
+        &["value", "signal", "histogram"]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_rsi() {
+        let mut rsi = Rsi::new(2);
+        assert_eq!(rsi.push(10.0), None);
+        assert_eq!(rsi.push(11.0), None);
+        // avg gain = 0.5, avg loss = 0.5
+        assert_eq!(rsi.push(10.0), Some(50.0));
+        // avg gain = (0.5 + 2) / 2 = 1.25, avg loss = 0.25
+        let value = rsi.push(12.0).unwrap();
+        assert!((value - (100.0 - 100.0 / 6.0)).abs() < 1e-9);
+
+        let mut rising = Rsi::new(2);
+        for price in [1.0, 2.0, 3.0] {
+            rising.push(price);
+        }
+        assert_eq!(rising.value(), Some(100.0));
+    }
+
+    #[test]
+    fn test_macd() {
+        let mut macd = Macd::new(2, 3, 2);
+        assert_eq!(macd.push(10.0), None);
+        assert_eq!(macd.push(11.0), None);
+        // fast EMA = 11.5 (10.5 + 2/3 * 1.5), slow EMA = 11.0 (seed)
+        let line = macd.push(12.0).unwrap();
+        assert!((line - 0.5).abs() < 1e-9);
+        assert_eq!(macd.signal(), None);
+
+        macd.push(13.0);
+        assert!(macd.signal().is_some());
+        assert!(macd.output("histogram").is_some());
+    }
+}
@@ -0,0 +1,267 @@
+use super::Indicator;
+use crate::market_data::parser::Tick;
+use std::collections::VecDeque;
+
+// running sum and sum of squares over a fixed number of values
+struct RollingMoments {
+    period: usize,
+    window: VecDeque<f64>,
+    sum: f64,
+    sum_sq: f64,
+}
+
+impl RollingMoments {
+    fn new(period: usize) -> Self {
+        RollingMoments {
+            period,
+            window: VecDeque::with_capacity(period + 1),
+            sum: 0.0,
+            sum_sq: 0.0,
+        }
+    }
+
+    fn push(&mut self, value: f64) {
+        self.window.push_back(value);
+        self.sum += value;
+        self.sum_sq += value * value;
+        if self.window.len() > self.period {
+            if let Some(oldest) = self.window.pop_front() {
+                self.sum -= oldest;
+                self.sum_sq -= oldest * oldest;
+            }
+        }
+    }
+
+    fn is_full(&self) -> bool {
+        self.window.len() >= self.period
+    }
+
+    fn mean(&self) -> f64 {
+        self.sum / self.window.len() as f64
+    }
+
+    // population standard deviation; clamped at zero against rounding drift
+    fn std_dev(&self) -> f64 {
+        let n = self.window.len() as f64;
+        let variance = (self.sum_sq / n - (self.sum / n).powi(2)).max(0.0);
+        variance.sqrt()
+    }
+
+    fn last(&self) -> Option<f64> {
+        self.window.back().copied()
+    }
+}
+
+// rolling standard deviation of prices
+pub struct RollingStdDev {
+    moments: RollingMoments,
+}
+
+impl RollingStdDev {
+    pub fn new(period: usize) -> Self {
+        RollingStdDev { moments: RollingMoments::new(period) }
+    }
+}
+
+impl Indicator for RollingStdDev {
+    fn update(&mut self, tick: &Tick) -> Option<f64> {
+        self.moments.push(tick.price);
+        self.value()
+    }
+
+    fn value(&self) -> Option<f64> {
+        self.moments.is_full().then(|| self.moments.std_dev())
+    }
+}
+
+// distance of the latest price from the rolling mean, in standard deviations
+pub struct ZScore {
+    moments: RollingMoments,
+}
+
+impl ZScore {
+    pub fn new(period: usize) -> Self {
+        ZScore { moments: RollingMoments::new(period) }
+    }
+
+    pub fn push(&mut self, value: f64) -> Option<f64> {
+        self.moments.push(value);
+        self.value()
+    }
+}
+
+impl Indicator for ZScore {
+    fn update(&mut self, tick: &Tick) -> Option<f64> {
+        self.push(tick.price)
+    }
+
+    fn value(&self) -> Option<f64> {
+        if !self.moments.is_full() {
+            return None;
+        }
+        let std_dev = self.moments.std_dev();
+        if std_dev == 0.0 {
+            return Some(0.0);
+        }
+        self.moments.last().map(|last| (last - self.moments.mean()) / std_dev)
+    }
+}
+
+// bollinger bands: SMA middle band with upper/lower bands `k` standard deviations away.
+// the primary value is the middle band; "upper", "lower" and "bandwidth" are extra outputs.
+pub struct BollingerBands {
+    moments: RollingMoments,
+    k: f64,
+}
+
+impl BollingerBands {
+    pub fn new(period: usize, k: f64) -> Self {
+        BollingerBands { moments: RollingMoments::new(period), k }
+    }
+
+    pub fn push(&mut self, price: f64) -> Option<f64> {
+        self.moments.push(price);
+        self.value()
+    }
+
+    /// (lower, middle, upper)
+    pub fn bands(&self) -> Option<(f64, f64, f64)> {
+        if !self.moments.is_full() {
+            return None;
+        }
+        let middle = self.moments.mean();
+        let offset = self.k * self.moments.std_dev();
+        Some((middle - offset, middle, middle + offset))
+    }
+
+    pub fn upper(&self) -> Option<f64> {
+        self.bands().map(|(_, _, upper)| upper)
+    }
+
+    pub fn lower(&self) -> Option<f64> {
+        self.bands().map(|(lower, _, _)| lower)
+    }
+}
+
+impl Indicator for BollingerBands {
+    fn update(&mut self, tick: &Tick) -> Option<f64> {
+        self.push(tick.price)
+    }
+
+    fn value(&self) -> Option<f64> {
+        self.bands().map(|(_, middle, _)| middle)
+    }
+
+    fn output(&self, field: &str) -> Option<f64> {
+        match field {
+            "value" | "middle" => self.value(),
+            "upper" => self.upper(),
+            "lower" => self.lower(),
+            "bandwidth" => self.bands().map(|(lower, middle, upper)| (upper - lower) / middle),
+            _ => None,
+        }
+    }
+
+    fn outputs(&self) -> &'static [&'static str] {
+        &["value", "upper", "lower", "bandwidth"]
+    }
+}
+
+// average true range with wilder smoothing.
+// ticks carry no high/low, so the true range is the absolute price change between ticks.
+pub struct Atr {
+    period: usize,
+    prev_close: Option<f64>,
+    count: usize,
+    seed_sum: f64,
+    atr: Option<f64>,
+}
+
+impl Atr {
+    pub fn new(period: usize) -> Self {
+        Atr {
+            period,
+            prev_close: None,
+            count: 0,
+            seed_sum: 0.0,
+            atr: None,
+        }
+    }
+
+    fn push_true_range(&mut self, true_range: f64) {
+        match self.atr {
+            Some(prev) => {
+                let n = self.period as f64;
+                self.atr = Some((prev * (n - 1.0) + true_range) / n);
+            }
+            None => {
+                self.count += 1;
+                self.seed_sum += true_range;
+                if self.count >= self.period {
+                    self.atr = Some(self.seed_sum / self.period as f64);
+                }
+            }
+        }
+    }
+}
+
+impl Indicator for Atr {
+    fn update(&mut self, tick: &Tick) -> Option<f64> {
+        match self.prev_close.replace(tick.price) {
+            Some(prev) => self.push_true_range((tick.price - prev).abs()),
+            None => return None, // first tick only establishes the reference price
+        }
+        self.atr
+    }
+
+    fn value(&self) -> Option<f64> {
+        self.atr
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn tick(price: f64) -> Tick {
+        Tick { price, volume: 1, ..Default::default() }
+    }
+
+    #[test]
+    fn test_std_dev_and_z_score() {
+        let mut std_dev = RollingStdDev::new(4);
+        let mut z_score = ZScore::new(4);
+        for price in [2.0, 4.0, 4.0, 6.0] {
+            std_dev.update(&tick(price));
+            z_score.update(&tick(price));
+        }
+        assert!((std_dev.value().unwrap() - 2.0f64.sqrt()).abs() < 1e-9);
+        assert!((z_score.value().unwrap() - 2.0 / 2.0f64.sqrt()).abs() < 1e-9);
+
+        // flat prices have no dispersion
+        let mut flat = ZScore::new(2);
+        flat.push(5.0);
+        assert_eq!(flat.push(5.0), Some(0.0));
+    }
+
+    #[test]
+    fn test_bollinger_bands() {
+        let mut bands = BollingerBands::new(4, 2.0);
+        for price in [2.0, 4.0, 4.0] {
+            assert_eq!(bands.update(&tick(price)), None);
+        }
+        assert_eq!(bands.update(&tick(6.0)), Some(4.0));
+        let offset = 2.0 * 2.0f64.sqrt();
+        assert!((bands.output("upper").unwrap() - (4.0 + offset)).abs() < 1e-9);
+        assert!((bands.output("lower").unwrap() - (4.0 - offset)).abs() < 1e-9);
+    }
+
+    #[test]
+    fn test_atr() {
+        let mut atr = Atr::new(2);
+        assert_eq!(atr.update(&tick(10.0)), None);
+        assert_eq!(atr.update(&tick(11.0)), None);
+        assert_eq!(atr.update(&tick(10.0)), Some(1.0));
+        assert_eq!(atr.update(&tick(13.0)), Some(2.0)); // (1 * 1 + 3) / 2
+    }
+}
@@ -0,0 +1,292 @@
+use crate::strategy::{Order, OrderSide};
+use log::{debug, info};
+use serde::Deserialize;
+use std::collections::HashMap;
+use std::fmt;
+use std::path::Path;
+
+// reference data for a tradeable instrument
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+pub struct Instrument {
+    pub symbol: String,
+    // symbol the venue expects on orders, defaults to `symbol`
+    #[serde(default)]
+    pub venue_symbol: Option<String>,
+    pub tick_size: f64,
+    #[serde(default = "default_lot_size")]
+    pub lot_size: u64,
+    #[serde(default = "default_lot_size")]
+    pub min_quantity: u64,
+    #[serde(default = "default_max_quantity")]
+    pub max_quantity: u64,
+    // decimals sent on the wire, derived from the tick size when omitted
+    #[serde(default)]
+    pub price_precision: Option<u32>,
+    pub currency: String,
+    #[serde(default = "default_multiplier")]
+    pub multiplier: f64,
+}
+
+fn default_lot_size() -> u64 {
+    1
+}
+
+fn default_max_quantity() -> u64 {
+    u64::MAX
+}
+
+fn default_multiplier() -> f64 {
+    1.0
+}
+
+// reasons an order does not fit the instrument's trading rules
+#[derive(Debug, Clone, PartialEq)]
+pub enum InstrumentViolation {
+    UnknownInstrument(String),
+    InvalidPrice(f64),
+    BelowMinQuantity { quantity: u64, min: u64 },
+    AboveMaxQuantity { quantity: u64, max: u64 },
+}
+
+impl fmt::Display for InstrumentViolation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            InstrumentViolation::UnknownInstrument(symbol) => write!(f, "unknown instrument {}", symbol),
+            InstrumentViolation::InvalidPrice(price) => write!(f, "invalid price {}", price),
+            InstrumentViolation::BelowMinQuantity { quantity, min } => {
+                write!(f, "quantity {} below minimum {}", quantity, min)
+            }
+            InstrumentViolation::AboveMaxQuantity { quantity, max } => {
+                write!(f, "quantity {} above maximum {}", quantity, max)
+            }
+        }
+    }
+}
+
+impl std::error::Error for InstrumentViolation {}
+
+impl Instrument {
+    pub fn venue_symbol(&self) -> &str {
+        self.venue_symbol.as_deref().unwrap_or(&self.symbol)
+    }
+
+    pub fn price_precision(&self) -> u32 {
+        self.price_precision.unwrap_or_else(|| {
+            // number of decimals needed to represent the tick size, e.g. 0.005 -> 3
+            let mut precision = 0;
+            while precision < 10 && (self.tick_size * 10f64.powi(precision)).fract().abs() > 1e-9 {
+                precision += 1;
+            }
+            precision as u32
+        })
+    }
+
+    /// rounds to the tick grid on the passive side (buys down, sells up) and to the price precision
+    pub fn round_price(&self, price: f64, side: OrderSide) -> f64 {
+        let ticks = price / self.tick_size;
+        // prices already on the grid must not move a tick because of float noise
+        let ticks = match side {
+            OrderSide::Buy => (ticks + 1e-9).floor(),
+            OrderSide::Sell => (ticks - 1e-9).ceil(),
+        };
+        let scale = 10f64.powi(self.price_precision() as i32);
+        (ticks * self.tick_size * scale).round() / scale
+    }
+
+    /// rounds down to a whole number of lots
+    pub fn round_quantity(&self, quantity: u64) -> u64 {
+        quantity - quantity % self.lot_size
+    }
+
+    fn validate(&self) -> Result<(), Box<dyn std::error::Error>> {
+        if self.symbol.is_empty() {
+            return Err("Instrument with empty symbol".into());
+        }
+        if !(self.tick_size.is_finite() && self.tick_size > 0.0) {
+            return Err(format!("{}: tick_size must be positive", self.symbol).into());
+        }
+        if self.lot_size == 0 {
+            return Err(format!("{}: lot_size must be positive", self.symbol).into());
+        }
+        if self.min_quantity > self.max_quantity {
+            return Err(format!("{}: min_quantity above max_quantity", self.symbol).into());
+        }
+        if !(self.multiplier.is_finite() && self.multiplier > 0.0) {
+            return Err(format!("{}: multiplier must be positive", self.symbol).into());
+        }
+        Ok(())
+    }
+}
+
+#[derive(Deserialize)]
+struct InstrumentFile {
+    #[serde(default)]
+    instrument: Vec<Instrument>,
+}
+
+// instrument reference data keyed by symbol
+#[derive(Debug, Default)]
+pub struct InstrumentMaster {
+    instruments: HashMap<String, Instrument>,
+}
+
+impl InstrumentMaster {
+    /// loads `[[instrument]]` tables from a .toml file or rows from a .csv file with a header line
+    pub fn load(path: &Path) -> Result<Self, Box<dyn std::error::Error>> {
+        let content = std::fs::read_to_string(path)?;
+        let master = match path.extension().and_then(|ext| ext.to_str()) {
+            Some("csv") => Self::from_csv(&content)?,
+            Some("toml") => Self::from_toml(&content)?,
+            _ => return Err(format!("Unsupported instrument file {}", path.display()).into()),
+        };
+        info!("Loaded {} instruments from {}", master.instruments.len(), path.display());
+        Ok(master)
+    }
+
+    pub fn from_toml(content: &str) -> Result<Self, Box<dyn std::error::Error>> {
+        let file: InstrumentFile = toml::from_str(content)?;
+        Self::from_instruments(file.instrument)
+    }
+
+    pub fn from_csv(content: &str) -> Result<Self, Box<dyn std::error::Error>> {
+        let mut reader = csv::ReaderBuilder::new().trim(csv::Trim::All).from_reader(content.as_bytes());
+        let instruments = reader.deserialize().collect::<Result<Vec<Instrument>, _>>()?;
+        Self::from_instruments(instruments)
+    }
+
+    pub fn from_instruments(instruments: Vec<Instrument>) -> Result<Self, Box<dyn std::error::Error>> {
+        let mut master = InstrumentMaster::default();
+        for mut instrument in instruments {
+            // empty csv cells
+            if instrument.venue_symbol.as_deref() == Some("") {
+                instrument.venue_symbol = None;
+            }
+            instrument.validate()?;
+            if master.instruments.contains_key(&instrument.symbol) {
+                return Err(format!("Duplicate instrument {}", instrument.symbol).into());
+            }
+            master.instruments.insert(instrument.symbol.clone(), instrument);
+        }
+        Ok(master)
+    }
+
+    pub fn get(&self, symbol: &str) -> Option<&Instrument> {
+        self.instruments.get(symbol)
+    }
+
+    /// validates an order against its instrument and returns it with price and quantity rounded
+    pub fn prepare_order(&self, order: &Order) -> Result<Order, InstrumentViolation> {
+        let instrument = self
+            .get(&order.symbol)
+            .ok_or_else(|| InstrumentViolation::UnknownInstrument(order.symbol.clone()))?;
+
+        let price = instrument.round_price(order.price, order.side);
+        if !price.is_finite() || price <= 0.0 {
+            return Err(InstrumentViolation::InvalidPrice(order.price));
+        }
+        if order.quantity > instrument.max_quantity {
+            return Err(InstrumentViolation::AboveMaxQuantity {
+                quantity: order.quantity,
+                max: instrument.max_quantity,
+            });
+        }
+        let quantity = instrument.round_quantity(order.quantity);
+        if quantity < instrument.min_quantity || quantity == 0 {
+            return Err(InstrumentViolation::BelowMinQuantity {
+                quantity: order.quantity,
+                min: instrument.min_quantity.max(instrument.lot_size),
+            });
+        }
+
+        if price != order.price || quantity != order.quantity {
+            debug!(
+                "Rounded {} order {}@{} to {}@{}",
+                order.symbol, order.quantity, order.price, quantity, price
+            );
+        }
+        Ok(Order { price, quantity, ..order.clone() })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const TOML: &str = r#"
+        [[instrument]]
+        symbol = "AAPL"
+        tick_size = 0.01
+        currency = "USD"
+
+        [[instrument]]
+        symbol = "ES"
+        venue_symbol = "ESZ4"
+        tick_size = 0.25
+        lot_size = 1
+        min_quantity = 1
+        max_quantity = 50
+        currency = "USD"
+        multiplier = 50.0
+    "#;
+
+    fn order(symbol: &str, price: f64, quantity: u64, side: OrderSide) -> Order {
+        Order { symbol: symbol.to_string(), price, quantity, side }
+    }
+
+    #[test]
+    fn test_load_toml_and_csv() {
+        let master = InstrumentMaster::from_toml(TOML).unwrap();
+        let es = master.get("ES").unwrap();
+        assert_eq!(es.venue_symbol(), "ESZ4");
+        assert_eq!(es.price_precision(), 2);
+        assert_eq!(es.multiplier, 50.0);
+        assert_eq!(master.get("AAPL").unwrap().venue_symbol(), "AAPL");
+
+        let csv = "symbol,venue_symbol,tick_size,lot_size,min_quantity,max_quantity,price_precision,currency,multiplier\n\
+                   7203,7203.T,0.5,100,100,100000,1,JPY,1\n\
+                   VOD,,0.002,1,1,1000000,,GBP,1\n";
+        let master = InstrumentMaster::from_csv(csv).unwrap();
+        let toyota = master.get("7203").unwrap();
+        assert_eq!((toyota.lot_size, toyota.currency.as_str()), (100, "JPY"));
+        assert_eq!(toyota.venue_symbol(), "7203.T");
+        let vod = master.get("VOD").unwrap();
+        assert_eq!(vod.venue_symbol(), "VOD");
+        assert_eq!(vod.price_precision(), 3);
+
+        assert!(InstrumentMaster::from_toml("[[instrument]]\nsymbol = \"X\"\ntick_size = 0.0\ncurrency = \"USD\"").is_err());
+    }
+
+    #[test]
+    fn test_prepare_order() {
+        let master = InstrumentMaster::from_toml(TOML).unwrap();
+
+        // passive rounding: buys down, sells up, on-grid prices untouched
+        assert_eq!(master.prepare_order(&order("ES", 5000.37, 3, OrderSide::Buy)).unwrap().price, 5000.25);
+        assert_eq!(master.prepare_order(&order("ES", 5000.37, 3, OrderSide::Sell)).unwrap().price, 5000.5);
+        assert_eq!(master.prepare_order(&order("AAPL", 189.07, 3, OrderSide::Buy)).unwrap().price, 189.07);
+
+        assert_eq!(
+            master.prepare_order(&order("ES", 5000.0, 51, OrderSide::Buy)),
+            Err(InstrumentViolation::AboveMaxQuantity { quantity: 51, max: 50 })
+        );
+        assert_eq!(
+            master.prepare_order(&order("MSFT", 400.0, 1, OrderSide::Buy)),
+            Err(InstrumentViolation::UnknownInstrument("MSFT".to_string()))
+        );
+        assert!(master.prepare_order(&order("AAPL", 0.001, 1, OrderSide::Buy)).is_err());
+    }
+
+    #[test]
+    fn test_lot_rounding() {
+        let master = InstrumentMaster::from_csv(
+            "symbol,tick_size,lot_size,min_quantity,currency\n7203,0.5,100,100,JPY\n",
+        )
+        .unwrap();
+        let prepared = master.prepare_order(&order("7203", 2500.2, 250, OrderSide::Buy)).unwrap();
+        assert_eq!((prepared.price, prepared.quantity), (2500.0, 200));
+        assert_eq!(
+            master.prepare_order(&order("7203", 2500.0, 99, OrderSide::Buy)),
+            Err(InstrumentViolation::BelowMinQuantity { quantity: 99, min: 100 })
+        );
+    }
+}
@@ -4,28 +4,51 @@ mod market_data;
 mod strategy;
 mod order_execution;
 mod logging;
+mod indicators;
+mod storage;
+mod instruments;
+mod calendar;
+mod reload;
 
-use anyhow::{anyhow, Context, Result};
-use chrono::Local;
+use anyhow::{anyhow, bail, Context, Result};
+use calendar::TradingCalendar;
+use chrono::{Local, Utc};
 use exchange::connection::ExchangeConnection;
-use log::{info, error, warn, debug, LevelFilter};
+use log::{info, error, warn, debug, trace, LevelFilter};
 use market_data::{
-    parser::{MarketDataParser, MarketDataFormat, Tick},
+    parser::{MarketDataParser, MarketDataFormat, Tick, now_nanos},
+    quality::DataQualityGuard,
     aggregator::MarketDataAggregator,
-    order_book::OrderBook,
+    consolidated_book::ConsolidatedBook,
+    trade_tape::TradeTape,
+    bar::{Bar, BarBuilder},
+    symbol::Symbol,
+    warmup::{load_history, load_stored, WarmupWindow},
 };
 // use order_execution::executor::OrderExecutor;
-use config::{Config};
+use order_execution::open_orders::OpenOrders;
+use order_execution::algo::{AlgoEngine, AlgoParams};
+use order_execution::emulation::{EmulationParams, OrderEmulator};
+use order_execution::ParentOrders;
+use storage::reader::TickReader;
+use storage::TickWriter;
+use instruments::InstrumentMaster;
+use config::{Config, WarmupUnit};
+use reload::ConfigReloader;
+use serde::Serialize;
 use strategy::{
     Strategy,
-    SimpleMovingAverageStrategy,
-    TrendFollowingStrategy,
+    StrategyRegistry,
+    SnapshotStore,
     risk_management::RiskManager,
     Order, OrderSide,
+    StrategyContext, StrategyAction, Fill, OrderUpdate, OrderReject, OrderStatus, RequestKind,
 };
 use std::{
+    collections::{HashMap, HashSet, VecDeque},
     fs::{OpenOptions, File},
     io::Write,
+    path::Path,
 };
 use tokio::{
     signal,
@@ -33,12 +56,12 @@ use tokio::{
 };
 use tokio::time::{sleep, Duration, timeout};
 
-
+const CONFIG_PATH: &str = "config.toml";
 
 #[tokio::main]
 async fn main() -> anyhow::Result<()> {
     // 1. Initializing the configuration and logger
-    let config = Config::load("config.toml").context("Failed to load config")?;
+    let config = Config::load(CONFIG_PATH).context("Failed to load config")?;
     logging::init(&config.logging).context("Failed to initialize logging")?;
     info!("Starting HFT bot with config: {:#?}", config);
 
@@ -66,26 +89,70 @@ async fn main() -> anyhow::Result<()> {
     });
 
     // 4. Main loop with timeout handling
-    let mut market_data_parser = MarketDataParser::new(MarketDataFormat::JSON);
-    let mut market_data_aggregator = MarketDataAggregator::new(
-        config.market_data.aggregation_window,
-        config.market_data.use_wma.then(|| vec![1.0; config.market_data.aggregation_window]),
-    );
-    // init OrderBook
-    let mut order_book = OrderBook::new();
-    info!("Order book initialized");
-    // init strategy
-    let mut strategy: Box<dyn Strategy> = match config.strategy.name.to_lowercase().as_str() {
-        "sma" => Box::new(SimpleMovingAverageStrategy::new()),
-        "trendfollowing" => Box::new(TrendFollowingStrategy::new(
-            config.strategy.long_period,
-            config.strategy.short_period,
-        )),
-        _ => {
-            warn!("Unknown strategy, defaulting to SMA");
-            Box::new(SimpleMovingAverageStrategy::new())
+    let market_data_format: MarketDataFormat = config.market_data.format.parse()
+        .map_err(|e: String| anyhow::anyhow!(e))?;
+    info!("Market data format: {:?}", market_data_format);
+    let market_data_parser = MarketDataParser::new(market_data_format);
+    let mut market_data_aggregator = match config.market_data.aggregation_window_ms {
+        Some(window_ms) => {
+            if config.market_data.use_wma {
+                warn!("WMA weights need a tick-count window, using SMA over {}ms", window_ms);
+            }
+            MarketDataAggregator::with_duration(Duration::from_millis(window_ms))
+        }
+        None => MarketDataAggregator::new(
+            config.market_data.aggregation_window,
+            config.market_data.use_wma.then(|| vec![1.0; config.market_data.aggregation_window]),
+        ),
+    };
+    market_data_aggregator
+        .add_indicators_from_config(&config.market_data.indicators)
+        .map_err(|e| anyhow!("Failed to configure indicators: {}", e))?;
+    // init consolidated book; this connection feeds the configured venue
+    let mut consolidated_book = ConsolidatedBook::new();
+    let venue = config.exchange.venue.clone();
+    // symbols whose cross-venue crossed market has been reported
+    let mut cross_venue_alerts: HashSet<Symbol> = HashSet::new();
+    info!("Order book initialized for venue {}", venue);
+    // init strategy from its [strategy.<name>] table
+    let strategy_registry = StrategyRegistry::new();
+    let mut strategy = strategy_registry
+        .build_from_config(&config.strategy)
+        .map_err(|e| anyhow!("Failed to build strategy: {}", e))?;
+    let strategy_symbols = strategy.symbols();
+    info!("Strategy {} trading {:?}", config.strategy.name, strategy_symbols);
+    // an indicator without a symbol takes every tick, which only works for a single instrument
+    if strategy_symbols.len() > 1 {
+        if let Some(indicator) = config.market_data.indicators.iter().find(|indicator| indicator.symbol.is_none()) {
+            bail!("Indicator '{}' needs a symbol: the strategy trades {:?}", indicator.name, strategy_symbols);
+        }
+    }
+    // warm state from the last run, if recent enough; a bad snapshot only costs the warm-up
+    let mut strategy_snapshots = SnapshotStore::from_config(&config.strategy);
+    let restored_at = match strategy_snapshots.as_ref().map(|snapshots| snapshots.restore(&mut *strategy, now_nanos())) {
+        Some(Ok(saved_at)) => saved_at,
+        Some(Err(e)) => {
+            warn!("Ignoring strategy snapshot: {}", e);
+            None
+        }
+        None => None,
+    };
+    // reference data every order is validated and rounded against
+    let instrument_master = match config.instruments.as_ref() {
+        Some(instruments) => {
+            let master = InstrumentMaster::load(&instruments.path)
+                .map_err(|e| anyhow!("Failed to load instruments from {}: {}", instruments.path.display(), e))?;
+            if let Some(missing) = strategy_symbols.iter().find(|symbol| master.get(symbol).is_none()) {
+                return Err(anyhow!("Strategy symbol {} is missing from the instrument master", missing));
+            }
+            Some(master)
+        }
+        None => {
+            warn!("No instrument master configured, orders are sent without tick/lot size checks");
+            None
         }
     };
+    config.risk.validate().context("Invalid risk config")?;
     let mut risk_manager = RiskManager::new(
         config.risk.max_position_size,
         config.risk.max_loss_per_trade,
@@ -93,32 +160,235 @@ async fn main() -> anyhow::Result<()> {
         config.risk.initial_capital,
     );
 
+    // data quality checks between the parser and the strategies
+    let mut quality_guard = DataQualityGuard::new(config.data_quality.clone());
+    let mut stale_check = tokio::time::interval(Duration::from_millis(
+        (config.data_quality.stale_after_ms / 2).max(10),
+    ));
+
+    // session hours, holidays and phases; orders are only sent in permitted phases
+    let mut trading_calendar = TradingCalendar::from_config(&config.calendar)
+        .map_err(|e| anyhow!("Invalid trading calendar: {}", e))?;
+    let mut session_check = tokio::time::interval(Duration::from_secs(1));
+    let strategy_sessions: HashSet<String> = strategy_symbols
+        .iter()
+        .filter_map(|symbol| trading_calendar.session_name(symbol))
+        .map(str::to_string)
+        .collect();
+    // last trade or quote per symbol, the reference price for risk checks and flattening
+    let mut last_prices: HashMap<Symbol, f64> = HashMap::new();
+
+    // time and sales with aggressor classification
+    let mut trade_tape = TradeTape::new(
+        config.trade_tape.capacity,
+        Duration::from_millis(config.trade_tape.flow_window_ms),
+    );
+
+    // strategy lifecycle: completed bars, a periodic timer and the orders the strategy has working
+    let mut bar_builder = BarBuilder::new(Duration::from_millis(config.strategy.bar_interval_ms));
+    let mut strategy_timer = tokio::time::interval(Duration::from_millis(config.strategy.timer_interval_ms.max(1)));
+    let mut open_orders = OpenOrders::new();
+    // TWAP/VWAP/POV parents the strategy hands over, worked as child orders
+    let mut algo_engine = AlgoEngine::new(config.execution.clone())
+        .map_err(|e| anyhow!("Invalid execution config: {}", e))?;
+    // iceberg and pegged orders emulated with plain limit orders
+    let mut order_emulator = OrderEmulator::new(config.execution.clone())
+        .map_err(|e| anyhow!("Invalid execution config: {}", e))?;
+
+    // optional on-disk tick store
+    let mut tick_writer = match config.storage.as_ref() {
+        Some(storage) => {
+            info!("Recording ticks to {}", storage.tick_store_path.display());
+            Some(TickWriter::new(&storage.tick_store_path, storage.block_size)
+                .context("Failed to open tick store")?)
+        }
+        None => None,
+    };
+
+    // replay recorded market data so indicators and strategy windows are full from the first live tick;
+    // nothing is sent meanwhile, every order the strategy asks for is reported back as rejected.
+    // a restored strategy already holds everything up to its snapshot and only sees the ticks after it
+    if let Some(warmup) = config.warmup.as_ref() {
+        let window = match warmup.unit {
+            WarmupUnit::Ticks => WarmupWindow::Ticks(warmup.last),
+            WarmupUnit::Bars => WarmupWindow::Bars { count: warmup.last, interval_ns: config.strategy.bar_interval_ms * 1_000_000 },
+        };
+        let history = match (warmup.path.as_ref(), config.storage.as_ref()) {
+            (Some(path), _) => {
+                let format = match warmup.format.as_deref() {
+                    Some(format) => format.parse().map_err(|e: String| anyhow!(e))?,
+                    None => market_data_format,
+                };
+                load_history(path, &MarketDataParser::new(format), window)
+            }
+            (None, Some(storage)) => {
+                let symbols = strategy_symbols.iter().map(|symbol| Symbol::new(symbol)).collect::<Vec<_>>();
+                load_stored(&TickReader::new(&storage.tick_store_path), &symbols, window)
+            }
+            (None, None) => bail!("[warmup] needs a path or a [storage] tick store to read from"),
+        }
+        .map_err(|e| anyhow!("Failed to load warm-up data: {}", e))?;
+        let mut suppressed = 0;
+        let mut replayed = 0;
+        for tick in &history {
+            market_data_aggregator.update(tick);
+            last_prices.insert(tick.symbol, tick.price);
+            let completed_bar = bar_builder.update(tick);
+            if restored_at.is_some_and(|saved_at| tick.timestamp <= saved_at) {
+                continue;
+            }
+            replayed += 1;
+            let warmup_ctx = || StrategyContext::new(tick.timestamp, &risk_manager, &open_orders, None, &market_data_aggregator);
+            suppressed += warm_up_event(&mut *strategy, warmup_ctx, |strategy, ctx| strategy.on_tick(tick, ctx));
+            if let Some(bar) = completed_bar.as_ref() {
+                suppressed += warm_up_event(&mut *strategy, warmup_ctx, |strategy, ctx| strategy.on_bar(bar, ctx));
+            }
+        }
+        info!(
+            "Warmed up on {} ticks, {} replayed to the strategy, {} strategy orders rejected",
+            history.len(),
+            replayed,
+            suppressed
+        );
+    }
+
+    // every strategy event runs against the same state
+    macro_rules! driver {
+        () => {
+            StrategyDriver {
+                strategy: &mut *strategy,
+                market_data: &market_data_aggregator,
+                books: &consolidated_book,
+                calendar: &trading_calendar,
+                instruments: instrument_master.as_ref(),
+                risk_manager: &mut risk_manager,
+                open_orders: &mut open_orders,
+                algos: &mut algo_engine,
+                emulator: &mut order_emulator,
+                last_prices: &last_prices,
+                trade_tape: &trade_tape,
+                exchange: &mut exchange,
+            }
+        };
+    }
+
+    driver!()
+        .run(now_nanos(), |strategy, ctx| strategy.on_start(ctx))
+        .await
+        .context("Strategy failed to start")?;
+
+    // edits to [strategy] and [risk] are applied while running, when the file changes or on `kill -HUP`
+    let mut config_reloader = ConfigReloader::new(Path::new(CONFIG_PATH)).context("Failed to read config for reloading")?;
+    let mut reload_check = tokio::time::interval(Duration::from_millis(config.reload.check_interval_ms.max(1)));
+    let mut hangup = signal::unix::signal(signal::unix::SignalKind::hangup()).context("Failed to listen for SIGHUP")?;
+
     info!("Entering main trading loop");
     let mut is_first_message = true;
     loop {
         tokio::select! {
             // market data processing
-            result = timeout(Duration::from_secs(10), exchange.receive_message()) => {
+            result = timeout(Duration::from_secs(10), exchange.receive_bytes()) => {
                 match result {
                     Ok(Ok(data)) => {
                         if is_first_message {
-                            debug!("First message received: {}", data.trim());
+                            debug!("First message received: {}", String::from_utf8_lossy(data).trim());
                             is_first_message = false;
                         }
 
                         // skip heartbeat responses
-                        if data.trim() != "HEARTBEAT_ACK" {
-                            if let Err(e) = process_market_data(
-                                &data,
-                                &market_data_parser,
-                                &mut market_data_aggregator,
-                                &mut order_book,
-                                &mut *strategy,
-                                &mut risk_manager,
-                                &mut exchange,
-                            ).await {
-                                error!("Error processing market data: {}", e);
+                        if data.trim_ascii() == b"HEARTBEAT_ACK" {
+                            debug!("Received heartbeat ack");
+                            continue;
+                        }
+
+                        let mut update = match market_data_parser.parse_update(data) {
+                            Ok(update) => update,
+                            Err(e) => {
+                                error!("Failed to parse market data: {}", e);
+                                continue;
+                            }
+                        };
+                        // a replayed or late message would set price levels back, so it is dropped before the book
+                        for (symbol, issue) in quality_guard.check_sequence(&mut update, now_nanos()) {
+                            warn!("Dropping market data for {}: {}", symbol, issue);
+                        }
+                        let book_updates = update.book.as_ref().map_or_else(Vec::new, |md| consolidated_book.apply(&venue, md));
+                        for symbol in &book_updates {
+                            if let Some(venue_book) = consolidated_book.venue(symbol, &venue) {
+                                if let Err(issue) = quality_guard.check_book(*symbol, venue_book) {
+                                    warn!("Bad order book for {}: {}", symbol, issue);
+                                }
+                            }
+                            match consolidated_book.crossed_market(symbol) {
+                                Some(crossed) if cross_venue_alerts.insert(*symbol) => {
+                                    warn!(
+                                        "Cross-venue {} market in {}: bid {} on {} vs ask {} on {}",
+                                        if crossed.is_locked() { "locked" } else { "crossed" },
+                                        symbol, crossed.bid, crossed.bid_venue, crossed.ask, crossed.ask_venue
+                                    );
+                                }
+                                None => {
+                                    cross_venue_alerts.remove(symbol);
+                                }
+                                _ => {}
+                            }
+                        }
+
+                        // a tick per instrument that traded, or whose touch moved, priced from the merged book
+                        let mut ticks = consolidated_book.ticks(&update, &book_updates);
+                        ticks.retain(|tick| {
+                            if let Some(writer) = tick_writer.as_mut() {
+                                if let Err(e) = writer.append(tick) {
+                                    error!("Failed to store tick: {}", e);
+                                }
                             }
+                            match quality_guard.check_tick(tick) {
+                                Ok(()) => true,
+                                Err(issue) => {
+                                    warn!("Dropping tick for {}: {}", tick.symbol, issue);
+                                    if !quality_guard.is_tradeable(&tick.symbol) {
+                                        risk_manager.set_trading_halted(tick.symbol.as_str(), true);
+                                    }
+                                    false
+                                }
+                            }
+                        });
+
+                        let mut completed_bars = Vec::new();
+                        for tick in &ticks {
+                            if let Some(print) = trade_tape.record(tick, consolidated_book.book(&tick.symbol)) {
+                                if let Some(flow) = trade_tape.flow(&tick.symbol) {
+                                    trace!(
+                                        "{} print {}@{} {:?} ({:?}), flow buy={} sell={} imbalance={:?} intensity={:.1}/s",
+                                        tick.symbol, print.volume, print.price, print.aggressor, print.classification,
+                                        flow.buy_volume(), flow.sell_volume(), flow.imbalance(), flow.intensity()
+                                    );
+                                }
+                            }
+
+                            trace!("Received tick: price={}, volume={}", tick.price, tick.volume);
+                            market_data_aggregator.update(tick);
+                            algo_engine.on_tick(tick);
+                            last_prices.insert(tick.symbol, tick.price);
+                            completed_bars.extend(bar_builder.update(tick));
+                        }
+
+                        // instruments with bad data are halted and get no callbacks
+                        ticks.retain(|tick| update_tradeable(&quality_guard, &mut risk_manager, &tick.symbol));
+                        completed_bars.retain(|bar| ticks.iter().any(|tick| tick.symbol == bar.symbol));
+                        let book_updates: Vec<Symbol> = book_updates
+                            .into_iter()
+                            .filter(|symbol| update_tradeable(&quality_guard, &mut risk_manager, symbol))
+                            .collect();
+                        if ticks.is_empty() && book_updates.is_empty() {
+                            continue;
+                        }
+                        let now = ticks.last().map_or_else(now_nanos, |tick| tick.timestamp);
+                        if let Err(e) = driver!()
+                            .run(now, |strategy, ctx| on_market_data(strategy, ctx, &ticks, &book_updates, &completed_bars))
+                            .await
+                        {
+                            error!("Error processing market data: {}", e);
                         }
                     }
                     Ok(Err(e)) => error!("Error receiving message: {}", e),
@@ -127,11 +397,70 @@ async fn main() -> anyhow::Result<()> {
                         exchange = ExchangeConnection::new(&config.exchange.address, "text")
                             .await
                             .map_err(|e| anyhow::anyhow!("Reconnect failed: {}", e))?;
+                        // quotes from before the disconnect are stale
+                        consolidated_book.remove_venue(&venue);
                         exchange.send_message("SUBSCRIBE_MARKET_DATA").await?;
                     }
                 }
             },
 
+            // stale feed detection; a quiet feed also ages prices and trade flow out of their time windows
+            _ = stale_check.tick() => {
+                let now = now_nanos();
+                for symbol in quality_guard.check_stale(now) {
+                    risk_manager.set_trading_halted(symbol.as_str(), true);
+                }
+                market_data_aggregator.expire(now);
+                trade_tape.expire(now);
+            },
+
+            // session phase changes and flattening before the close
+            _ = session_check.tick() => {
+                let now = Utc::now();
+                for change in trading_calendar.poll(now) {
+                    if strategy_sessions.contains(&change.session) {
+                        strategy.on_phase_change(&change);
+                    }
+                }
+                for session in trading_calendar.flatten_due(now) {
+                    for symbol in &strategy_symbols {
+                        if trading_calendar.session_name(symbol) != Some(session.as_str()) {
+                            continue;
+                        }
+                        info!("Flattening {} before the close, position {}", symbol, risk_manager.get_position(symbol));
+                        let price = last_prices.get(&Symbol::new(symbol)).copied();
+                        if let Err(e) = driver!().flatten(now_nanos(), symbol, price).await {
+                            error!("Failed to flatten before the close: {}", e);
+                        }
+                    }
+                }
+            },
+
+            // strategy timer
+            _ = strategy_timer.tick() => {
+                let now = now_nanos();
+                if let Err(e) = driver!().run(now, |strategy, ctx| strategy.on_timer(now, ctx)).await {
+                    error!("Error running strategy timer: {}", e);
+                }
+                if let Some(snapshots) = strategy_snapshots.as_mut() {
+                    if let Err(e) = snapshots.save_if_due(&*strategy, now) {
+                        error!("Failed to save strategy state: {}", e);
+                    }
+                }
+            },
+
+            // config hot reload
+            _ = reload_check.tick(), if config.reload.watch => {
+                if config_reloader.changed() {
+                    reload_config(&mut config_reloader, &strategy_registry, &mut strategy, &mut risk_manager);
+                }
+            },
+
+            _ = hangup.recv() => {
+                info!("SIGHUP received, reloading {}", CONFIG_PATH);
+                reload_config(&mut config_reloader, &strategy_registry, &mut strategy, &mut risk_manager);
+            },
+
             // heartbeat
             _ = rx.recv() => {
                 if let Err(e) = exchange.send_message("HEARTBEAT").await {
@@ -144,6 +473,19 @@ async fn main() -> anyhow::Result<()> {
             // graceful shutdown
             _ = signal::ctrl_c() => {
                 info!("Shutting down gracefully");
+                if let Err(e) = driver!().run(now_nanos(), |strategy, ctx| strategy.on_stop(ctx)).await {
+                    error!("Error stopping strategy: {}", e);
+                }
+                if let Some(snapshots) = strategy_snapshots.as_mut() {
+                    if let Err(e) = snapshots.save(&*strategy, now_nanos()) {
+                        error!("Failed to save strategy state: {}", e);
+                    }
+                }
+                if let Some(writer) = tick_writer.as_mut() {
+                    if let Err(e) = writer.flush() {
+                        error!("Failed to flush tick store: {}", e);
+                    }
+                }
                 break;
             }
         }
@@ -151,6 +493,86 @@ async fn main() -> anyhow::Result<()> {
     Ok(())
 }
 
+// runs a warm-up callback without sending anything. every request is reported back as rejected, and so are
+// the ones the strategy makes in reaction, so it holds no quote, position or trade it never got.
+fn warm_up_event<'a>(
+    strategy: &mut dyn Strategy,
+    context: impl Fn() -> StrategyContext<'a>,
+    callback: impl FnOnce(&mut dyn Strategy, &mut StrategyContext),
+) -> usize {
+    let mut ctx = context();
+    callback(strategy, &mut ctx);
+    let mut queue: VecDeque<StrategyAction> = ctx.into_actions().into();
+    let mut rejected = 0;
+    while let Some(action) = queue.pop_front() {
+        if rejected == MAX_ACTIONS_PER_EVENT {
+            warn!("Dropping {} warm-up actions: more than {} for one event", queue.len() + 1, MAX_ACTIONS_PER_EVENT);
+            break;
+        }
+        let (client_id, request, order) = match action {
+            StrategyAction::Place { client_id, order }
+            | StrategyAction::Execute { client_id, order, .. }
+            | StrategyAction::Emulate { client_id, order, .. } => (client_id, RequestKind::Place, Some(order)),
+            StrategyAction::Amend { client_id, .. } => (client_id, RequestKind::Amend, None),
+            StrategyAction::Cancel { client_id } => (client_id, RequestKind::Cancel, None),
+            StrategyAction::CancelAll { .. } => continue,
+        };
+        rejected += 1;
+        let reject = OrderReject { client_id, request, order, reason: "warming up, nothing is sent".to_string() };
+        let mut ctx = context();
+        strategy.on_order_reject(&reject, &mut ctx);
+        queue.extend(ctx.into_actions());
+    }
+    rejected
+}
+
+// halts or resumes trading in an instrument as its data quality changes
+fn update_tradeable(quality_guard: &DataQualityGuard, risk_manager: &mut RiskManager, symbol: &Symbol) -> bool {
+    let tradeable = quality_guard.is_tradeable(symbol);
+    risk_manager.set_trading_halted(symbol.as_str(), !tradeable);
+    if !tradeable {
+        let issues = quality_guard
+            .status(symbol)
+            .map(|status| status.issues().map(ToString::to_string).collect::<Vec<_>>().join(", "));
+        debug!("{} is not tradeable ({}), skipping strategy", symbol, issues.unwrap_or_else(|| "no data yet".to_string()));
+    }
+    tradeable
+}
+
+// strategy callbacks of one market data message: its ticks, the instruments whose touch moved,
+// then the bars the ticks completed
+fn on_market_data(
+    strategy: &mut dyn Strategy,
+    ctx: &mut StrategyContext,
+    ticks: &[Tick],
+    book_updates: &[Symbol],
+    completed_bars: &[Bar],
+) {
+    for tick in ticks {
+        strategy.on_tick(tick, ctx);
+    }
+    for symbol in book_updates {
+        strategy.on_book_update(symbol, ctx);
+    }
+    for bar in completed_bars {
+        strategy.on_bar(bar, ctx);
+    }
+}
+
+// new strategy and risk parameters take effect between two events, or not at all
+fn reload_config(
+    reloader: &mut ConfigReloader,
+    registry: &StrategyRegistry,
+    strategy: &mut Box<dyn Strategy>,
+    risk_manager: &mut RiskManager,
+) {
+    match reloader.reload(registry, strategy, risk_manager) {
+        Ok(changes) if changes.is_empty() => info!("Config reloaded, no strategy or risk changes"),
+        Ok(changes) => info!("Config reloaded, {} changes applied", changes.len()),
+        Err(e) => error!("Config reload rejected, keeping the running parameters: {:#}", e),
+    }
+}
+
 // async fn process_market_data(
 //     data: &str,
 //     parser: &MarketDataParser,
@@ -208,46 +630,511 @@ async fn process_market_data(
 }
 */
 
-async fn process_market_data(
-    data: &str,
-    parser: &MarketDataParser,
-    aggregator: &mut MarketDataAggregator,
-    order_book: &mut OrderBook,
-    strategy: &mut dyn Strategy,
-    risk_manager: &mut RiskManager,
-    exchange: &mut ExchangeConnection,
-) -> Result<()> {
-    if data.trim() == "HEARTBEAT_ACK" {
-        debug!("Received heartbeat ack");
-        return Ok(());
+// most places and amends one event may trigger, including follow-ups from fill and reject callbacks.
+// cancels only take risk off and are always sent.
+const MAX_ACTIONS_PER_EVENT: usize = 32;
+
+#[derive(Debug, PartialEq)]
+enum OrderOutcome {
+    Filled(Order),
+    // the venue accepted the request: new order working, amendment applied or order cancelled
+    Acknowledged(Order),
+    Rejected(String),
+}
+
+// order as sent to the venue, tagged with its client order id
+#[derive(Serialize)]
+struct WireOrder<'a> {
+    client_id: u64,
+    #[serde(flatten)]
+    order: &'a Order,
+}
+
+// runs strategy callbacks and sends the actions they take through the calendar, instrument checks,
+// risk and execution, reporting every outcome back to the strategy
+struct StrategyDriver<'a> {
+    strategy: &'a mut dyn Strategy,
+    market_data: &'a MarketDataAggregator,
+    books: &'a ConsolidatedBook,
+    calendar: &'a TradingCalendar,
+    instruments: Option<&'a InstrumentMaster>,
+    risk_manager: &'a mut RiskManager,
+    open_orders: &'a mut OpenOrders,
+    algos: &'a mut AlgoEngine,
+    emulator: &'a mut OrderEmulator,
+    last_prices: &'a HashMap<Symbol, f64>,
+    trade_tape: &'a TradeTape,
+    exchange: &'a mut ExchangeConnection,
+}
+
+impl StrategyDriver<'_> {
+    fn callback(
+        &mut self,
+        now: u64,
+        callback: impl FnOnce(&mut dyn Strategy, &mut StrategyContext),
+    ) -> Vec<StrategyAction> {
+        let mut parents = self.algos.working();
+        parents.extend(self.emulator.working());
+        let mut ctx = StrategyContext::new(now, self.risk_manager, self.open_orders, Some(self.books), self.market_data)
+            .with_trade_tape(self.trade_tape)
+            .with_parents(parents);
+        callback(&mut *self.strategy, &mut ctx);
+        ctx.into_actions()
     }
 
-    let tick = parser.parse(data)
-    .map_err(|e| anyhow::anyhow!("Failed to parse market data: {}", e))?;
-    debug!("Received tick: price={}, volume={}", tick.price, tick.volume);
-    
-    aggregator.update(&tick);
-    
-    if let Some(mut order) = strategy.evaluate(&tick) {
-        if let Some(approved_order) = risk_manager.evaluate_order(&mut order, tick.price) {
-            let order_msg = format!(
-                "PLACE_ORDER {}",
-                serde_json::to_string(&approved_order)?
-            );
-            
-            debug!("Sending order: {}", order_msg.trim());
-            exchange.send_message(&order_msg).await?;
-            
-            let response = timeout(Duration::from_secs(5), exchange.receive_message()).await??;
-            info!("Order response: {}", response.trim());
-            
-            if response.contains("EXECUTED") {
-                risk_manager.update_position(&approved_order);
+    async fn run(
+        &mut self,
+        now: u64,
+        callback: impl FnOnce(&mut dyn Strategy, &mut StrategyContext),
+    ) -> Result<()> {
+        let actions = self.callback(now, callback);
+        let mut sent = 0;
+        self.process(now, actions, &mut sent).await?;
+
+        // then whatever the execution algos and emulated orders have due, until they settle
+        while sent < MAX_ACTIONS_PER_EVENT {
+            let (mut actions, expired) = self.algos.poll(now, self.open_orders, self.books, self.last_prices);
+            actions.extend(self.emulator.poll(now, self.open_orders, self.books));
+            for update in expired {
+                actions.extend(self.callback(now, |strategy, ctx| strategy.on_order_update(&update, ctx)));
             }
+            if actions.is_empty() {
+                break;
+            }
+            self.process(now, actions, &mut sent).await?;
         }
+        Ok(())
     }
-    
-    Ok(())
+
+    // pulls everything working in the symbol, algo and emulated parents included, then closes what is
+    // left of the position at `price`, if there is one yet. the strategy sees both like its own orders. the
+    // calendar blocks places in the flatten window, so the closing order goes straight to risk and the exchange.
+    async fn flatten(&mut self, now: u64, symbol: &str, price: Option<f64>) -> Result<()> {
+        let mut sent = 0;
+        self.process(now, vec![StrategyAction::CancelAll { symbol: Some(symbol.to_string()) }], &mut sent).await?;
+        let position = self.risk_manager.get_position(symbol);
+        if position == 0 {
+            return Ok(());
+        }
+        let Some(price) = price else {
+            warn!("Cannot close the position of {} in {} before the close: no price yet", position, symbol);
+            return Ok(());
+        };
+        let order = Order {
+            symbol: symbol.to_string(),
+            price,
+            quantity: u64::from(position.unsigned_abs()),
+            side: if position > 0 { OrderSide::Sell } else { OrderSide::Buy },
+        };
+        let client_id = self.open_orders.allocate_id();
+        let reference_price = self.reference_price(&order);
+        let outcome = submit_order(client_id, order.clone(), reference_price, self.instruments, self.risk_manager, self.exchange).await?;
+        let follow_ups = self.report(now, client_id, RequestKind::Place, order, outcome);
+        self.process(now, follow_ups, &mut sent).await
+    }
+
+    async fn process(&mut self, now: u64, actions: Vec<StrategyAction>, sent: &mut usize) -> Result<()> {
+        let mut queue: VecDeque<StrategyAction> = actions.into();
+        let mut dropped = 0;
+        while let Some(action) = queue.pop_front() {
+            let action = match action {
+                StrategyAction::CancelAll { symbol } => {
+                    // children of algo and emulated orders go with their parent
+                    let mut cancels = self
+                        .open_orders
+                        .iter()
+                        .filter(|open| symbol.as_deref().is_none_or(|symbol| open.order.symbol == symbol))
+                        .filter(|open| self.parent_of(open.client_id).is_none())
+                        .map(|open| StrategyAction::Cancel { client_id: open.client_id })
+                        .collect::<Vec<_>>();
+                    let parents = [self.algos.parents(symbol.as_deref()), self.emulator.parents(symbol.as_deref())];
+                    cancels.extend(parents.into_iter().flatten().map(|client_id| StrategyAction::Cancel { client_id }));
+                    debug!("Cancelling {} open orders", cancels.len());
+                    for cancel in cancels.into_iter().rev() {
+                        queue.push_front(cancel);
+                    }
+                    continue;
+                }
+                StrategyAction::Execute { client_id, order, algo } => {
+                    let follow_ups = self.start_algo(now, client_id, order, algo);
+                    queue.extend(follow_ups);
+                    continue;
+                }
+                StrategyAction::Emulate { client_id, order, emulation } => {
+                    let follow_ups = self.start_emulated(now, client_id, order, emulation);
+                    queue.extend(follow_ups);
+                    continue;
+                }
+                StrategyAction::Cancel { client_id } if self.is_parent(client_id) => {
+                    let follow_ups = self.cancel_parent(now, client_id);
+                    queue.extend(follow_ups);
+                    continue;
+                }
+                StrategyAction::Amend { client_id, .. } if self.is_parent(client_id) => {
+                    let reject = OrderReject {
+                        client_id,
+                        request: RequestKind::Amend,
+                        order: None,
+                        reason: "algo and emulated orders cannot be amended".to_string(),
+                    };
+                    let follow_ups = self.callback(now, |strategy, ctx| strategy.on_order_reject(&reject, ctx));
+                    queue.extend(follow_ups);
+                    continue;
+                }
+                action => action,
+            };
+            if !matches!(action, StrategyAction::Cancel { .. }) {
+                if *sent >= MAX_ACTIONS_PER_EVENT {
+                    dropped += 1;
+                    continue;
+                }
+                *sent += 1;
+            }
+
+            let follow_ups = match action {
+                StrategyAction::Place { client_id, order } => {
+                    let outcome = self.place(client_id, order.clone()).await?;
+                    self.report(now, client_id, RequestKind::Place, order, outcome)
+                }
+                StrategyAction::Cancel { client_id } => {
+                    let Some(open) = self.open_orders.get(client_id).cloned() else {
+                        let follow_ups = self.unknown_order(now, client_id, RequestKind::Cancel);
+                        queue.extend(follow_ups);
+                        continue;
+                    };
+                    let response = send_order_request("CANCEL_ORDER", client_id, &open.order, self.instruments, self.exchange).await?;
+                    let outcome = order_outcome(RequestKind::Cancel, &response, open.order.clone(), self.risk_manager);
+                    self.report(now, client_id, RequestKind::Cancel, open.order, outcome)
+                }
+                StrategyAction::Amend { client_id, price, quantity } => {
+                    let Some(open) = self.open_orders.get(client_id).cloned() else {
+                        let follow_ups = self.unknown_order(now, client_id, RequestKind::Amend);
+                        queue.extend(follow_ups);
+                        continue;
+                    };
+                    let amended = Order { price, quantity, ..open.order.clone() };
+                    let outcome = self.amend(client_id, amended).await?;
+                    self.report(now, client_id, RequestKind::Amend, open.order, outcome)
+                }
+                StrategyAction::CancelAll { .. } | StrategyAction::Execute { .. } | StrategyAction::Emulate { .. } => {
+                    unreachable!("handled above")
+                }
+            };
+            queue.extend(follow_ups);
+        }
+        if dropped > 0 {
+            warn!("Dropped {} strategy orders: more than {} places and amends for one event", dropped, MAX_ACTIONS_PER_EVENT);
+        }
+        Ok(())
+    }
+
+    fn start_algo(&mut self, now: u64, client_id: u64, order: Order, algo: AlgoParams) -> Vec<StrategyAction> {
+        match self.algos.start(client_id, order.clone(), algo, now) {
+            Ok(()) => {
+                let update = OrderUpdate { client_id, order, status: OrderStatus::Accepted };
+                self.callback(now, |strategy, ctx| strategy.on_order_update(&update, ctx))
+            }
+            Err(reason) => {
+                warn!("Algo order {} rejected: {}", client_id, reason);
+                let reject = OrderReject { client_id, request: RequestKind::Place, order: Some(order), reason };
+                self.callback(now, |strategy, ctx| strategy.on_order_reject(&reject, ctx))
+            }
+        }
+    }
+
+    fn start_emulated(&mut self, now: u64, client_id: u64, order: Order, emulation: EmulationParams) -> Vec<StrategyAction> {
+        match self.emulator.start(client_id, order.clone(), emulation, self.books) {
+            Ok(()) => {
+                let update = OrderUpdate { client_id, order, status: OrderStatus::Accepted };
+                self.callback(now, |strategy, ctx| strategy.on_order_update(&update, ctx))
+            }
+            Err(reason) => {
+                warn!("Emulated order {} rejected: {}", client_id, reason);
+                let reject = OrderReject { client_id, request: RequestKind::Place, order: Some(order), reason };
+                self.callback(now, |strategy, ctx| strategy.on_order_reject(&reject, ctx))
+            }
+        }
+    }
+
+    fn is_parent(&self, client_id: u64) -> bool {
+        self.algos.is_parent(client_id) || self.emulator.is_parent(client_id)
+    }
+
+    fn parent_of(&self, child_id: u64) -> Option<u64> {
+        self.algos.parent_of(child_id).or_else(|| self.emulator.parent_of(child_id))
+    }
+
+    // the algo engine or emulator a child order belongs to
+    fn child_owner(&mut self, child_id: u64) -> Option<&mut dyn ParentOrders> {
+        if self.algos.parent_of(child_id).is_some() {
+            Some(&mut *self.algos)
+        } else if self.emulator.parent_of(child_id).is_some() {
+            Some(&mut *self.emulator)
+        } else {
+            None
+        }
+    }
+
+    // stops the parent, pulls its working child and reports the unfilled remainder as cancelled
+    fn cancel_parent(&mut self, now: u64, client_id: u64) -> Vec<StrategyAction> {
+        let cancelled = match self.algos.cancel(client_id) {
+            Some(cancelled) => Some(cancelled),
+            None => self.emulator.cancel(client_id),
+        };
+        let Some((remainder, child)) = cancelled else {
+            return Vec::new();
+        };
+        let mut actions: Vec<StrategyAction> =
+            child.map(|child_id| StrategyAction::Cancel { client_id: child_id }).into_iter().collect();
+        let update = OrderUpdate { client_id, order: remainder, status: OrderStatus::Cancelled };
+        actions.extend(self.callback(now, |strategy, ctx| strategy.on_order_update(&update, ctx)));
+        actions
+    }
+
+    fn unknown_order(&mut self, now: u64, client_id: u64, request: RequestKind) -> Vec<StrategyAction> {
+        warn!("{:?} request for unknown order {}", request, client_id);
+        if let Some(owner) = self.child_owner(client_id) {
+            owner.child_closed(client_id);
+            return Vec::new();
+        }
+        let reject = OrderReject { client_id, request, order: None, reason: "unknown order".to_string() };
+        self.callback(now, |strategy, ctx| strategy.on_order_reject(&reject, ctx))
+    }
+
+    // the calendar gates new exposure: places and amends, never cancels
+    fn blocked_by_calendar(&self, symbol: &str) -> Option<String> {
+        let now = Utc::now();
+        if self.calendar.can_trade(symbol, now) {
+            return None;
+        }
+        let reason = format!(
+            "{} phase{}",
+            self.calendar.phase(symbol, now),
+            if self.calendar.in_flatten_window(symbol, now) { ", flattening before the close" } else { "" }
+        );
+        debug!("Order for {} blocked: {}", symbol, reason);
+        Some(reason)
+    }
+
+    // last price seen for the symbol, else the order's own limit
+    fn reference_price(&self, order: &Order) -> f64 {
+        self.last_prices.get(&Symbol::new(&order.symbol)).copied().unwrap_or(order.price)
+    }
+
+    async fn place(&mut self, client_id: u64, order: Order) -> Result<OrderOutcome> {
+        if let Some(reason) = self.blocked_by_calendar(&order.symbol) {
+            return Ok(OrderOutcome::Rejected(reason));
+        }
+        let reference_price = self.reference_price(&order);
+        submit_order(client_id, order, reference_price, self.instruments, self.risk_manager, self.exchange).await
+    }
+
+    async fn amend(&mut self, client_id: u64, amended: Order) -> Result<OrderOutcome> {
+        if let Some(reason) = self.blocked_by_calendar(&amended.symbol) {
+            return Ok(OrderOutcome::Rejected(reason));
+        }
+        let amended = match check_order(&amended, self.reference_price(&amended), self.instruments, self.risk_manager) {
+            Ok(checked) => checked,
+            Err(reason) => return Ok(OrderOutcome::Rejected(reason)),
+        };
+        let response = send_order_request("AMEND_ORDER", client_id, &amended, self.instruments, self.exchange).await?;
+        Ok(order_outcome(RequestKind::Amend, &response, amended, self.risk_manager))
+    }
+
+    // keeps the open orders in line with the outcome and passes it to the strategy
+    fn report(
+        &mut self,
+        now: u64,
+        client_id: u64,
+        request: RequestKind,
+        order: Order,
+        outcome: OrderOutcome,
+    ) -> Vec<StrategyAction> {
+        if self.parent_of(client_id).is_some() {
+            return self.report_child(now, client_id, request, order, outcome);
+        }
+        match outcome {
+            OrderOutcome::Filled(filled) => {
+                self.open_orders.remove(client_id);
+                let fill = Fill {
+                    client_id,
+                    symbol: filled.symbol,
+                    side: filled.side,
+                    price: filled.price,
+                    quantity: filled.quantity,
+                    timestamp: now,
+                };
+                self.callback(now, |strategy, ctx| strategy.on_fill(&fill, ctx))
+            }
+            OrderOutcome::Acknowledged(order) => {
+                let status = match request {
+                    RequestKind::Place => OrderStatus::Accepted,
+                    RequestKind::Amend => OrderStatus::Amended,
+                    RequestKind::Cancel => OrderStatus::Cancelled,
+                };
+                if status == OrderStatus::Cancelled {
+                    self.open_orders.remove(client_id);
+                } else {
+                    self.open_orders.insert(client_id, order.clone());
+                }
+                let update = OrderUpdate { client_id, order, status };
+                self.callback(now, |strategy, ctx| strategy.on_order_update(&update, ctx))
+            }
+            OrderOutcome::Rejected(reason) => {
+                warn!("{:?} request for order {} rejected: {}", request, client_id, reason);
+                let reject = OrderReject { client_id, request, order: Some(order), reason };
+                self.callback(now, |strategy, ctx| strategy.on_order_reject(&reject, ctx))
+            }
+        }
+    }
+
+    // outcomes of children update their algo or emulated order; only fills and abandoned parents reach the strategy
+    fn report_child(
+        &mut self,
+        now: u64,
+        client_id: u64,
+        request: RequestKind,
+        order: Order,
+        outcome: OrderOutcome,
+    ) -> Vec<StrategyAction> {
+        let Some(owner) = self.child_owner(client_id) else {
+            return Vec::new();
+        };
+        match outcome {
+            OrderOutcome::Filled(filled) => {
+                let Some(parent_id) = owner.child_filled(client_id, filled.quantity, filled.price, now) else {
+                    return Vec::new();
+                };
+                self.open_orders.remove(client_id);
+                let fill = Fill {
+                    client_id: parent_id,
+                    symbol: filled.symbol,
+                    side: filled.side,
+                    price: filled.price,
+                    quantity: filled.quantity,
+                    timestamp: now,
+                };
+                self.callback(now, |strategy, ctx| strategy.on_fill(&fill, ctx))
+            }
+            OrderOutcome::Acknowledged(acknowledged) => {
+                if request == RequestKind::Cancel {
+                    owner.child_closed(client_id);
+                    self.open_orders.remove(client_id);
+                } else {
+                    owner.child_acknowledged(client_id, &acknowledged);
+                    self.open_orders.insert(client_id, acknowledged);
+                }
+                Vec::new()
+            }
+            OrderOutcome::Rejected(reason) => {
+                warn!("{:?} request for child order {} rejected: {}", request, client_id, reason);
+                let abandoned = match request {
+                    RequestKind::Place => owner.child_rejected(client_id),
+                    // the child keeps working as it was
+                    RequestKind::Amend => {
+                        owner.child_acknowledged(client_id, &order);
+                        None
+                    }
+                    RequestKind::Cancel => None,
+                };
+                let Some((parent_id, remainder)) = abandoned else {
+                    return Vec::new();
+                };
+                let reject = OrderReject { client_id: parent_id, request, order: Some(remainder), reason };
+                self.callback(now, |strategy, ctx| strategy.on_order_reject(&reject, ctx))
+            }
+        }
+    }
+}
+
+// tick/lot size rounding and quantity limits, then risk checks; returns the order to send
+fn check_order(
+    order: &Order,
+    reference_price: f64,
+    instruments: Option<&InstrumentMaster>,
+    risk_manager: &RiskManager,
+) -> std::result::Result<Order, String> {
+    let approved = risk_manager
+        .evaluate_order(&mut order.clone(), reference_price)
+        .ok_or_else(|| "risk limits".to_string())?;
+    // risk may cut the quantity, so what it approved is what gets rounded to the lot and checked
+    match instruments.map(|master| master.prepare_order(&approved)) {
+        Some(Ok(prepared)) => Ok(prepared),
+        Some(Err(violation)) => {
+            warn!("Order for {} rejected: {}", order.symbol, violation);
+            Err(violation.to_string())
+        }
+        None => Ok(approved),
+    }
+}
+
+// sends one order request to the venue and waits for its response
+async fn send_order_request(
+    request: &str,
+    client_id: u64,
+    order: &Order,
+    instruments: Option<&InstrumentMaster>,
+    exchange: &mut ExchangeConnection,
+) -> Result<String> {
+    // the venue may know the instrument under a different symbol
+    let mut wire_order = order.clone();
+    if let Some(instrument) = instruments.and_then(|master| master.get(&order.symbol)) {
+        wire_order.symbol = instrument.venue_symbol().to_string();
+    }
+    let order_msg = format!(
+        "{} {}",
+        request,
+        serde_json::to_string(&WireOrder { client_id, order: &wire_order })?
+    );
+
+    debug!("Sending order request: {}", order_msg.trim());
+    exchange.send_message(&order_msg).await?;
+
+    let response = timeout(Duration::from_secs(5), exchange.receive_message()).await??;
+    info!("Order response: {}", response.trim());
+    Ok(response)
+}
+
+// an execution report on a place or amend fills `order` and moves the position, anything but a reject acknowledges
+// the request. a cancel only fills what the venue says was executed before it arrived, as `EXECUTED <quantity>`
+fn order_outcome(request: RequestKind, response: &str, order: Order, risk_manager: &mut RiskManager) -> OrderOutcome {
+    if response.contains("REJECT") {
+        return OrderOutcome::Rejected(response.trim().to_string());
+    }
+    let filled = match request {
+        RequestKind::Place | RequestKind::Amend => response.contains("EXECUTED").then_some(order.quantity),
+        RequestKind::Cancel => executed_quantity(response).map(|quantity| quantity.min(order.quantity)),
+    };
+    match filled.filter(|&quantity| quantity > 0) {
+        Some(quantity) => {
+            let filled = Order { quantity, ..order };
+            risk_manager.update_position(&filled);
+            OrderOutcome::Filled(filled)
+        }
+        None => OrderOutcome::Acknowledged(order),
+    }
+}
+
+// quantity in an `EXECUTED <quantity>` report
+fn executed_quantity(response: &str) -> Option<u64> {
+    let (_, rest) = response.split_once("EXECUTED")?;
+    rest.split_whitespace().next()?.parse().ok()
+}
+
+async fn submit_order(
+    client_id: u64,
+    order: Order,
+    reference_price: f64,
+    instruments: Option<&InstrumentMaster>,
+    risk_manager: &mut RiskManager,
+    exchange: &mut ExchangeConnection,
+) -> Result<OrderOutcome> {
+    let order = match check_order(&order, reference_price, instruments, risk_manager) {
+        Ok(checked) => checked,
+        Err(reason) => return Ok(OrderOutcome::Rejected(reason)),
+    };
+    let response = send_order_request("PLACE_ORDER", client_id, &order, instruments, exchange).await?;
+    Ok(order_outcome(RequestKind::Place, &response, order, risk_manager))
 }
 
 fn init_logging(config: &config::LoggingConfig) -> Result<()> {
@@ -343,4 +1230,131 @@ fn calculate_pnl(order: &Order, tick: &Tick) -> f64 {
         OrderSide::Buy => (tick.price - order.price) * order.quantity as f64,
         OrderSide::Sell => (order.price - tick.price) * order.quantity as f64,
     }
-}
\ No newline at end of file
+}
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use market_data::order_book::OrderBook;
+    use strategy::context::ContextHarness;
+
+    // records the touch every book update shows, and the tick prices
+    #[derive(Default)]
+    struct BookWatcher {
+        updates: Vec<(Symbol, Option<f64>, Option<f64>)>,
+        ticks: Vec<f64>,
+    }
+
+    impl Strategy for BookWatcher {
+        fn on_tick(&mut self, tick: &Tick, _ctx: &mut StrategyContext) {
+            self.ticks.push(tick.price);
+        }
+
+        fn on_book_update(&mut self, symbol: &Symbol, ctx: &mut StrategyContext) {
+            let book = ctx.book(symbol.as_str());
+            self.updates.push((*symbol, book.and_then(OrderBook::get_best_bid), book.and_then(OrderBook::get_best_ask)));
+        }
+    }
+
+    // keeps one order working and re-sends it when it is rejected, if told to
+    struct Resubmitter {
+        working: Option<u64>,
+        rejects: Vec<String>,
+        resubmit: bool,
+    }
+
+    impl Strategy for Resubmitter {
+        fn on_tick(&mut self, tick: &Tick, ctx: &mut StrategyContext) {
+            if self.working.is_none() {
+                let order = Order { symbol: tick.symbol.to_string(), price: tick.price, quantity: 1, side: OrderSide::Buy };
+                self.working = Some(ctx.submit(order));
+            }
+        }
+
+        fn on_order_reject(&mut self, reject: &OrderReject, ctx: &mut StrategyContext) {
+            self.working = None;
+            self.rejects.push(reject.reason.clone());
+            if self.resubmit {
+                let order = reject.order.clone().unwrap();
+                self.working = Some(ctx.submit(order));
+            }
+        }
+    }
+
+    #[test]
+    fn test_warm_up_orders_are_reported_rejected() {
+        let harness = ContextHarness::default();
+        let context = || harness.ctx(0);
+        let tick = Tick { symbol: "AAPL".into(), price: 100.0, volume: 1, ..Default::default() };
+
+        let mut strategy = Resubmitter { working: None, rejects: Vec::new(), resubmit: false };
+        assert_eq!(warm_up_event(&mut strategy, context, |strategy, ctx| strategy.on_tick(&tick, ctx)), 1);
+        assert_eq!((strategy.working, strategy.rejects.len()), (None, 1));
+
+        // orders sent in reaction to the rejects are rejected too, up to the per-event cap
+        let mut stubborn = Resubmitter { working: None, rejects: Vec::new(), resubmit: true };
+        let rejected = warm_up_event(&mut stubborn, context, |strategy, ctx| strategy.on_tick(&tick, ctx));
+        assert_eq!((rejected, stubborn.rejects.len()), (MAX_ACTIONS_PER_EVENT, MAX_ACTIONS_PER_EVENT));
+    }
+
+    #[test]
+    fn test_order_cut_by_risk_is_rounded_to_the_lot() {
+        let instruments = InstrumentMaster::from_csv("symbol,tick_size,lot_size,min_quantity,currency\n7203,0.5,100,100,JPY\n").unwrap();
+        let mut risk_manager = RiskManager::new(250, 1e9, 0.0, 1e6);
+        let order = |quantity| Order { symbol: "7203".to_string(), price: 2500.2, quantity, side: OrderSide::Buy };
+
+        // the position limit cuts 300 to 250, which is then rounded down to the lot
+        let checked = check_order(&order(300), 2500.0, Some(&instruments), &risk_manager).unwrap();
+        assert_eq!((checked.quantity, checked.price), (200, 2500.0));
+
+        // 50 left below the limit is less than a lot
+        risk_manager.update_position(&order(200));
+        assert_eq!(
+            check_order(&order(100), 2500.0, Some(&instruments), &risk_manager),
+            Err("quantity 50 below minimum 100".to_string())
+        );
+    }
+
+    #[test]
+    fn test_cancel_fills_only_what_the_venue_executed() {
+        let mut risk_manager = RiskManager::new(1000, 1e9, 0.0, 1e6);
+        let order = Order { symbol: "AAPL".to_string(), price: 100.0, quantity: 5, side: OrderSide::Buy };
+
+        let outcome = order_outcome(RequestKind::Cancel, "CANCEL_ORDER EXECUTED", order.clone(), &mut risk_manager);
+        assert_eq!(outcome, OrderOutcome::Acknowledged(order.clone()));
+        assert_eq!(risk_manager.get_position("AAPL"), 0);
+
+        // filled in part before the cancel arrived
+        let outcome = order_outcome(RequestKind::Cancel, "CANCELLED EXECUTED 3", order.clone(), &mut risk_manager);
+        assert_eq!(outcome, OrderOutcome::Filled(Order { quantity: 3, ..order.clone() }));
+        assert_eq!(risk_manager.get_position("AAPL"), 3);
+
+        let outcome = order_outcome(RequestKind::Place, "ORDER EXECUTED", order.clone(), &mut risk_manager);
+        assert_eq!(outcome, OrderOutcome::Filled(order.clone()));
+        assert_eq!(risk_manager.get_position("AAPL"), 8);
+        assert!(matches!(order_outcome(RequestKind::Amend, "AMEND REJECTED", order, &mut risk_manager), OrderOutcome::Rejected(_)));
+    }
+
+    #[test]
+    fn test_fix_book_messages_reach_on_book_update() {
+        let parser = MarketDataParser::new(MarketDataFormat::Auto);
+        let mut books = ConsolidatedBook::new();
+        let harness = ContextHarness::default();
+        let mut strategy = BookWatcher::default();
+        let messages: [&[u8]; 3] = [
+            b"8=FIX.4.4|35=W|34=1|55=AAPL|269=0|270=99.5|271=10|269=1|270=100.5|271=20|",
+            // a level behind the touch: no callback
+            b"8=FIX.4.4|35=X|34=2|279=0|269=0|55=AAPL|270=99|271=5|",
+            // the best bid is pulled; the tick is the mid of what is left
+            b"8=FIX.4.4|35=X|34=3|279=2|269=0|55=AAPL|270=99.5|271=0|",
+        ];
+        for message in messages {
+            let update = parser.parse_update(message).unwrap();
+            let book_updates = books.apply("XNAS", update.book.as_ref().unwrap());
+            let mut ctx = harness.ctx_with_books(0, &books);
+            on_market_data(&mut strategy, &mut ctx, &books.ticks(&update, &book_updates), &book_updates, &[]);
+        }
+        let aapl = Symbol::new("AAPL");
+        assert_eq!(strategy.updates, vec![(aapl, Some(99.5), Some(100.5)), (aapl, Some(99.0), Some(100.5))]);
+        assert_eq!(strategy.ticks, vec![100.0, 99.75]);
+    }
+}
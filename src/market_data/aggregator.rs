@@ -1,13 +1,16 @@
 
+use crate::config::IndicatorConfig;
+use crate::indicators::{self, Indicator};
 use crate::market_data::parser::Tick;
-use std::collections::VecDeque;
+use crate::market_data::rolling_window::{RollingWindow, WindowSpec};
+use crate::market_data::symbol::Symbol;
+use std::time::Duration;
 
 // struct for aggregating market data such as 
 // moving average, total volume, high and low prices.
 pub struct MarketDataAggregator {
-    // price window for calculating the moving average
-    price_window: VecDeque<f64>,
-    window_size: usize,
+    // price window for calculating the moving average, either count- or time-based
+    price_window: RollingWindow,
     // simple moving average (SMA).
     sma: Option<f64>,
 
@@ -15,8 +18,12 @@ pub struct MarketDataAggregator {
     high_price: Option<f64>,
     low_price: Option<f64>,
 
-    // weights for the weighted moving average (WMA). If `None`, a simple SMA is used.
+    // weights for the weighted moving average (WMA), oldest first; the last one goes to the newest price.
+    // If `None`, a simple SMA is used. only available for count-based windows; weights are normalised by their sum.
     weights: Option<Vec<f64>>,
+
+    // named indicators updated on the ticks of their instrument, or on every tick when it is `None`
+    indicators: Vec<(String, Option<Symbol>, Box<dyn Indicator>)>,
 }
 
 impl MarketDataAggregator {
@@ -29,22 +36,50 @@ impl MarketDataAggregator {
         }
 
         MarketDataAggregator {
-            price_window: VecDeque::new(),
-            window_size,
+            price_window: RollingWindow::new(WindowSpec::Ticks(window_size)),
             sma: None,
             total_volume: 0,
             high_price: None,
             low_price: None,
             weights,
+            indicators: Vec::new(),
+        }
+    }
+
+    // aggregator whose window covers all ticks whose event time lies within `window` of the newest one
+    pub fn with_duration(window: Duration) -> Self {
+        MarketDataAggregator {
+            price_window: RollingWindow::new(WindowSpec::Time(window)),
+            sma: None,
+            total_volume: 0,
+            high_price: None,
+            low_price: None,
+            weights: None,
+            indicators: Vec::new(),
+        }
+    }
+
+    pub fn add_indicator(&mut self, name: &str, symbol: Option<Symbol>, indicator: Box<dyn Indicator>) {
+        self.indicators.retain(|(existing, _, _)| existing != name);
+        self.indicators.push((name.to_string(), symbol, indicator));
+    }
+
+    // builds and registers every indicator listed in the config
+    pub fn add_indicators_from_config(
+        &mut self,
+        configs: &[IndicatorConfig],
+    ) -> Result<(), Box<dyn std::error::Error>> {
+        for config in configs {
+            let indicator = indicators::from_config(&config.kind)
+                .map_err(|e| format!("Invalid indicator '{}': {}", config.name, e))?;
+            self.add_indicator(&config.name, config.symbol.as_deref().map(Symbol::new), indicator);
         }
+        Ok(())
     }
 
     // update aggregated data based on a new tick
     pub fn update(&mut self, tick: &Tick) {
-        self.price_window.push_back(tick.price);
-        if self.price_window.len() > self.window_size {
-            self.price_window.pop_front();
-        }
+        self.price_window.push(tick.timestamp, tick.price);
         self.sma = self.calculate_sma();
 
         self.total_volume += tick.volume;
@@ -56,6 +91,12 @@ impl MarketDataAggregator {
             Some(low) => Some(low.min(tick.price)),
             None => Some(tick.price),
         };
+
+        for (_, symbol, indicator) in self.indicators.iter_mut() {
+            if symbol.is_none_or(|symbol| symbol == tick.symbol) {
+                indicator.update(tick);
+            }
+        }
     }
 
     fn calculate_sma(&self) -> Option<f64> {
@@ -64,19 +105,31 @@ impl MarketDataAggregator {
         }
 
         if let Some(ref weights) = self.weights {
-            // weighted moving average (WMA)
+            // weighted moving average (WMA), normalised by the weights in use
+            // so a partially filled window still yields a price; its prices take the newest weights
+            let newest = &weights[weights.len() - self.price_window.len()..];
             let mut weighted_sum = 0.0;
-            for (i, &price) in self.price_window.iter().enumerate() {
-                weighted_sum += price * weights[i];
+            let mut weight_total = 0.0;
+            for (price, weight) in self.price_window.values().zip(newest) {
+                weighted_sum += price * weight;
+                weight_total += weight;
+            }
+            if weight_total == 0.0 {
+                return None;
             }
-            Some(weighted_sum)
+            Some(weighted_sum / weight_total)
         } else {
-            // simple moving average (SMA)
-            let sum: f64 = self.price_window.iter().sum();
-            Some(sum / self.price_window.len() as f64)
+            // simple moving average (SMA), kept up to date incrementally by the window
+            self.price_window.mean()
         }
     }
 
+    // ages the window out against the wall clock so a quiet feed does not keep stale prices
+    pub fn expire(&mut self, now: u64) {
+        self.price_window.expire(now);
+        self.sma = self.calculate_sma();
+    }
+
     pub fn get_sma(&self) -> Option<f64> {
         self.sma
     }
@@ -92,6 +145,36 @@ impl MarketDataAggregator {
     pub fn get_low_price(&self) -> Option<f64> {
         self.low_price
     }
+
+    pub fn get_indicator(&self, name: &str) -> Option<&dyn Indicator> {
+        self.indicators
+            .iter()
+            .find(|(existing, _, _)| existing == name)
+            .map(|(_, _, indicator)| indicator.as_ref())
+    }
+
+    // looks up "name" or "name.output", e.g. "bb.upper" or "macd.signal"
+    pub fn get_indicator_value(&self, key: &str) -> Option<f64> {
+        let (name, field) = key.split_once('.').unwrap_or((key, "value"));
+        self.get_indicator(name)?.output(field)
+    }
+
+    // every output that has a value, keyed like `get_indicator_value`
+    pub fn indicator_values(&self) -> Vec<(String, f64)> {
+        let mut values = Vec::new();
+        for (name, _, indicator) in &self.indicators {
+            for field in indicator.outputs() {
+                let Some(value) = indicator.output(field) else {
+                    continue;
+                };
+                if *field == "value" {
+                    values.push((name.clone(), value));
+                }
+                values.push((format!("{}.{}", name, field), value));
+            }
+        }
+        values
+    }
 }
 
 #[cfg(test)]
@@ -102,10 +185,10 @@ mod tests {
     fn test_market_data_aggregator_sma() {
         let mut aggregator = MarketDataAggregator::new(3, None); // Simple Moving Average
 
-        let tick1 = Tick { price: 10.0, volume: 100 };
-        let tick2 = Tick { price: 11.0, volume: 150 };
-        let tick3 = Tick { price: 12.0, volume: 200 };
-        let tick4 = Tick { price: 13.0, volume: 250 };
+        let tick1 = Tick { price: 10.0, volume: 100, ..Default::default() };
+        let tick2 = Tick { price: 11.0, volume: 150, ..Default::default() };
+        let tick3 = Tick { price: 12.0, volume: 200, ..Default::default() };
+        let tick4 = Tick { price: 13.0, volume: 250, ..Default::default() };
 
         aggregator.update(&tick1);
         assert_eq!(aggregator.get_sma(), Some(10.0));
@@ -138,23 +221,94 @@ mod tests {
         let weights = vec![0.1, 0.3, 0.6];
         let mut aggregator = MarketDataAggregator::new(3, Some(weights));
 
-        let tick1 = Tick { price: 10.0, volume: 100 };
-        let tick2 = Tick { price: 11.0, volume: 150 };
-        let tick3 = Tick { price: 12.0, volume: 200 };
-        let tick4 = Tick { price: 13.0, volume: 250 };
+        let tick1 = Tick { price: 10.0, volume: 100, ..Default::default() };
+        let tick2 = Tick { price: 11.0, volume: 150, ..Default::default() };
+        let tick3 = Tick { price: 12.0, volume: 200, ..Default::default() };
+        let tick4 = Tick { price: 13.0, volume: 250, ..Default::default() };
+
+        // weights are normalised, so compare with a tolerance
+        let assert_sma = |aggregator: &MarketDataAggregator, expected: f64| {
+            let sma = aggregator.get_sma().unwrap();
+            assert!((sma - expected).abs() < 1e-9, "expected {}, got {}", expected, sma);
+        };
 
         aggregator.update(&tick1);
-        assert_eq!(aggregator.get_sma(), Some(10.0)); // only one value
+        assert_sma(&aggregator, 10.0); // only one value
 
         aggregator.update(&tick2);
-        assert_eq!(aggregator.get_sma(), Some(10.7)); // (10 * 0.1 + 11 * 0.3)
+        assert_sma(&aggregator, 32.0 / 3.0); // (10 * 0.3 + 11 * 0.6) / 0.9
 
         aggregator.update(&tick3);
-        assert_eq!(aggregator.get_sma(), Some(11.5)); // (10 * 0.1 + 11 * 0.3 + 12 * 0.6)
+        assert_sma(&aggregator, 11.5); // (10 * 0.1 + 11 * 0.3 + 12 * 0.6) / 1.0
 
         aggregator.update(&tick4);
         //(11 * 0.1 + 12 * 0.3 + 13 * 0.6) = 1.1 + 3.6 + 7.8 = 12.5
-        assert_eq!(aggregator.get_sma(), Some(12.5));
+        assert_sma(&aggregator, 12.5);
+
+        // `use_wma` weights every price 1.0: an average, not the sum of the window
+        let mut equal = MarketDataAggregator::new(3, Some(vec![1.0; 3]));
+        equal.update(&tick1);
+        equal.update(&tick2);
+        assert_sma(&equal, 10.5);
+    }
+
+    #[test]
+    fn test_market_data_aggregator_time_window() {
+        const MS: u64 = 1_000_000;
+        let mut aggregator = MarketDataAggregator::with_duration(Duration::from_millis(500));
+
+        aggregator.update(&Tick { price: 10.0, volume: 100, timestamp: 1_000 * MS, ..Default::default() });
+        aggregator.update(&Tick { price: 14.0, volume: 100, timestamp: 1_200 * MS, ..Default::default() });
+        assert_eq!(aggregator.get_sma(), Some(12.0));
+
+        // a busy burst and a quiet gap cover the same amount of time
+        aggregator.update(&Tick { price: 11.0, volume: 100, timestamp: 1_600 * MS, ..Default::default() });
+        assert_eq!(aggregator.get_sma(), Some(12.5)); // 10.0 left the window
+        assert_eq!(aggregator.get_low_price(), Some(10.0)); // all-time low is kept
+
+        aggregator.expire(3_000 * MS);
+        assert_eq!(aggregator.get_sma(), None);
+    }
+
+    #[test]
+    fn test_market_data_aggregator_indicators() {
+        use crate::config::IndicatorKind;
+
+        let mut aggregator = MarketDataAggregator::new(3, None);
+        let configs = vec![
+            IndicatorConfig { name: "sma".to_string(), symbol: None, kind: IndicatorKind::Sma { period: 2 } },
+            IndicatorConfig { name: "bb".to_string(), symbol: None, kind: IndicatorKind::Bollinger { period: 2, k: 1.0 } },
+        ];
+        aggregator.add_indicators_from_config(&configs).unwrap();
+
+        aggregator.update(&Tick { price: 10.0, volume: 100, ..Default::default() });
+        assert_eq!(aggregator.get_indicator_value("sma"), None);
+        aggregator.update(&Tick { price: 12.0, volume: 100, ..Default::default() });
+        assert_eq!(aggregator.get_indicator_value("sma"), Some(11.0));
+        assert_eq!(aggregator.get_indicator_value("bb.upper"), Some(12.0));
+        assert_eq!(aggregator.get_indicator_value("bb.lower"), Some(10.0));
+        assert_eq!(aggregator.get_indicator_value("missing"), None);
+        assert!(aggregator.get_indicator("bb").unwrap().value().is_some());
+    }
+
+    #[test]
+    fn test_indicators_per_symbol() {
+        use crate::config::IndicatorKind;
+
+        let mut aggregator = MarketDataAggregator::new(3, None);
+        let sma = |name: &str, symbol: &str| IndicatorConfig {
+            name: name.to_string(),
+            symbol: Some(symbol.to_string()),
+            kind: IndicatorKind::Sma { period: 2 },
+        };
+        aggregator.add_indicators_from_config(&[sma("aapl_sma", "AAPL"), sma("msft_sma", "MSFT")]).unwrap();
+
+        let tick = |symbol: &str, price: f64| Tick { symbol: Symbol::new(symbol), price, volume: 1, ..Default::default() };
+        for tick in [tick("AAPL", 190.0), tick("MSFT", 410.0), tick("AAPL", 192.0), tick("MSFT", 412.0)] {
+            aggregator.update(&tick);
+        }
+        assert_eq!(aggregator.get_indicator_value("aapl_sma"), Some(191.0));
+        assert_eq!(aggregator.get_indicator_value("msft_sma"), Some(411.0));
     }
 
     #[test]
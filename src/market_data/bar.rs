@@ -0,0 +1,103 @@
+use crate::market_data::parser::Tick;
+use crate::market_data::symbol::Symbol;
+use std::collections::HashMap;
+use std::time::Duration;
+
+// OHLCV bar covering [start, start + interval) in event time (ns)
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Bar {
+    pub symbol: Symbol,
+    pub start: u64,
+    pub open: f64,
+    pub high: f64,
+    pub low: f64,
+    pub close: f64,
+    pub volume: u64,
+    pub ticks: u64,
+}
+
+impl Bar {
+    fn open(tick: &Tick, start: u64) -> Self {
+        Bar {
+            symbol: tick.symbol,
+            start,
+            open: tick.price,
+            high: tick.price,
+            low: tick.price,
+            close: tick.price,
+            volume: tick.volume,
+            ticks: 1,
+        }
+    }
+
+    fn add(&mut self, tick: &Tick) {
+        self.high = self.high.max(tick.price);
+        self.low = self.low.min(tick.price);
+        self.close = tick.price;
+        self.volume += tick.volume;
+        self.ticks += 1;
+    }
+}
+
+// builds time bars per symbol; bars are aligned to multiples of the interval since the epoch.
+// a bar is completed by the first tick of a later interval, so empty intervals produce no bars.
+pub struct BarBuilder {
+    interval_ns: u64,
+    open_bars: HashMap<Symbol, Bar>,
+}
+
+impl BarBuilder {
+    pub fn new(interval: Duration) -> Self {
+        BarBuilder {
+            interval_ns: (interval.as_nanos() as u64).max(1),
+            open_bars: HashMap::new(),
+        }
+    }
+
+    /// adds a tick and returns the bar it completed, if any
+    pub fn update(&mut self, tick: &Tick) -> Option<Bar> {
+        let start = tick.timestamp - tick.timestamp % self.interval_ns;
+        match self.open_bars.get_mut(&tick.symbol) {
+            // late ticks of an already completed interval are folded into the open bar
+            Some(bar) if start <= bar.start => {
+                bar.add(tick);
+                None
+            }
+            Some(bar) => Some(std::mem::replace(bar, Bar::open(tick, start))),
+            None => {
+                self.open_bars.insert(tick.symbol, Bar::open(tick, start));
+                None
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SEC: u64 = 1_000_000_000;
+
+    fn tick(price: f64, volume: u64, timestamp: u64) -> Tick {
+        Tick { symbol: Symbol::new("AAPL"), price, volume, timestamp, ..Default::default() }
+    }
+
+    #[test]
+    fn test_time_bars() {
+        let mut builder = BarBuilder::new(Duration::from_secs(60));
+        assert_eq!(builder.update(&tick(100.0, 10, 60 * SEC + 1)), None);
+        assert_eq!(builder.update(&tick(101.5, 5, 90 * SEC)), None);
+        assert_eq!(builder.update(&tick(99.5, 5, 100 * SEC)), None);
+        assert_eq!(builder.update(&tick(100.5, 0, 119 * SEC)), None);
+
+        let bar = builder.update(&tick(102.0, 1, 185 * SEC)).unwrap();
+        assert_eq!(bar.start, 60 * SEC);
+        assert_eq!((bar.open, bar.high, bar.low, bar.close), (100.0, 101.5, 99.5, 100.5));
+        assert_eq!((bar.volume, bar.ticks), (20, 4));
+
+        // the gap from 120s to 180s produces no bar, the new one starts at 180s
+        assert_eq!(builder.open_bars[&Symbol::new("AAPL")].start, 180 * SEC);
+        assert_eq!(builder.update(&tick(101.0, 1, 170 * SEC)), None);
+        assert_eq!(builder.open_bars[&Symbol::new("AAPL")].low, 101.0);
+    }
+}
@@ -0,0 +1,371 @@
+use crate::market_data::fix_md::{FixMarketData, FixMdMessageType, MdEntryType, MdUpdateAction};
+use crate::market_data::order_book::{Order, OrderBook, OrderSide};
+use crate::market_data::parser::{MarketUpdate, Tick};
+use crate::market_data::symbol::Symbol;
+use log::debug;
+use ordered_float::OrderedFloat;
+use std::collections::{BTreeMap, HashMap};
+
+// quantity a single venue shows at a consolidated price level
+#[derive(Debug, Clone, PartialEq)]
+pub struct VenueQuantity {
+    pub venue: String,
+    pub quantity: u64,
+}
+
+// one price level of the consolidated book, attributed to the venues quoting it
+#[derive(Debug, Clone, PartialEq)]
+pub struct ConsolidatedLevel {
+    pub price: f64,
+    pub quantity: u64,
+    // sorted by venue name
+    pub venues: Vec<VenueQuantity>,
+}
+
+// best bid on one venue at or above the best ask on another
+#[derive(Debug, Clone, PartialEq)]
+pub struct CrossedMarket {
+    pub bid_venue: String,
+    pub bid: f64,
+    pub ask_venue: String,
+    pub ask: f64,
+}
+
+impl CrossedMarket {
+    pub fn is_locked(&self) -> bool {
+        self.bid == self.ask
+    }
+}
+
+// best (price, size) on the bid and ask side
+pub type TopOfBook = (Option<(f64, u64)>, Option<(f64, u64)>);
+
+// price level a venue quotes, the unit venue feeds update
+type LevelKey = (String, OrderSide, OrderedFloat<f64>);
+
+// books of one instrument: one per venue, and all venues merged into the book strategies see
+struct SymbolBooks {
+    venues: HashMap<String, OrderBook>,
+    merged: OrderBook,
+    // each venue level is one order with the same id in its venue book and in the merged book
+    level_ids: HashMap<LevelKey, u64>,
+}
+
+impl SymbolBooks {
+    fn new() -> Self {
+        SymbolBooks { venues: HashMap::new(), merged: OrderBook::new(), level_ids: HashMap::new() }
+    }
+
+    fn remove_level(&mut self, key: &LevelKey) {
+        if let Some(id) = self.level_ids.remove(key) {
+            self.merged.remove_order(id);
+            if let Some(book) = self.venues.get_mut(&key.0) {
+                book.remove_order(id);
+            }
+        }
+    }
+
+    fn remove_venue(&mut self, venue: &str) -> bool {
+        let keys: Vec<LevelKey> = self.level_ids.keys().filter(|key| key.0 == venue).cloned().collect();
+        for key in &keys {
+            self.remove_level(key);
+        }
+        self.venues.remove(venue).is_some()
+    }
+}
+
+// merges per-venue order books into one view of the market, per instrument
+#[derive(Default)]
+pub struct ConsolidatedBook {
+    symbols: HashMap<Symbol, SymbolBooks>,
+    next_level_id: u64,
+}
+
+impl ConsolidatedBook {
+    pub fn new() -> Self {
+        ConsolidatedBook::default()
+    }
+
+    /// sets the quantity a venue shows at one price; zero removes the level
+    pub fn update_level(&mut self, symbol: Symbol, venue: &str, side: OrderSide, price: f64, quantity: u64) {
+        let books = self.symbols.entry(symbol).or_insert_with(SymbolBooks::new);
+        let key = (venue.to_string(), side, OrderedFloat(price));
+        if quantity == 0 {
+            books.remove_level(&key);
+            return;
+        }
+        if let Some(&id) = books.level_ids.get(&key) {
+            books.merged.update_order(id, quantity);
+            if let Some(book) = books.venues.get_mut(venue) {
+                book.update_order(id, quantity);
+            }
+            return;
+        }
+        let id = self.next_level_id;
+        self.next_level_id += 1;
+        let order = Order { id, price, quantity, side };
+        if !books.venues.contains_key(venue) {
+            debug!("Adding venue {} to consolidated book of {}", venue, symbol);
+            books.venues.insert(venue.to_string(), OrderBook::new());
+        }
+        books.venues.get_mut(venue).expect("venue inserted above").add_order(order);
+        books.merged.add_order(order);
+        books.level_ids.insert(key, id);
+    }
+
+    /// applies the bid and offer entries of a FIX W/X message from `venue`; a snapshot first drops
+    /// what the venue quoted in the instrument. returns the instruments whose touch moved.
+    pub fn apply(&mut self, venue: &str, md: &FixMarketData) -> Vec<Symbol> {
+        let mut touched: Vec<(Symbol, TopOfBook)> =
+            md.symbols().into_iter().map(|symbol| (symbol, self.top_of_book(&symbol))).collect();
+        if md.message_type == FixMdMessageType::Snapshot {
+            self.clear(md.symbol, venue);
+        }
+        for entry in &md.entries {
+            let side = match entry.entry_type {
+                MdEntryType::Bid => OrderSide::Bid,
+                MdEntryType::Offer => OrderSide::Ask,
+                MdEntryType::Trade => continue,
+            };
+            let quantity = if entry.action == MdUpdateAction::Delete { 0 } else { entry.size };
+            self.update_level(md.entry_symbol(entry), venue, side, entry.price, quantity);
+        }
+        touched.retain(|(symbol, before)| self.top_of_book(symbol) != *before);
+        touched.into_iter().map(|(symbol, _)| symbol).collect()
+    }
+
+    /// ticks of a message whose entries were applied, `touched` being what `apply` returned: the trades,
+    /// then for each instrument whose touch moved without a trade, the merged touch after the update
+    pub fn ticks(&self, update: &MarketUpdate, touched: &[Symbol]) -> Vec<Tick> {
+        let mut ticks = update.ticks.clone();
+        for symbol in touched {
+            if ticks.iter().any(|tick| tick.symbol == *symbol) {
+                continue;
+            }
+            let (bid, ask) = self.top_of_book(symbol);
+            ticks.extend(update.quote_tick(*symbol, bid.map(|(price, _)| price), ask.map(|(price, _)| price)));
+        }
+        ticks
+    }
+
+    // drops every level a venue quotes in one instrument, e.g. before applying a full snapshot
+    pub fn clear(&mut self, symbol: Symbol, venue: &str) {
+        if let Some(books) = self.symbols.get_mut(&symbol) {
+            books.remove_venue(venue);
+        }
+    }
+
+    /// all venues merged into one book
+    pub fn book(&self, symbol: &Symbol) -> Option<&OrderBook> {
+        self.symbols.get(symbol).map(|books| &books.merged)
+    }
+
+    pub fn top_of_book(&self, symbol: &Symbol) -> TopOfBook {
+        self.book(symbol).map_or((None, None), |book| (book.bid_levels().next(), book.ask_levels().next()))
+    }
+
+    pub fn venue(&self, symbol: &Symbol, venue: &str) -> Option<&OrderBook> {
+        self.symbols.get(symbol)?.venues.get(venue)
+    }
+
+    // drops a venue in every instrument, e.g. after a disconnect, so its stale quotes stop counting
+    pub fn remove_venue(&mut self, venue: &str) -> bool {
+        let mut removed = false;
+        for books in self.symbols.values_mut() {
+            removed |= books.remove_venue(venue);
+        }
+        removed
+    }
+
+    /// aggregated depth on one side, best price first, at most `depth` levels
+    pub fn levels(&self, symbol: &Symbol, side: OrderSide, depth: usize) -> Vec<ConsolidatedLevel> {
+        let Some(books) = self.symbols.get(symbol) else {
+            return Vec::new();
+        };
+        let mut merged: BTreeMap<OrderedFloat<f64>, ConsolidatedLevel> = BTreeMap::new();
+        for (venue, book) in &books.venues {
+            // each venue can only contribute to the first `depth` consolidated levels with its own top `depth`
+            let venue_levels: Vec<(f64, u64)> = match side {
+                OrderSide::Bid => book.bid_levels().take(depth).collect(),
+                OrderSide::Ask => book.ask_levels().take(depth).collect(),
+            };
+            for (price, quantity) in venue_levels {
+                let level = merged.entry(OrderedFloat(price)).or_insert_with(|| ConsolidatedLevel {
+                    price,
+                    quantity: 0,
+                    venues: Vec::new(),
+                });
+                level.quantity += quantity;
+                level.venues.push(VenueQuantity { venue: venue.clone(), quantity });
+            }
+        }
+
+        let ordered: Box<dyn Iterator<Item = ConsolidatedLevel>> = match side {
+            OrderSide::Bid => Box::new(merged.into_values().rev()),
+            OrderSide::Ask => Box::new(merged.into_values()),
+        };
+        ordered
+            .take(depth)
+            .map(|mut level| {
+                level.venues.sort_by(|a, b| a.venue.cmp(&b.venue));
+                level
+            })
+            .collect()
+    }
+
+    pub fn best_bid(&self, symbol: &Symbol) -> Option<ConsolidatedLevel> {
+        self.levels(symbol, OrderSide::Bid, 1).pop()
+    }
+
+    pub fn best_ask(&self, symbol: &Symbol) -> Option<ConsolidatedLevel> {
+        self.levels(symbol, OrderSide::Ask, 1).pop()
+    }
+
+    /// reports the best bid and ask when bid >= ask and two different venues quote them.
+    /// a venue crossed against itself is bad data, see `DataQualityGuard::check_book`.
+    pub fn crossed_market(&self, symbol: &Symbol) -> Option<CrossedMarket> {
+        let (bid, ask) = (self.best_bid(symbol)?, self.best_ask(symbol)?);
+        if bid.price < ask.price {
+            return None;
+        }
+        // venues are sorted by name, so ties go to the first pair of different venues
+        let (bid_venue, ask_venue) = bid
+            .venues
+            .iter()
+            .flat_map(|b| ask.venues.iter().map(move |a| (&b.venue, &a.venue)))
+            .find(|(b, a)| b != a)?;
+        Some(CrossedMarket {
+            bid_venue: bid_venue.clone(),
+            bid: bid.price,
+            ask_venue: ask_venue.clone(),
+            ask: ask.price,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::market_data::fix_md::parse_fix_market_data;
+    use crate::market_data::parser::{MarketDataFormat, MarketDataParser};
+
+    fn quote(book: &mut ConsolidatedBook, venue: &str, side: OrderSide, price: f64, quantity: u64) {
+        book.update_level(Symbol::new("AAPL"), venue, side, price, quantity);
+    }
+
+    #[test]
+    fn test_consolidated_depth_and_bbo() {
+        let aapl = Symbol::new("AAPL");
+        let mut book = ConsolidatedBook::new();
+        quote(&mut book, "XNAS", OrderSide::Bid, 100.0, 5);
+        quote(&mut book, "XNAS", OrderSide::Bid, 99.9, 7);
+        quote(&mut book, "XNAS", OrderSide::Ask, 100.2, 4);
+        quote(&mut book, "BATS", OrderSide::Bid, 100.0, 3);
+        quote(&mut book, "BATS", OrderSide::Ask, 100.1, 6);
+        quote(&mut book, "BATS", OrderSide::Ask, 100.1, 7);
+
+        let best_bid = book.best_bid(&aapl).unwrap();
+        assert_eq!(best_bid.price, 100.0);
+        assert_eq!(best_bid.quantity, 8);
+        assert_eq!(
+            best_bid.venues,
+            vec![
+                VenueQuantity { venue: "BATS".to_string(), quantity: 3 },
+                VenueQuantity { venue: "XNAS".to_string(), quantity: 5 },
+            ]
+        );
+
+        let asks = book.levels(&aapl, OrderSide::Ask, 5);
+        assert_eq!(asks.iter().map(|l| l.price).collect::<Vec<_>>(), vec![100.1, 100.2]);
+        assert_eq!(asks[0].quantity, 7);
+        assert_eq!(book.best_ask(&aapl), Some(asks[0].clone()));
+        assert_eq!(asks[1].venues[0].venue, "XNAS");
+        assert_eq!(book.levels(&aapl, OrderSide::Bid, 1).len(), 1);
+        assert_eq!(book.crossed_market(&aapl), None);
+
+        // the merged book strategies see matches the consolidated levels
+        let merged = book.book(&aapl).unwrap();
+        assert_eq!(merged.bid_levels().collect::<Vec<_>>(), vec![(100.0, 8), (99.9, 7)]);
+        assert!((merged.get_mid_price().unwrap() - 100.05).abs() < 1e-9);
+        quote(&mut book, "XNAS", OrderSide::Bid, 100.0, 0);
+        assert_eq!(book.book(&aapl).unwrap().bid_levels().next(), Some((100.0, 3)));
+        assert!(book.book(&Symbol::new("MSFT")).is_none());
+        assert!(book.levels(&Symbol::new("MSFT"), OrderSide::Bid, 5).is_empty());
+    }
+
+    #[test]
+    fn test_cross_venue_crossed_market() {
+        let aapl = Symbol::new("AAPL");
+        let mut book = ConsolidatedBook::new();
+        quote(&mut book, "XNAS", OrderSide::Bid, 100.0, 5);
+        quote(&mut book, "XNAS", OrderSide::Ask, 100.3, 5);
+        quote(&mut book, "BATS", OrderSide::Bid, 99.8, 5);
+        quote(&mut book, "BATS", OrderSide::Ask, 100.0, 5);
+
+        let locked = book.crossed_market(&aapl).unwrap();
+        assert!(locked.is_locked());
+
+        quote(&mut book, "BATS", OrderSide::Ask, 99.9, 5);
+        let crossed = book.crossed_market(&aapl).unwrap();
+        assert_eq!(crossed.bid_venue, "XNAS");
+        assert_eq!(crossed.ask_venue, "BATS");
+        assert_eq!((crossed.bid, crossed.ask), (100.0, 99.9));
+        assert!(!crossed.is_locked());
+        assert_eq!(book.crossed_market(&Symbol::new("MSFT")), None);
+
+        assert!(book.remove_venue("BATS"));
+        assert_eq!(book.crossed_market(&aapl), None);
+        assert!(book.venue(&aapl, "BATS").is_none());
+        assert_eq!(book.book(&aapl).unwrap().get_best_ask(), Some(100.3));
+
+        // a snapshot replaces everything the venue quoted
+        book.clear(aapl, "XNAS");
+        assert_eq!(book.book(&aapl).unwrap().get_best_bid(), None);
+    }
+
+    #[test]
+    fn test_apply_fix_market_data() {
+        let (aapl, msft) = (Symbol::new("AAPL"), Symbol::new("MSFT"));
+        let mut book = ConsolidatedBook::new();
+        quote(&mut book, "XNAS", OrderSide::Bid, 99.0, 1);
+        let snapshot = parse_fix_market_data("35=W|55=AAPL|269=0|270=99.5|271=10|269=1|270=100.5|271=20|269=2|270=100|271=5|").unwrap();
+        assert_eq!(book.apply("XNAS", &snapshot), vec![aapl]);
+        assert_eq!(book.top_of_book(&aapl), (Some((99.5, 10)), Some((100.5, 20))));
+        assert_eq!(book.book(&aapl).unwrap().bid_levels().count(), 1, "the snapshot replaced the old bid");
+
+        // a change deeper in the book leaves the touch alone; the next message moves it in two symbols
+        let deeper = parse_fix_market_data("35=X|279=0|269=1|55=AAPL|270=101|271=5|").unwrap();
+        assert!(book.apply("XNAS", &deeper).is_empty());
+        let incremental = parse_fix_market_data(
+            "35=X|279=1|269=0|55=AAPL|270=99.5|271=40|279=2|269=1|55=AAPL|270=100.5|271=0|279=0|269=0|55=MSFT|270=410|271=3|",
+        )
+        .unwrap();
+        assert_eq!(book.apply("XNAS", &incremental), vec![aapl, msft]);
+        assert_eq!(book.top_of_book(&aapl), (Some((99.5, 40)), Some((101.0, 5))));
+        assert_eq!(book.top_of_book(&msft), (Some((410.0, 3)), None));
+    }
+
+    #[test]
+    fn test_ticks_from_merged_touch() {
+        let parser = MarketDataParser::new(MarketDataFormat::FixIncremental);
+        let mut book = ConsolidatedBook::new();
+        let mut ticks = |message: &[u8]| {
+            let update = parser.parse_update(message).unwrap();
+            let touched = book.apply("XNAS", update.book.as_ref().unwrap());
+            book.ticks(&update, &touched).iter().map(|tick| (tick.symbol.to_string(), tick.price)).collect::<Vec<_>>()
+        };
+
+        let quotes = b"8=FIX.4.4|35=W|55=AAPL|269=0|270=99.5|271=10|269=0|270=99|271=10|269=1|270=100.5|271=20|";
+        assert_eq!(ticks(quotes), vec![("AAPL".to_string(), 100.0)]);
+        // a deep level, added or deleted, leaves the touch and gives no tick
+        assert!(ticks(b"8=FIX.4.4|35=X|279=0|269=1|55=AAPL|270=102|271=5|").is_empty());
+        assert!(ticks(b"8=FIX.4.4|35=X|279=2|269=0|55=AAPL|270=99|271=0|").is_empty());
+        // a better bid moves the mid of the whole book, not of the changed level
+        assert_eq!(ticks(b"8=FIX.4.4|35=X|279=0|269=0|55=AAPL|270=100|271=5|"), vec![("AAPL".to_string(), 100.25)]);
+        // a trade gives the tick of its own instrument; the other one's quote moved its touch
+        assert_eq!(
+            ticks(b"8=FIX.4.4|35=X|279=0|269=2|55=AAPL|270=100.5|271=3|279=0|269=1|55=MSFT|270=410|271=3|"),
+            vec![("AAPL".to_string(), 100.5), ("MSFT".to_string(), 410.0)]
+        );
+    }
+}
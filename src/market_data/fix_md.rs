@@ -0,0 +1,261 @@
+use crate::market_data::parser::AggressorSide;
+use crate::market_data::symbol::Symbol;
+use chrono::NaiveDateTime;
+
+// decoding of FIX market data messages:
+// MarketDataSnapshotFullRefresh (35=W) and MarketDataIncrementalRefresh (35=X).
+// fields may be separated by SOH or by '|' as in the rest of the code base.
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FixMdMessageType {
+    Snapshot,
+    Incremental,
+}
+
+// MDEntryType (269)
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MdEntryType {
+    Bid,
+    Offer,
+    Trade,
+}
+
+// MDUpdateAction (279); snapshot entries are always `New`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MdUpdateAction {
+    New,
+    Change,
+    Delete,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct MdEntry {
+    pub entry_type: MdEntryType,
+    pub action: MdUpdateAction,
+    pub symbol: Symbol,
+    pub price: f64,
+    pub size: u64,
+    // AggressorSide (2446) on trade entries
+    pub aggressor: Option<AggressorSide>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct FixMarketData {
+    pub message_type: FixMdMessageType,
+    pub symbol: Symbol,
+    pub sequence: Option<u64>,
+    // SendingTime (52) in nanoseconds since the Unix epoch, if present
+    pub sending_time: Option<u64>,
+    pub entries: Vec<MdEntry>,
+}
+
+impl FixMarketData {
+    // instrument of an entry; entries without their own Symbol (55) belong to the message's
+    pub fn entry_symbol(&self, entry: &MdEntry) -> Symbol {
+        if entry.symbol.is_empty() {
+            self.symbol
+        } else {
+            entry.symbol
+        }
+    }
+
+    // instruments the message updates: a snapshot's own, then those of the entries, in order of first appearance
+    pub fn symbols(&self) -> Vec<Symbol> {
+        let mut symbols: Vec<Symbol> = Vec::new();
+        let snapshot = (self.message_type == FixMdMessageType::Snapshot).then_some(self.symbol);
+        for symbol in snapshot.into_iter().chain(self.entries.iter().map(|entry| self.entry_symbol(entry))) {
+            if !symbols.contains(&symbol) {
+                symbols.push(symbol);
+            }
+        }
+        symbols
+    }
+
+    // last trade in one instrument, if any
+    pub fn last_trade(&self, symbol: Symbol) -> Option<&MdEntry> {
+        self.entries
+            .iter()
+            .rev()
+            .find(|e| e.entry_type == MdEntryType::Trade && self.entry_symbol(e) == symbol)
+    }
+
+    // best bid and offer quoted in one instrument (deleted levels ignored)
+    pub fn best_quotes(&self, symbol: Symbol) -> (Option<f64>, Option<f64>) {
+        let live = self
+            .entries
+            .iter()
+            .filter(|e| e.action != MdUpdateAction::Delete && self.entry_symbol(e) == symbol);
+        let mut bid: Option<f64> = None;
+        let mut offer: Option<f64> = None;
+        for entry in live {
+            match entry.entry_type {
+                MdEntryType::Bid => bid = Some(bid.map_or(entry.price, |b| b.max(entry.price))),
+                MdEntryType::Offer => offer = Some(offer.map_or(entry.price, |o| o.min(entry.price))),
+                MdEntryType::Trade => {}
+            }
+        }
+        (bid, offer)
+    }
+}
+
+pub fn is_fix_message(data: &str) -> bool {
+    data.starts_with("8=FIX") || fields(data).any(|(tag, _)| tag == "35")
+}
+
+fn fields(data: &str) -> impl Iterator<Item = (&str, &str)> {
+    data.trim()
+        .split(['\x01', '|'])
+        .filter_map(|field| field.split_once('='))
+}
+
+fn parse_sending_time(value: &str) -> Option<u64> {
+    ["%Y%m%d-%H:%M:%S%.f", "%Y%m%d-%H:%M:%S"]
+        .iter()
+        .find_map(|format| NaiveDateTime::parse_from_str(value, format).ok())
+        .and_then(|time| time.and_utc().timestamp_nanos_opt())
+        .map(|nanos| nanos as u64)
+}
+
+pub fn parse_fix_market_data(data: &str) -> Result<FixMarketData, Box<dyn std::error::Error>> {
+    let mut message_type = None;
+    let mut symbol = Symbol::default();
+    let mut sequence = None;
+    let mut sending_time = None;
+    let mut entries: Vec<MdEntry> = Vec::new();
+    let mut awaiting_type = false;
+
+    for (tag, value) in fields(data) {
+        match tag {
+            "35" => {
+                message_type = Some(match value {
+                    "W" => FixMdMessageType::Snapshot,
+                    "X" => FixMdMessageType::Incremental,
+                    other => return Err(format!("Unsupported FIX market data MsgType: {}", other).into()),
+                })
+            }
+            "34" => sequence = Some(value.parse()?),
+            "52" => sending_time = parse_sending_time(value),
+            // MDUpdateAction starts an entry in incremental refreshes
+            "279" => {
+                awaiting_type = true;
+                entries.push(MdEntry {
+                    entry_type: MdEntryType::Trade,
+                    action: match value {
+                        "0" => MdUpdateAction::New,
+                        "1" => MdUpdateAction::Change,
+                        "2" => MdUpdateAction::Delete,
+                        other => return Err(format!("Unsupported MDUpdateAction: {}", other).into()),
+                    },
+                    symbol,
+                    price: 0.0,
+                    size: 0,
+                    aggressor: None,
+                });
+            }
+            "269" => {
+                let entry_type = match value {
+                    "0" => MdEntryType::Bid,
+                    "1" => MdEntryType::Offer,
+                    "2" => MdEntryType::Trade,
+                    other => return Err(format!("Unsupported MDEntryType: {}", other).into()),
+                };
+                // 269 completes an entry opened by 279, otherwise it starts a new one
+                match entries.last_mut() {
+                    Some(entry) if awaiting_type => entry.entry_type = entry_type,
+                    _ => entries.push(MdEntry {
+                        entry_type,
+                        action: MdUpdateAction::New,
+                        symbol,
+                        price: 0.0,
+                        size: 0,
+                        aggressor: None,
+                    }),
+                }
+                awaiting_type = false;
+            }
+            "55" => match entries.last_mut() {
+                Some(entry) => entry.symbol = Symbol::new(value),
+                None => symbol = Symbol::new(value),
+            },
+            "270" => {
+                let entry = entries.last_mut().ok_or("MDEntryPx outside of an MD entry")?;
+                entry.price = value.parse()?;
+            }
+            "271" => {
+                let entry = entries.last_mut().ok_or("MDEntrySize outside of an MD entry")?;
+                entry.size = value.parse::<f64>()? as u64;
+            }
+            "2446" => {
+                let entry = entries.last_mut().ok_or("AggressorSide outside of an MD entry")?;
+                entry.aggressor = match value {
+                    "1" => Some(AggressorSide::Buy),
+                    "2" => Some(AggressorSide::Sell),
+                    _ => None,
+                };
+            }
+            _ => {}
+        }
+    }
+
+    let message_type = message_type.ok_or("Missing MsgType (35)")?;
+    if symbol.is_empty() {
+        symbol = entries.first().map(|e| e.symbol).unwrap_or_default();
+    }
+    Ok(FixMarketData {
+        message_type,
+        symbol,
+        sequence,
+        sending_time,
+        entries,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_snapshot() {
+        let data = "8=FIX.4.4|35=W|34=7|52=20240102-14:30:00.250|55=AAPL|268=3|\
+                    269=0|270=189.10|271=300|269=1|270=189.20|271=200|269=2|270=189.15|271=50|10=000|";
+        let md = parse_fix_market_data(data).unwrap();
+        assert_eq!(md.message_type, FixMdMessageType::Snapshot);
+        assert_eq!(md.symbol.as_str(), "AAPL");
+        assert_eq!(md.sequence, Some(7));
+        assert_eq!(md.sending_time, Some(1_704_205_800_250_000_000));
+        assert_eq!(md.entries.len(), 3);
+        assert_eq!(md.best_quotes(md.symbol), (Some(189.10), Some(189.20)));
+        assert_eq!(md.last_trade(md.symbol).unwrap().size, 50);
+    }
+
+    #[test]
+    fn test_parse_incremental() {
+        let data = "8=FIX.4.4\x0135=X\x0134=8\x01268=2\x01\
+                    279=0\x01269=0\x0155=MSFT\x01270=410.5\x01271=100\x01\
+                    279=2\x01269=1\x0155=MSFT\x01270=410.9\x01271=0\x01";
+        let md = parse_fix_market_data(data).unwrap();
+        assert_eq!(md.message_type, FixMdMessageType::Incremental);
+        assert_eq!(md.symbol.as_str(), "MSFT");
+        assert_eq!(md.entries[0].entry_type, MdEntryType::Bid);
+        assert_eq!(md.entries[1].action, MdUpdateAction::Delete);
+        // the deleted offer does not count as a quote
+        assert_eq!(md.best_quotes(md.symbol), (Some(410.5), None));
+    }
+
+    #[test]
+    fn test_entries_per_symbol() {
+        let data = "8=FIX.4.4|35=X|279=0|269=2|55=AAPL|270=190|271=5|279=0|269=2|55=MSFT|270=410|271=7|\
+                    279=0|269=0|55=AAPL|270=189.9|271=100|";
+        let md = parse_fix_market_data(data).unwrap();
+        assert_eq!(md.symbols(), vec![Symbol::new("AAPL"), Symbol::new("MSFT")]);
+        assert_eq!(md.last_trade(Symbol::new("AAPL")).unwrap().price, 190.0);
+        assert_eq!(md.last_trade(Symbol::new("MSFT")).unwrap().price, 410.0);
+        assert_eq!(md.best_quotes(Symbol::new("MSFT")), (None, None));
+    }
+
+    #[test]
+    fn test_reject_other_message_types() {
+        assert!(parse_fix_market_data("8=FIX.4.4|35=D|55=AAPL|").is_err());
+        assert!(parse_fix_market_data("55=AAPL|270=1").is_err());
+    }
+}
@@ -1,3 +1,12 @@
 pub mod parser;
 pub mod order_book;
 pub mod aggregator;
+pub mod rolling_window;
+pub mod symbol;
+pub mod quality;
+pub mod fix_md;
+pub mod scan;
+pub mod consolidated_book;
+pub mod trade_tape;
+pub mod bar;
+pub mod warmup;
@@ -6,7 +6,7 @@ use log::debug;
 use crate::market_data::parser::Tick;
 
 // order side (buy or sell)
-#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
 pub enum OrderSide {
     Bid,
     Ask,
@@ -97,6 +97,7 @@ impl OrderBook {
                         for o in orders.iter_mut() {
                             if o.id == order_id {
                                 o.quantity = new_quantity;
+                                order.quantity = new_quantity;
                                 debug!("Updated order {} quantity to {}", order_id, new_quantity);
                                 return Some(());
                             }
@@ -108,6 +109,7 @@ impl OrderBook {
                         for o in orders.iter_mut() {
                             if o.id == order_id {
                                 o.quantity = new_quantity;
+                                order.quantity = new_quantity;
                                 debug!("Updated order {} quantity to {}", order_id, new_quantity);
                                 return Some(());
                             }
@@ -151,6 +153,30 @@ impl OrderBook {
         self.asks.keys().next().map(|of| of.0) // First key (lowest price)
     }
 
+    pub fn get_mid_price(&self) -> Option<f64> {
+        Some((self.get_best_bid()? + self.get_best_ask()?) / 2.0)
+    }
+
+    // aggregated (price, quantity) per bid level, best price first
+    pub fn bid_levels(&self) -> impl Iterator<Item = (f64, u64)> + '_ {
+        self.bids.iter().rev().map(|(price, orders)| (price.0, orders.iter().map(|o| o.quantity).sum()))
+    }
+
+    // aggregated (price, quantity) per ask level, best price first
+    pub fn ask_levels(&self) -> impl Iterator<Item = (f64, u64)> + '_ {
+        self.asks.iter().map(|(price, orders)| (price.0, orders.iter().map(|o| o.quantity).sum()))
+    }
+
+    // best bid above best ask
+    pub fn is_crossed(&self) -> bool {
+        matches!((self.get_best_bid(), self.get_best_ask()), (Some(bid), Some(ask)) if bid > ask)
+    }
+
+    // best bid equal to best ask
+    pub fn is_locked(&self) -> bool {
+        matches!((self.get_best_bid(), self.get_best_ask()), (Some(bid), Some(ask)) if bid == ask)
+    }
+
     pub fn get_order(&self, order_id: u64) -> Option<Order> {
         self.orders_by_id.get(&order_id).copied()
     }
@@ -186,6 +212,7 @@ mod tests {
 
         assert_eq!(order_book.get_best_bid(), Some(10.5));
         assert_eq!(order_book.get_best_ask(), Some(11.0));
+        assert_eq!(order_book.get_mid_price(), Some(10.75));
         assert_eq!(order_book.get_order(1), Some(order1));
         assert_eq!(order_book.get_order(5), None);
 
@@ -198,5 +225,14 @@ mod tests {
         order_book.remove_order(2);
         assert_eq!(order_book.get_order(2), None);
         assert_eq!(order_book.get_best_bid(), Some(10.0));
+        assert!(!order_book.is_crossed());
+        assert!(!order_book.is_locked());
+
+        order_book.add_order(Order { id: 5, price: 11.0, quantity: 1, side: OrderSide::Bid });
+        assert!(order_book.is_locked());
+        order_book.add_order(Order { id: 6, price: 11.2, quantity: 1, side: OrderSide::Bid });
+        assert!(order_book.is_crossed());
+        assert_eq!(order_book.bid_levels().collect::<Vec<_>>(), vec![(11.2, 1), (11.0, 1), (10.0, 20)]);
+        assert_eq!(order_book.ask_levels().next(), Some((11.0, 8)));
     }
 }
\ No newline at end of file
@@ -1,15 +1,66 @@
-use log::{debug, error};
+use crate::exchange::binary::MARKET_DATA_TICK;
+use crate::market_data::fix_md::{self, parse_fix_market_data, FixMarketData, FixMdMessageType, MdEntry};
+use crate::market_data::symbol::Symbol;
+use byteorder::{ByteOrder, LittleEndian};
+use crate::market_data::scan;
+use log::{error, info, trace};
+use std::cell::Cell;
+use std::str::FromStr;
+use std::time::{SystemTime, UNIX_EPOCH};
 
 // represents a tick of market data containing price and volume
-#[derive(Debug, Clone, Copy, PartialEq, PartialOrd)]
+#[derive(Debug, Clone, Copy, Default, PartialEq, PartialOrd)]
 pub struct Tick {
+    // empty when the feed does not name the instrument
+    pub symbol: Symbol,
     pub price: f64,
     pub volume: u64,
+    // event time in nanoseconds since the Unix epoch
+    pub timestamp: u64,
+    // feed sequence number, if the venue provides one
+    pub sequence: Option<u64>,
+    // aggressor of a trade print, if the venue flags it
+    pub aggressor: Option<AggressorSide>,
+}
+
+// one market data message: the ticks for the strategies and, for FIX W/X messages, the book entries.
+// a FIX message has a tick per instrument that traded in it; instruments whose quotes changed get theirs
+// from the book once the entries are applied, see `quote_tick`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct MarketUpdate {
+    pub ticks: Vec<Tick>,
+    pub book: Option<FixMarketData>,
+}
+
+impl MarketUpdate {
+    /// tick of an instrument whose touch the FIX message moved without a trade: the mid of the best bid
+    /// and ask in the book after the update, or the one side left
+    pub fn quote_tick(&self, symbol: Symbol, bid: Option<f64>, ask: Option<f64>) -> Option<Tick> {
+        Some(fix_tick(self.book.as_ref()?, symbol, touch_price(bid, ask)?, None))
+    }
+}
+
+// side that initiated a trade: the buyer lifting the offer or the seller hitting the bid
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Hash)]
+pub enum AggressorSide {
+    Buy,
+    Sell,
+}
+
+// current wall-clock time in nanoseconds since the Unix epoch,
+// used to stamp ticks from feeds that carry no event time
+pub fn now_nanos() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_nanos() as u64)
+        .unwrap_or_default()
 }
 
 /// responsible for parsing market data from various formats
 pub struct MarketDataParser {
     format: MarketDataFormat,
+    // format detected from the first message in `Auto` mode
+    detected: Cell<Option<MarketDataFormat>>,
 }
 
 // supported market data formats
@@ -18,53 +69,192 @@ pub enum MarketDataFormat {
     CSV,
     #[cfg(feature = "json")]
     JSON,
+    // FIX MarketDataSnapshotFullRefresh (35=W); incremental refreshes on the same feed are accepted too
+    FixSnapshot,
+    // FIX MarketDataIncrementalRefresh (35=X); snapshots on the same feed are accepted too
+    FixIncremental,
+    // `BinaryMessage` frames of type `MARKET_DATA_TICK`
+    Binary,
+    // detect the format from the first message and stick to it
+    Auto,
+}
+
+impl FromStr for MarketDataFormat {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "csv" => Ok(MarketDataFormat::CSV),
+            #[cfg(feature = "json")]
+            "json" => Ok(MarketDataFormat::JSON),
+            "fix_snapshot" | "fix_w" => Ok(MarketDataFormat::FixSnapshot),
+            "fix_incremental" | "fix_x" => Ok(MarketDataFormat::FixIncremental),
+            "binary" => Ok(MarketDataFormat::Binary),
+            "auto" => Ok(MarketDataFormat::Auto),
+            other => Err(format!("Unknown market data format: {}", other)),
+        }
+    }
+}
+
+// binary tick payload, little endian:
+// message type u8 | payload length u32 | price f64 | volume u64 | timestamp u64 | sequence u64 (0 = none) |
+// symbol length u8 | symbol bytes
+const BINARY_HEADER_LEN: usize = 5;
+const BINARY_TICK_FIXED_LEN: usize = 33;
+
+/// guesses the format of a raw market data message
+pub fn detect_format(data: &[u8]) -> Option<MarketDataFormat> {
+    if data.len() >= BINARY_HEADER_LEN && data[0] == MARKET_DATA_TICK {
+        let length = LittleEndian::read_u32(&data[1..BINARY_HEADER_LEN]) as usize;
+        if BINARY_HEADER_LEN + length == data.len() {
+            return Some(MarketDataFormat::Binary);
+        }
+    }
+
+    let text = std::str::from_utf8(data).ok()?.trim();
+    #[cfg(feature = "json")]
+    if text.starts_with('{') {
+        return Some(MarketDataFormat::JSON);
+    }
+    if fix_md::is_fix_message(text) {
+        return Some(if text.contains("35=X") {
+            MarketDataFormat::FixIncremental
+        } else {
+            MarketDataFormat::FixSnapshot
+        });
+    }
+    if text.contains(',') {
+        return Some(MarketDataFormat::CSV);
+    }
+    None
 }
 
 impl MarketDataParser {
     pub fn new(format: MarketDataFormat) -> Self {
-        MarketDataParser { format }
+        MarketDataParser { format, detected: Cell::new(None) }
+    }
+
+    // configured format, or the detected one in `Auto` mode once the first message arrived
+    pub fn format(&self) -> MarketDataFormat {
+        self.detected.get().unwrap_or(self.format)
+    }
+
+    fn resolve_format(&self, data: &[u8]) -> Result<MarketDataFormat, Box<dyn std::error::Error>> {
+        if self.format != MarketDataFormat::Auto {
+            return Ok(self.format);
+        }
+        if let Some(format) = self.detected.get() {
+            return Ok(format);
+        }
+        let format = detect_format(data).ok_or("Could not detect market data format")?;
+        info!("Detected market data format: {:?}", format);
+        self.detected.set(Some(format));
+        Ok(format)
     }
 
-    // Parses market data from a string into a Tick structure
+    // Parses market data from a string into a Tick structure; the feed itself goes through parse_update
+    #[cfg(test)]
     pub fn parse(&self, data: &str) -> Result<Tick, Box<dyn std::error::Error>> {
-        debug!("Parsing market data: {}", data);
-        match self.format {
-            MarketDataFormat::CSV => self.parse_csv(data),
+        self.parse_bytes(data.as_bytes())
+    }
+
+    /// parses a raw message straight from the receive buffer. CSV, JSON and binary ticks
+    /// are decoded without heap allocation.
+    pub fn parse_bytes(&self, data: &[u8]) -> Result<Tick, Box<dyn std::error::Error>> {
+        trace!("Parsing market data: {}", String::from_utf8_lossy(data));
+        let result = match self.resolve_format(data)? {
+            MarketDataFormat::CSV => scan::parse_csv(data),
             #[cfg(feature = "json")]
-            MarketDataFormat::JSON => self.parse_json(data),
+            MarketDataFormat::JSON => scan::parse_json(data),
+            MarketDataFormat::FixSnapshot | MarketDataFormat::FixIncremental => {
+                self.parse_fix(std::str::from_utf8(data)?)
+            }
+            MarketDataFormat::Binary => self.parse_binary(data),
+            MarketDataFormat::Auto => Err("Market data format has not been detected".into()),
+        };
+        if let Err(e) = &result {
+            error!("Invalid {:?} market data ({}): {}", self.format(), e, String::from_utf8_lossy(data));
         }
+        result
     }
 
-    /// parses market data from CSV format
-    fn parse_csv(&self, data: &str) -> Result<Tick, Box<dyn std::error::Error>> {
-        let parts: Vec<&str> = data.split(',').collect();
-        if parts.len() != 2 {
-            error!("Invalid CSV market data format: {}", data);
-            return Err("Invalid CSV market data format".into());
+    /// like `parse_bytes`, but FIX W/X messages also keep their entries for the order book
+    pub fn parse_update(&self, data: &[u8]) -> Result<MarketUpdate, Box<dyn std::error::Error>> {
+        match self.resolve_format(data)? {
+            MarketDataFormat::FixSnapshot | MarketDataFormat::FixIncremental => {
+                let md = std::str::from_utf8(data).map_err(Into::into).and_then(parse_fix_market_data);
+                let md = md.inspect_err(|e| {
+                    error!("Invalid {:?} market data ({}): {}", self.format(), e, String::from_utf8_lossy(data))
+                })?;
+                Ok(MarketUpdate { ticks: fix_trade_ticks(&md), book: Some(md) })
+            }
+            _ => Ok(MarketUpdate { ticks: vec![self.parse_bytes(data)?], book: None }),
         }
+    }
 
-        let price: f64 = parts[0].parse()?;
-        let volume: u64 = parts[1].parse()?;
-
-        Ok(Tick { price, volume })
+    // the first instrument that traded. without a book, quotes only give the touch in a snapshot,
+    // which is the venue's whole book
+    fn parse_fix(&self, data: &str) -> Result<Tick, Box<dyn std::error::Error>> {
+        let md = parse_fix_market_data(data)?;
+        let snapshot_mid = || {
+            let (bid, offer) = md.best_quotes(md.symbol);
+            let price = touch_price(bid, offer).filter(|_| md.message_type == FixMdMessageType::Snapshot)?;
+            Some(fix_tick(&md, md.symbol, price, None))
+        };
+        fix_trade_ticks(&md)
+            .into_iter()
+            .next()
+            .or_else(snapshot_mid)
+            .ok_or_else(|| "FIX market data message carries no trade or snapshot".into())
     }
 
-    /// parses market data from JSON format
-    #[cfg(feature = "json")]
-    fn parse_json(&self, data: &str) -> Result<Tick, Box<dyn std::error::Error>> {
-        #[derive(serde::Deserialize)]
-        struct JsonTick {
-            price: f64,
-            volume: u64,
+    /// parses a binary tick frame
+    fn parse_binary(&self, data: &[u8]) -> Result<Tick, Box<dyn std::error::Error>> {
+        if data.len() < BINARY_HEADER_LEN + BINARY_TICK_FIXED_LEN || data[0] != MARKET_DATA_TICK {
+            return Err("Invalid binary market data frame".into());
         }
+        let payload = &data[BINARY_HEADER_LEN..];
+        let symbol_len = payload[32] as usize;
+        let symbol_bytes = payload
+            .get(BINARY_TICK_FIXED_LEN..BINARY_TICK_FIXED_LEN + symbol_len)
+            .ok_or("Truncated symbol in binary market data")?;
+        let sequence = LittleEndian::read_u64(&payload[24..32]);
+        Ok(Tick {
+            symbol: Symbol::new(std::str::from_utf8(symbol_bytes)?),
+            price: LittleEndian::read_f64(&payload[0..8]),
+            volume: LittleEndian::read_u64(&payload[8..16]),
+            timestamp: LittleEndian::read_u64(&payload[16..24]),
+            sequence: (sequence != 0).then_some(sequence),
+            aggressor: None,
+        })
+    }
+}
 
-        match serde_json::from_str::<JsonTick>(data) {
-            Ok(json_tick) => Ok(Tick { price: json_tick.price, volume: json_tick.volume }),
-            Err(e) => {
-                error!("Invalid JSON market data format: {}", data);
-                Err(e.into())
-            }
-        }
+/// one tick per instrument that traded in a FIX W/X message, from its last trade
+fn fix_trade_ticks(md: &FixMarketData) -> Vec<Tick> {
+    md.symbols()
+        .into_iter()
+        .filter_map(|symbol| md.last_trade(symbol).map(|trade| fix_tick(md, symbol, trade.price, Some(trade))))
+        .collect()
+}
+
+fn fix_tick(md: &FixMarketData, symbol: Symbol, price: f64, trade: Option<&MdEntry>) -> Tick {
+    Tick {
+        symbol,
+        price,
+        volume: trade.map_or(0, |trade| trade.size),
+        timestamp: md.sending_time.unwrap_or_else(now_nanos),
+        sequence: md.sequence,
+        aggressor: trade.and_then(|trade| trade.aggressor),
+    }
+}
+
+// mid of the touch, or the one side quoted
+fn touch_price(bid: Option<f64>, ask: Option<f64>) -> Option<f64> {
+    match (bid, ask) {
+        (Some(bid), Some(ask)) => Some((bid + ask) / 2.0),
+        (Some(price), None) | (None, Some(price)) => Some(price),
+        (None, None) => None,
     }
 }
 
@@ -81,6 +271,23 @@ mod tests {
         assert_eq!(tick.volume, 100);
     }
 
+    #[test]
+    fn test_parse_csv_tick_with_timestamp() {
+        let parser = MarketDataParser::new(MarketDataFormat::CSV);
+        let tick = parser.parse("100.50,100,1700000000000000000").unwrap();
+        assert_eq!(tick.timestamp, 1_700_000_000_000_000_000);
+
+        // feeds without event time are stamped on arrival
+        let tick = parser.parse("100.50,100").unwrap();
+        assert!(tick.timestamp > 0);
+        assert!(tick.symbol.is_empty());
+
+        let tick = parser.parse("AAPL,100.50,100,42").unwrap();
+        assert_eq!(tick.symbol.as_str(), "AAPL");
+        assert_eq!(tick.price, 100.50);
+        assert_eq!(tick.timestamp, 42);
+    }
+
     #[test]
     fn test_parse_invalid_csv_tick() {
         let parser = MarketDataParser::new(MarketDataFormat::CSV);
@@ -97,6 +304,12 @@ mod tests {
         let tick = parser.parse(data).unwrap();
         assert_eq!(tick.price, 100.50);
         assert_eq!(tick.volume, 100);
+
+        let data = r#"{"symbol":"AAPL","price":100.50,"volume":100,"timestamp":7,"seq":12}"#;
+        let tick = parser.parse(data).unwrap();
+        assert_eq!(tick.symbol.as_str(), "AAPL");
+        assert_eq!(tick.timestamp, 7);
+        assert_eq!(tick.sequence, Some(12));
     }
 
     #[cfg(feature = "json")]
@@ -107,4 +320,109 @@ mod tests {
         let result = parser.parse(data);
         assert!(result.is_err());
     }
+
+    fn binary_frame(price: f64, volume: u64, timestamp: u64, sequence: u64, symbol: &str) -> Vec<u8> {
+        let mut payload = vec![0u8; BINARY_TICK_FIXED_LEN];
+        LittleEndian::write_f64(&mut payload[0..8], price);
+        LittleEndian::write_u64(&mut payload[8..16], volume);
+        LittleEndian::write_u64(&mut payload[16..24], timestamp);
+        LittleEndian::write_u64(&mut payload[24..32], sequence);
+        payload[32] = symbol.len() as u8;
+        payload.extend_from_slice(symbol.as_bytes());
+        crate::exchange::binary::BinaryMessage::new(MARKET_DATA_TICK, payload).serialize().unwrap()
+    }
+
+    #[test]
+    fn test_format_from_str() {
+        assert_eq!("csv".parse(), Ok(MarketDataFormat::CSV));
+        assert_eq!("FIX_W".parse(), Ok(MarketDataFormat::FixSnapshot));
+        assert_eq!("fix_incremental".parse(), Ok(MarketDataFormat::FixIncremental));
+        assert_eq!("auto".parse(), Ok(MarketDataFormat::Auto));
+        assert!("xml".parse::<MarketDataFormat>().is_err());
+    }
+
+    #[test]
+    fn test_parse_fix_tick() {
+        let parser = MarketDataParser::new(MarketDataFormat::FixSnapshot);
+        let tick = parser
+            .parse("8=FIX.4.4|35=W|34=3|55=AAPL|269=0|270=99.5|271=10|269=1|270=100.5|271=10|")
+            .unwrap();
+        assert_eq!(tick.symbol.as_str(), "AAPL");
+        assert_eq!(tick.price, 100.0);
+        assert_eq!(tick.volume, 0);
+        assert_eq!(tick.sequence, Some(3));
+
+        let parser = MarketDataParser::new(MarketDataFormat::FixIncremental);
+        let tick = parser.parse("8=FIX.4.4|35=X|279=0|269=2|55=MSFT|270=410.25|271=7|2446=2|").unwrap();
+        assert_eq!(tick.symbol.as_str(), "MSFT");
+        assert_eq!(tick.aggressor, Some(AggressorSide::Sell));
+        assert_eq!(tick.price, 410.25);
+        assert_eq!(tick.volume, 7);
+
+        assert!(parser.parse("8=FIX.4.4|35=X|55=MSFT|").is_err());
+
+        // a deleted level changes the book without a tick
+        let update = parser.parse_update(b"8=FIX.4.4|35=X|279=2|269=1|55=MSFT|270=410.5|271=0|").unwrap();
+        assert!(update.ticks.is_empty());
+        assert_eq!(update.book.unwrap().entries.len(), 1);
+        let csv = MarketDataParser::new(MarketDataFormat::CSV).parse_update(b"AAPL,100.5,10\n").unwrap();
+        assert_eq!((csv.ticks.iter().map(|tick| tick.price).collect(), csv.book), (vec![100.5], None));
+    }
+
+    #[test]
+    fn test_fix_ticks_per_symbol() {
+        let parser = MarketDataParser::new(MarketDataFormat::FixIncremental);
+        // AAPL and MSFT trade in one message, each tick takes only its own instrument's print
+        let update = parser
+            .parse_update(b"8=FIX.4.4|35=X|34=5|279=0|269=2|55=AAPL|270=190|271=5|279=0|269=0|55=AAPL|270=189.9|271=100|\
+                            279=0|269=2|55=MSFT|270=410|271=7|2446=1|")
+            .unwrap();
+        let ticks: Vec<_> = update.ticks.iter().map(|tick| (tick.symbol.as_str(), tick.price, tick.volume)).collect();
+        assert_eq!(ticks, vec![("AAPL", 190.0, 5), ("MSFT", 410.0, 7)]);
+        assert_eq!(update.ticks[1].aggressor, Some(AggressorSide::Buy));
+        assert!(update.ticks.iter().all(|tick| tick.sequence == Some(5)));
+
+        // quotes alone give no tick: the touch is only known once the book has the entries
+        let update = parser.parse_update(b"8=FIX.4.4|35=X|279=1|269=0|55=AAPL|270=189.5|271=300|").unwrap();
+        assert!(update.ticks.is_empty());
+        let tick = update.quote_tick(Symbol::new("AAPL"), Some(189.9), Some(190.1)).unwrap();
+        assert_eq!((tick.price, tick.volume), (190.0, 0));
+        assert_eq!(update.quote_tick(Symbol::new("AAPL"), None, None), None);
+        assert!(parser.parse("8=FIX.4.4|35=X|279=1|269=0|55=AAPL|270=189.5|271=300|").is_err());
+    }
+
+    #[test]
+    fn test_parse_binary_tick() {
+        let parser = MarketDataParser::new(MarketDataFormat::Binary);
+        let tick = parser.parse_bytes(&binary_frame(101.25, 40, 1_000, 9, "AAPL")).unwrap();
+        assert_eq!(tick.symbol.as_str(), "AAPL");
+        assert_eq!(tick.price, 101.25);
+        assert_eq!(tick.volume, 40);
+        assert_eq!(tick.timestamp, 1_000);
+        assert_eq!(tick.sequence, Some(9));
+
+        let tick = parser.parse_bytes(&binary_frame(1.0, 1, 1, 0, "")).unwrap();
+        assert_eq!(tick.sequence, None);
+
+        let mut truncated = binary_frame(1.0, 1, 1, 0, "AAPL");
+        truncated.pop();
+        assert!(parser.parse_bytes(&truncated).is_err());
+    }
+
+    #[test]
+    fn test_auto_detect_format() {
+        assert_eq!(detect_format(b"AAPL,100.5,10"), Some(MarketDataFormat::CSV));
+        assert_eq!(detect_format(b"8=FIX.4.4|35=X|"), Some(MarketDataFormat::FixIncremental));
+        assert_eq!(detect_format(&binary_frame(1.0, 1, 1, 0, "A")), Some(MarketDataFormat::Binary));
+        assert_eq!(detect_format(b"HELLO"), None);
+        #[cfg(feature = "json")]
+        assert_eq!(detect_format(b" {\"price\":1.0,\"volume\":1}\n"), Some(MarketDataFormat::JSON));
+
+        // the first message fixes the format for the rest of the session
+        let parser = MarketDataParser::new(MarketDataFormat::Auto);
+        assert!(parser.parse_bytes(b"HELLO").is_err());
+        assert_eq!(parser.parse_bytes(b"AAPL,100.5,10\n").unwrap().price, 100.5);
+        assert_eq!(parser.format(), MarketDataFormat::CSV);
+        assert!(parser.parse_bytes(b"8=FIX.4.4|35=X|").is_err());
+    }
 }
@@ -0,0 +1,377 @@
+use crate::config::DataQualityConfig;
+use crate::market_data::fix_md::FixMdMessageType;
+use crate::market_data::order_book::OrderBook;
+use crate::market_data::parser::{MarketUpdate, Tick};
+use crate::market_data::symbol::Symbol;
+use log::{info, warn};
+use std::collections::HashMap;
+use std::fmt;
+
+const NANOS_PER_MILLI: u64 = 1_000_000;
+
+// problems detected in the incoming market data
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum QualityIssue {
+    InvalidPrice { price: f64 },
+    StaleFeed { silent_ms: u64 },
+    PriceSpike { price: f64, reference: f64 },
+    CrossedBook { bid: f64, ask: f64 },
+    LockedBook { price: f64 },
+    OutOfOrder { last: u64, received: u64 },
+    Duplicate { sequence: u64 },
+}
+
+impl fmt::Display for QualityIssue {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            QualityIssue::InvalidPrice { price } => write!(f, "invalid price {}", price),
+            QualityIssue::StaleFeed { silent_ms } => write!(f, "no update for {}ms", silent_ms),
+            QualityIssue::PriceSpike { price, reference } => {
+                write!(f, "price {} outside band around {}", price, reference)
+            }
+            QualityIssue::CrossedBook { bid, ask } => write!(f, "crossed book: bid {} > ask {}", bid, ask),
+            QualityIssue::LockedBook { price } => write!(f, "locked book at {}", price),
+            QualityIssue::OutOfOrder { last, received } => {
+                write!(f, "sequence {} received after {}", received, last)
+            }
+            QualityIssue::Duplicate { sequence } => write!(f, "duplicate sequence {}", sequence),
+        }
+    }
+}
+
+// per-instrument data quality state. an instrument is tradeable while it has no open issue.
+#[derive(Debug, Clone, Default)]
+pub struct InstrumentStatus {
+    pub last_update: u64,
+    pub last_price: Option<f64>,
+    pub last_sequence: Option<u64>,
+    stale: Option<QualityIssue>,
+    price: Option<QualityIssue>,
+    book: Option<QualityIssue>,
+    // candidate new price level and how many consecutive ticks confirmed it
+    pending_level: Option<(f64, u32)>,
+}
+
+impl InstrumentStatus {
+    pub fn is_tradeable(&self) -> bool {
+        self.issues().next().is_none()
+    }
+
+    pub fn issues(&self) -> impl Iterator<Item = &QualityIssue> {
+        self.stale.iter().chain(self.price.iter()).chain(self.book.iter())
+    }
+}
+
+// data-quality layer between the parser and the strategies
+pub struct DataQualityGuard {
+    config: DataQualityConfig,
+    instruments: HashMap<Symbol, InstrumentStatus>,
+}
+
+impl DataQualityGuard {
+    pub fn new(config: DataQualityConfig) -> Self {
+        DataQualityGuard {
+            config,
+            instruments: HashMap::new(),
+        }
+    }
+
+    /// checks the sequence of a message received at `now` (nanoseconds) for each instrument it updates,
+    /// before its entries reach the book. the ticks and book entries of an instrument that already saw
+    /// this sequence or a later one are dropped, without affecting tradeability; the issues are returned.
+    pub fn check_sequence(&mut self, update: &mut MarketUpdate, now: u64) -> Vec<(Symbol, QualityIssue)> {
+        let sequenced: Vec<(Symbol, Option<u64>)> = match update.book.as_ref() {
+            Some(md) => md.symbols().into_iter().map(|symbol| (symbol, md.sequence)).collect(),
+            None => update.ticks.iter().map(|tick| (tick.symbol, tick.sequence)).collect(),
+        };
+        let mut issues = Vec::new();
+        for (symbol, sequence) in sequenced {
+            if let Err(issue) = self.sequence_received(symbol, sequence, now) {
+                issues.push((symbol, issue));
+            }
+        }
+
+        let rejected = |symbol: &Symbol| issues.iter().any(|(issued, _)| issued == symbol);
+        update.ticks.retain(|tick| !rejected(&tick.symbol));
+        if let Some(md) = update.book.as_mut() {
+            let entries = std::mem::take(&mut md.entries);
+            md.entries = entries.into_iter().filter(|entry| !rejected(&md.entry_symbol(entry))).collect();
+        }
+        // a snapshot would clear the book it no longer has the entries for
+        if update.book.as_ref().is_some_and(|md| md.message_type == FixMdMessageType::Snapshot && rejected(&md.symbol)) {
+            update.book = None;
+        }
+        issues
+    }
+
+    fn sequence_received(&mut self, symbol: Symbol, sequence: Option<u64>, now: u64) -> Result<(), QualityIssue> {
+        let status = self.instruments.entry(symbol).or_default();
+        if let (Some(sequence), Some(last)) = (sequence, status.last_sequence) {
+            if sequence == last {
+                return Err(QualityIssue::Duplicate { sequence });
+            }
+            if sequence < last {
+                return Err(QualityIssue::OutOfOrder { last, received: sequence });
+            }
+        }
+
+        status.last_update = now;
+        if sequence.is_some() {
+            status.last_sequence = sequence;
+        }
+        if status.stale.take().is_some() {
+            info!("Feed for {} recovered", symbol);
+        }
+        Ok(())
+    }
+
+    /// validates the price of a tick whose message passed `check_sequence`. `Err` means the tick must be dropped.
+    /// invalid prices and spikes mark the instrument untradeable until prices settle.
+    pub fn check_tick(&mut self, tick: &Tick) -> Result<(), QualityIssue> {
+        let max_deviation = self.config.max_price_deviation;
+        let confirmations = self.config.spike_confirmations;
+        let status = self.instruments.entry(tick.symbol).or_default();
+
+        if !tick.price.is_finite() || tick.price <= 0.0 {
+            let issue = QualityIssue::InvalidPrice { price: tick.price };
+            status.price = Some(issue);
+            return Err(issue);
+        }
+
+        let reference = match status.last_price {
+            Some(reference) => reference,
+            None => {
+                status.last_price = Some(tick.price);
+                status.price = None;
+                return Ok(());
+            }
+        };
+
+        if (tick.price - reference).abs() / reference <= max_deviation {
+            status.last_price = Some(tick.price);
+            status.price = None;
+            status.pending_level = None;
+            return Ok(());
+        }
+
+        // a jump is only accepted once enough consecutive ticks agree on the new level
+        let confirmed = match status.pending_level {
+            Some((level, count)) if (tick.price - level).abs() / level <= max_deviation => count + 1,
+            _ => 1,
+        };
+        if confirmed >= confirmations {
+            info!("{} repriced from {} to {}", tick.symbol, reference, tick.price);
+            status.last_price = Some(tick.price);
+            status.price = None;
+            status.pending_level = None;
+            return Ok(());
+        }
+        status.pending_level = Some((tick.price, confirmed));
+        let issue = QualityIssue::PriceSpike { price: tick.price, reference };
+        status.price = Some(issue);
+        Err(issue)
+    }
+
+    /// flags crossed books, and locked books when `reject_locked_books` is set
+    pub fn check_book(&mut self, symbol: Symbol, book: &OrderBook) -> Result<(), QualityIssue> {
+        let issue = match (book.get_best_bid(), book.get_best_ask()) {
+            (Some(bid), Some(ask)) if book.is_crossed() => Some(QualityIssue::CrossedBook { bid, ask }),
+            (Some(bid), Some(_)) if book.is_locked() && self.config.reject_locked_books => {
+                Some(QualityIssue::LockedBook { price: bid })
+            }
+            _ => None,
+        };
+        let status = self.instruments.entry(symbol).or_default();
+        status.book = issue;
+        match issue {
+            Some(issue) => Err(issue),
+            None => Ok(()),
+        }
+    }
+
+    /// marks instruments without an update for `stale_after_ms` as stale.
+    /// returns the instruments that just went stale.
+    pub fn check_stale(&mut self, now: u64) -> Vec<Symbol> {
+        let stale_after = self.config.stale_after_ms * NANOS_PER_MILLI;
+        let mut newly_stale = Vec::new();
+        for (symbol, status) in self.instruments.iter_mut() {
+            let silent = now.saturating_sub(status.last_update);
+            if silent > stale_after && status.stale.is_none() {
+                let issue = QualityIssue::StaleFeed { silent_ms: silent / NANOS_PER_MILLI };
+                warn!("Market data for {} is stale: {}", symbol, issue);
+                status.stale = Some(issue);
+                newly_stale.push(*symbol);
+            }
+        }
+        newly_stale
+    }
+
+    // instruments we have never seen data for are not tradeable
+    pub fn is_tradeable(&self, symbol: &Symbol) -> bool {
+        self.instruments.get(symbol).is_some_and(InstrumentStatus::is_tradeable)
+    }
+
+    pub fn status(&self, symbol: &Symbol) -> Option<&InstrumentStatus> {
+        self.instruments.get(symbol)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::market_data::consolidated_book::ConsolidatedBook;
+    use crate::market_data::order_book::{Order, OrderSide};
+    use crate::market_data::parser::{MarketDataFormat, MarketDataParser};
+
+    const MS: u64 = 1_000_000;
+
+    fn config() -> DataQualityConfig {
+        DataQualityConfig {
+            stale_after_ms: 100,
+            max_price_deviation: 0.05,
+            spike_confirmations: 2,
+            reject_locked_books: true,
+        }
+    }
+
+    fn tick(price: f64, sequence: Option<u64>) -> Tick {
+        Tick { symbol: Symbol::new("AAPL"), price, volume: 1, sequence, ..Default::default() }
+    }
+
+    // a tick as its own message: the sequence, then the price checks
+    fn receive(guard: &mut DataQualityGuard, tick: Tick, now: u64) -> Result<(), QualityIssue> {
+        let mut update = MarketUpdate { ticks: vec![tick], book: None };
+        match guard.check_sequence(&mut update, now).pop() {
+            Some((_, issue)) => Err(issue),
+            None => guard.check_tick(&tick),
+        }
+    }
+
+    #[test]
+    fn test_sequence_and_spike_checks() {
+        let mut guard = DataQualityGuard::new(config());
+        let symbol = Symbol::new("AAPL");
+        assert!(!guard.is_tradeable(&symbol));
+
+        assert_eq!(receive(&mut guard, tick(100.0, Some(1)), 0), Ok(()));
+        assert!(guard.is_tradeable(&symbol));
+        assert_eq!(receive(&mut guard, tick(100.0, Some(1)), 0), Err(QualityIssue::Duplicate { sequence: 1 }));
+        assert_eq!(
+            receive(&mut guard, tick(100.0, Some(0)), 0),
+            Err(QualityIssue::OutOfOrder { last: 1, received: 0 })
+        );
+        assert!(guard.is_tradeable(&symbol));
+
+        // a single print 20% away is rejected and halts the instrument
+        assert!(receive(&mut guard, tick(120.0, Some(2)), 0).is_err());
+        assert!(!guard.is_tradeable(&symbol));
+        // back inside the band
+        assert_eq!(receive(&mut guard, tick(101.0, Some(3)), 0), Ok(()));
+        assert!(guard.is_tradeable(&symbol));
+
+        // a sustained move is accepted after enough confirmations
+        assert!(receive(&mut guard, tick(120.0, Some(4)), 0).is_err());
+        assert_eq!(receive(&mut guard, tick(121.0, Some(5)), 0), Ok(()));
+        assert!(guard.is_tradeable(&symbol));
+        assert_eq!(guard.status(&symbol).unwrap().last_price, Some(121.0));
+
+        assert!(receive(&mut guard, tick(-1.0, None), 0).is_err());
+        assert!(!guard.is_tradeable(&symbol));
+    }
+
+    #[test]
+    fn test_stale_feed() {
+        let mut guard = DataQualityGuard::new(config());
+        let symbol = Symbol::new("AAPL");
+        receive(&mut guard, tick(100.0, None), 0).unwrap();
+        assert!(guard.check_stale(50 * MS).is_empty());
+        assert_eq!(guard.check_stale(150 * MS), vec![symbol]);
+        assert!(guard.check_stale(200 * MS).is_empty()); // reported once
+        assert!(!guard.is_tradeable(&symbol));
+
+        receive(&mut guard, tick(100.0, None), 210 * MS).unwrap();
+        assert!(guard.is_tradeable(&symbol));
+    }
+
+    #[test]
+    fn test_crossed_and_locked_books() {
+        let mut guard = DataQualityGuard::new(config());
+        let symbol = Symbol::new("AAPL");
+        receive(&mut guard, tick(100.0, None), 0).unwrap();
+
+        let mut book = OrderBook::new();
+        book.add_order(Order { id: 1, price: 100.0, quantity: 1, side: OrderSide::Bid });
+        book.add_order(Order { id: 2, price: 100.1, quantity: 1, side: OrderSide::Ask });
+        assert_eq!(guard.check_book(symbol, &book), Ok(()));
+
+        book.add_order(Order { id: 3, price: 100.1, quantity: 1, side: OrderSide::Bid });
+        assert_eq!(guard.check_book(symbol, &book), Err(QualityIssue::LockedBook { price: 100.1 }));
+
+        book.add_order(Order { id: 4, price: 100.2, quantity: 1, side: OrderSide::Bid });
+        assert!(matches!(guard.check_book(symbol, &book), Err(QualityIssue::CrossedBook { .. })));
+        assert!(!guard.is_tradeable(&symbol));
+
+        book.remove_order(3);
+        book.remove_order(4);
+        assert_eq!(guard.check_book(symbol, &book), Ok(()));
+        assert!(guard.is_tradeable(&symbol));
+    }
+
+    #[test]
+    fn test_crossed_feed_halts_instrument() {
+        let mut guard = DataQualityGuard::new(config());
+        let parser = MarketDataParser::new(MarketDataFormat::FixSnapshot);
+        let mut books = ConsolidatedBook::new();
+        let symbol = Symbol::new("AAPL");
+
+        // the venue's snapshot has its bid through its own offer
+        let messages = [
+            ("8=FIX.4.4|35=W|34=1|55=AAPL|269=0|270=100.2|271=5|269=1|270=100.1|271=5|", false),
+            ("8=FIX.4.4|35=X|34=2|279=2|269=0|55=AAPL|270=100.2|271=0|279=0|269=0|55=AAPL|270=100|271=5|", true),
+        ];
+        for (message, tradeable) in messages {
+            let mut update = parser.parse_update(message.as_bytes()).unwrap();
+            assert!(guard.check_sequence(&mut update, 0).is_empty());
+            assert_eq!(books.apply("XNAS", update.book.as_ref().unwrap()), vec![symbol]);
+            let result = guard.check_book(symbol, books.venue(&symbol, "XNAS").unwrap());
+            assert_eq!(result.is_ok(), tradeable);
+            assert_eq!(guard.is_tradeable(&symbol), tradeable);
+        }
+    }
+
+    #[test]
+    fn test_duplicate_fix_message_skips_the_book() {
+        let mut guard = DataQualityGuard::new(config());
+        let parser = MarketDataParser::new(MarketDataFormat::FixIncremental);
+        let mut books = ConsolidatedBook::new();
+        let (aapl, msft) = (Symbol::new("AAPL"), Symbol::new("MSFT"));
+        // issues of the message and the trades left
+        let mut receive = |books: &mut ConsolidatedBook, message: &str| {
+            let mut update = parser.parse_update(message.as_bytes()).unwrap();
+            let issues = guard.check_sequence(&mut update, 0);
+            if let Some(md) = update.book.as_ref() {
+                books.apply("XNAS", md);
+            }
+            (issues, update.ticks.len())
+        };
+
+        let first = "8=FIX.4.4|35=X|34=1|279=0|269=0|55=AAPL|270=100|271=5|";
+        assert_eq!(receive(&mut books, first), (vec![], 0));
+        let second = "8=FIX.4.4|35=X|34=2|279=1|269=0|55=AAPL|270=100|271=8|279=0|269=2|55=AAPL|270=100|271=1|";
+        assert_eq!(receive(&mut books, second), (vec![], 1));
+        // a replayed message would set the level back to what it was before the later one
+        assert_eq!(receive(&mut books, first), (vec![(aapl, QualityIssue::OutOfOrder { last: 2, received: 1 })], 0));
+        assert_eq!(receive(&mut books, second), (vec![(aapl, QualityIssue::Duplicate { sequence: 2 })], 0));
+        assert_eq!(books.top_of_book(&aapl).0, Some((100.0, 8)));
+
+        // in a message for two instruments only the one that saw the sequence is dropped
+        let late = "8=FIX.4.4|35=X|34=1|279=0|269=0|55=MSFT|270=410|271=3|279=2|269=0|55=AAPL|270=100|271=0|";
+        assert_eq!(receive(&mut books, late), (vec![(aapl, QualityIssue::OutOfOrder { last: 2, received: 1 })], 0));
+        assert_eq!(books.top_of_book(&aapl).0, Some((100.0, 8)));
+        assert_eq!(books.top_of_book(&msft).0, Some((410.0, 3)));
+
+        // a rejected snapshot does not clear the book
+        assert_eq!(receive(&mut books, "8=FIX.4.4|35=W|34=2|55=AAPL|269=1|270=101|271=5|").0.len(), 1);
+        assert_eq!(books.top_of_book(&aapl), (Some((100.0, 8)), None));
+    }
+}
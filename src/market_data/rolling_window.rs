@@ -0,0 +1,140 @@
+use std::collections::VecDeque;
+use std::time::Duration;
+
+// how far back a rolling window reaches
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum WindowSpec {
+    // the last N samples
+    Ticks(usize),
+    // samples whose event timestamp lies within the duration before the newest sample
+    Time(Duration),
+}
+
+#[derive(Debug, Clone, Copy)]
+struct Sample {
+    timestamp: u64,
+    value: f64,
+}
+
+// rolling window over timestamped values.
+// the sum is updated incrementally, so every push is amortised O(1) regardless of how many samples the window holds.
+pub struct RollingWindow {
+    spec: WindowSpec,
+    samples: VecDeque<Sample>,
+    sum: f64,
+    last_timestamp: u64,
+}
+
+impl RollingWindow {
+    pub fn new(spec: WindowSpec) -> Self {
+        RollingWindow {
+            spec,
+            samples: VecDeque::new(),
+            sum: 0.0,
+            last_timestamp: 0,
+        }
+    }
+
+    /// adds a value observed at `timestamp` (nanoseconds) and evicts everything that fell out of the window.
+    /// timestamps that go backwards are clamped to the newest one seen so far.
+    pub fn push(&mut self, timestamp: u64, value: f64) {
+        let timestamp = timestamp.max(self.last_timestamp);
+        self.last_timestamp = timestamp;
+
+        self.samples.push_back(Sample { timestamp, value });
+        self.sum += value;
+        self.expire(timestamp);
+    }
+
+    /// evicts samples that are too old relative to `now` (nanoseconds).
+    /// useful to age out a time window when the feed goes quiet.
+    pub fn expire(&mut self, now: u64) {
+        match self.spec {
+            WindowSpec::Ticks(size) => {
+                while self.samples.len() > size {
+                    self.pop_front();
+                }
+            }
+            WindowSpec::Time(duration) => {
+                let cutoff = match now.checked_sub(duration.as_nanos() as u64) {
+                    Some(cutoff) => cutoff,
+                    None => return, // nothing can be older than the window yet
+                };
+                while self.samples.front().is_some_and(|s| s.timestamp <= cutoff) {
+                    self.pop_front();
+                }
+            }
+        }
+    }
+
+    fn pop_front(&mut self) {
+        if let Some(oldest) = self.samples.pop_front() {
+            self.sum -= oldest.value;
+        }
+        if self.samples.is_empty() {
+            // drop accumulated rounding error whenever the window drains
+            self.sum = 0.0;
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.samples.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.samples.is_empty()
+    }
+
+    pub fn mean(&self) -> Option<f64> {
+        if self.samples.is_empty() {
+            return None;
+        }
+        Some(self.sum / self.samples.len() as f64)
+    }
+
+    // values from oldest to newest
+    pub fn values(&self) -> impl Iterator<Item = f64> + '_ {
+        self.samples.iter().map(|s| s.value)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const MS: u64 = 1_000_000;
+
+    #[test]
+    fn test_tick_window() {
+        let mut window = RollingWindow::new(WindowSpec::Ticks(3));
+        for (i, value) in [5.0, 1.0, 4.0, 3.0].iter().enumerate() {
+            window.push(i as u64, *value);
+        }
+        assert_eq!(window.len(), 3);
+        assert_eq!(window.mean(), Some(8.0 / 3.0));
+
+        window.push(4, 2.0);
+        assert_eq!(window.mean(), Some(3.0)); // 1.0 has been evicted
+        assert_eq!(window.values().collect::<Vec<_>>(), vec![4.0, 3.0, 2.0]);
+    }
+
+    #[test]
+    fn test_time_window() {
+        let mut window = RollingWindow::new(WindowSpec::Time(Duration::from_millis(500)));
+        window.push(0, 10.0);
+        window.push(100 * MS, 20.0);
+        window.push(400 * MS, 30.0);
+        assert_eq!(window.len(), 3);
+        assert_eq!(window.mean(), Some(20.0));
+
+        // 10.0 at t=0 is now exactly 500ms old and leaves the window
+        window.push(500 * MS, 6.0);
+        assert_eq!(window.len(), 3);
+        assert_eq!(window.mean(), Some(56.0 / 3.0));
+
+        // a quiet feed ages everything out
+        window.expire(2_000 * MS);
+        assert!(window.is_empty());
+        assert_eq!(window.mean(), None);
+    }
+}
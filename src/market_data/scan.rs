@@ -0,0 +1,306 @@
+#[cfg(feature = "json")]
+use crate::market_data::parser::AggressorSide;
+use crate::market_data::parser::{now_nanos, Tick};
+use crate::market_data::symbol::Symbol;
+use std::str::from_utf8;
+
+// allocation-free tick decoding straight from the receive buffer.
+// only the error paths allocate (boxing the error message).
+
+type ScanResult<T> = Result<T, Box<dyn std::error::Error>>;
+
+const MAX_CSV_FIELDS: usize = 4;
+
+fn parse_f64(field: &[u8]) -> ScanResult<f64> {
+    Ok(from_utf8(field.trim_ascii())?.parse()?)
+}
+
+// digits only; cheaper than going through `str::parse`
+fn parse_u64(field: &[u8]) -> ScanResult<u64> {
+    let digits = field.trim_ascii();
+    if digits.is_empty() {
+        return Err("Empty integer in market data".into());
+    }
+    digits.iter().try_fold(0u64, |value, &b| {
+        if !b.is_ascii_digit() {
+            return Err("Invalid integer in market data".into());
+        }
+        value
+            .checked_mul(10)
+            .and_then(|v| v.checked_add((b - b'0') as u64))
+            .ok_or_else(|| "Integer overflow in market data".into())
+    })
+}
+
+fn parse_symbol(field: &[u8]) -> ScanResult<Symbol> {
+    Ok(Symbol::new(from_utf8(field.trim_ascii())?))
+}
+
+// unrecognised flags are treated as unflagged and left to the trade classifier
+#[cfg(feature = "json")]
+fn parse_aggressor(field: &[u8]) -> Option<AggressorSide> {
+    match field {
+        b"buy" | b"Buy" | b"BUY" | b"B" | b"b" => Some(AggressorSide::Buy),
+        b"sell" | b"Sell" | b"SELL" | b"S" | b"s" => Some(AggressorSide::Sell),
+        _ => None,
+    }
+}
+
+/// `[symbol,]price,volume[,timestamp]`
+pub fn parse_csv(data: &[u8]) -> ScanResult<Tick> {
+    let mut fields: [&[u8]; MAX_CSV_FIELDS] = [&[]; MAX_CSV_FIELDS];
+    let mut count = 0;
+    for field in data.trim_ascii().split(|&b| b == b',') {
+        if count == MAX_CSV_FIELDS {
+            return Err("Invalid CSV market data format".into());
+        }
+        fields[count] = field;
+        count += 1;
+    }
+
+    let mut fields = &fields[..count];
+    let mut symbol = Symbol::default();
+    // checked without building an error, which would allocate
+    let is_number = |field: &[u8]| from_utf8(field.trim_ascii()).is_ok_and(|f| f.parse::<f64>().is_ok());
+    if fields.len() > 2 && !is_number(fields[0]) {
+        symbol = parse_symbol(fields[0])?;
+        fields = &fields[1..];
+    }
+    if fields.len() != 2 && fields.len() != 3 {
+        return Err("Invalid CSV market data format".into());
+    }
+
+    Ok(Tick {
+        symbol,
+        price: parse_f64(fields[0])?,
+        volume: parse_u64(fields[1])?,
+        timestamp: match fields.get(2) {
+            Some(ts) => parse_u64(ts)?,
+            None => now_nanos(),
+        },
+        sequence: None,
+        aggressor: None,
+    })
+}
+
+// cursor over a flat JSON object
+#[cfg(feature = "json")]
+struct JsonScanner<'a> {
+    data: &'a [u8],
+    pos: usize,
+    // a string with a backslash escape was met; the message is left to serde_json
+    escaped: bool,
+}
+
+#[cfg(feature = "json")]
+impl<'a> JsonScanner<'a> {
+    fn skip_whitespace(&mut self) {
+        while self.data.get(self.pos).is_some_and(u8::is_ascii_whitespace) {
+            self.pos += 1;
+        }
+    }
+
+    fn peek(&mut self) -> Option<u8> {
+        self.skip_whitespace();
+        self.data.get(self.pos).copied()
+    }
+
+    fn expect(&mut self, byte: u8) -> ScanResult<()> {
+        if self.peek() != Some(byte) {
+            return Err("Unexpected character in JSON market data".into());
+        }
+        self.pos += 1;
+        Ok(())
+    }
+
+    // string contents without the quotes; strings with escapes are not decoded here
+    fn string(&mut self) -> ScanResult<&'a [u8]> {
+        self.expect(b'"')?;
+        let rest = &self.data[self.pos..];
+        match rest.iter().position(|&b| b == b'"' || b == b'\\') {
+            Some(end) if rest[end] == b'"' => {
+                self.pos += end + 1;
+                Ok(&rest[..end])
+            }
+            Some(_) => {
+                self.escaped = true;
+                Err("Escaped string in JSON market data".into())
+            }
+            None => Err("Unterminated string in JSON market data".into()),
+        }
+    }
+
+    // raw bytes of a number or literal (true/false/null)
+    fn scalar(&mut self) -> &'a [u8] {
+        self.skip_whitespace();
+        let rest = &self.data[self.pos..];
+        let end = rest
+            .iter()
+            .position(|&b| matches!(b, b',' | b'}' | b']') || b.is_ascii_whitespace())
+            .unwrap_or(rest.len());
+        self.pos += end;
+        &rest[..end]
+    }
+
+    // skips a value of a field we do not use, including nested objects and arrays
+    fn skip_value(&mut self) -> ScanResult<()> {
+        match self.peek() {
+            Some(b'"') => self.string().map(|_| ()),
+            Some(b'{') | Some(b'[') => {
+                let mut depth = 0usize;
+                loop {
+                    match self.peek() {
+                        Some(b'"') => {
+                            self.string()?;
+                            continue;
+                        }
+                        Some(b'{') | Some(b'[') => depth += 1,
+                        Some(b'}') | Some(b']') => depth -= 1,
+                        Some(_) => {}
+                        None => return Err("Unterminated value in JSON market data".into()),
+                    }
+                    self.pos += 1;
+                    if depth == 0 {
+                        return Ok(());
+                    }
+                }
+            }
+            _ => {
+                if self.scalar().is_empty() {
+                    return Err("Missing value in JSON market data".into());
+                }
+                Ok(())
+            }
+        }
+    }
+}
+
+/// flat tick object: `{"symbol", "price", "volume", "timestamp", "sequence"|"seq", "side"|"aggressor"}`;
+/// price and volume are required, unknown fields are skipped. messages with escaped strings
+/// go through serde_json, which allocates.
+#[cfg(feature = "json")]
+pub fn parse_json(data: &[u8]) -> ScanResult<Tick> {
+    let mut scanner = JsonScanner { data, pos: 0, escaped: false };
+    match scan_json(&mut scanner) {
+        Err(_) if scanner.escaped => parse_json_escaped(data),
+        result => result,
+    }
+}
+
+#[cfg(feature = "json")]
+fn scan_json(scanner: &mut JsonScanner) -> ScanResult<Tick> {
+    let mut tick = Tick::default();
+    let mut price = None;
+    let mut volume = None;
+    let mut timestamp = None;
+
+    scanner.expect(b'{')?;
+    if scanner.peek() == Some(b'}') {
+        scanner.pos += 1;
+    } else {
+        loop {
+            let key = scanner.string()?;
+            scanner.expect(b':')?;
+            let is_null = scanner.peek() == Some(b'n');
+            match key {
+                b"symbol" if !is_null => tick.symbol = parse_symbol(scanner.string()?)?,
+                b"price" => price = Some(parse_f64(scanner.scalar())?),
+                b"volume" => volume = Some(parse_u64(scanner.scalar())?),
+                b"timestamp" if !is_null => timestamp = Some(parse_u64(scanner.scalar())?),
+                b"sequence" | b"seq" if !is_null => tick.sequence = Some(parse_u64(scanner.scalar())?),
+                b"side" | b"aggressor" if !is_null => tick.aggressor = parse_aggressor(scanner.string()?),
+                _ => scanner.skip_value()?,
+            }
+            match scanner.peek() {
+                Some(b',') => scanner.pos += 1,
+                Some(b'}') => {
+                    scanner.pos += 1;
+                    break;
+                }
+                _ => return Err("Expected ',' or '}' in JSON market data".into()),
+            }
+        }
+    }
+    if scanner.peek().is_some() {
+        return Err("Trailing characters after JSON market data".into());
+    }
+
+    tick.price = price.ok_or("Missing price in JSON market data")?;
+    tick.volume = volume.ok_or("Missing volume in JSON market data")?;
+    tick.timestamp = timestamp.unwrap_or_else(now_nanos);
+    Ok(tick)
+}
+
+#[cfg(feature = "json")]
+fn parse_json_escaped(data: &[u8]) -> ScanResult<Tick> {
+    #[derive(serde::Deserialize)]
+    struct JsonTick {
+        #[serde(default)]
+        symbol: Option<String>,
+        price: f64,
+        volume: u64,
+        #[serde(default)]
+        timestamp: Option<u64>,
+        #[serde(default, alias = "seq")]
+        sequence: Option<u64>,
+        #[serde(default, alias = "aggressor")]
+        side: Option<String>,
+    }
+
+    let json_tick: JsonTick = serde_json::from_slice(data)?;
+    Ok(Tick {
+        symbol: json_tick.symbol.as_deref().map(Symbol::new).unwrap_or_default(),
+        price: json_tick.price,
+        volume: json_tick.volume,
+        timestamp: json_tick.timestamp.unwrap_or_else(now_nanos),
+        sequence: json_tick.sequence,
+        aggressor: json_tick.side.as_deref().and_then(|side| parse_aggressor(side.as_bytes())),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[cfg(feature = "json")]
+    #[test]
+    fn test_scan_json() {
+        let tick = parse_json(
+            br#" { "symbol" : "AAPL", "price": 1.5e2, "volume":3, "venue": {"id": [1, "}"]}, "seq": 4, "timestamp": null }
+"#,
+        )
+        .unwrap();
+        assert_eq!(tick.symbol.as_str(), "AAPL");
+        assert_eq!(tick.price, 150.0);
+        assert_eq!(tick.volume, 3);
+        assert_eq!(tick.sequence, Some(4));
+        assert!(tick.timestamp > 0);
+        assert_eq!(tick.aggressor, None);
+
+        let tick = parse_json(br#"{"price":1.0,"volume":1,"side":"sell"}"#).unwrap();
+        assert_eq!(tick.aggressor, Some(AggressorSide::Sell));
+
+        assert!(parse_json(br#"{"price":1.0}"#).is_err());
+        assert!(parse_json(br#"{"price":1.0,"volume":1.5}"#).is_err());
+        assert!(parse_json(br#"{"price":1.0,"volume":1} x"#).is_err());
+        assert!(parse_json(br#"{"price":1.0,"volume":1"#).is_err());
+
+        // escapes, in a skipped field or the symbol, are decoded by serde_json
+        let tick = parse_json(br#"{"venue":"X\"NAS","symbol":"BRK\u002eB","price":2.0,"volume":5,"seq":6}"#).unwrap();
+        assert_eq!(tick.symbol.as_str(), "BRK.B");
+        assert_eq!((tick.price, tick.volume, tick.sequence), (2.0, 5, Some(6)));
+        assert!(parse_json(br#"{"venue":"X\"NAS","price":1.0}"#).is_err());
+    }
+
+    #[test]
+    fn test_scan_csv() {
+        let tick = parse_csv(b" MSFT, 410.5 ,7,99\r\n").unwrap();
+        assert_eq!(tick.symbol.as_str(), "MSFT");
+        assert_eq!(tick.price, 410.5);
+        assert_eq!(tick.volume, 7);
+        assert_eq!(tick.timestamp, 99);
+
+        assert!(parse_csv(b"A,1,2,3,4").is_err());
+        assert!(parse_csv(b"1.0").is_err());
+    }
+}
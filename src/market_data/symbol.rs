@@ -0,0 +1,71 @@
+use std::fmt;
+
+pub const MAX_SYMBOL_LEN: usize = 23;
+
+// instrument symbol stored inline, so ticks stay `Copy` and building one never allocates.
+// symbols longer than `MAX_SYMBOL_LEN` bytes are truncated on a char boundary.
+#[derive(Clone, Copy, Default, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct Symbol {
+    len: u8,
+    bytes: [u8; MAX_SYMBOL_LEN],
+}
+
+impl Symbol {
+    pub fn new(symbol: &str) -> Self {
+        let mut len = symbol.len().min(MAX_SYMBOL_LEN);
+        while !symbol.is_char_boundary(len) {
+            len -= 1;
+        }
+        let mut bytes = [0u8; MAX_SYMBOL_LEN];
+        bytes[..len].copy_from_slice(&symbol.as_bytes()[..len]);
+        Symbol { len: len as u8, bytes }
+    }
+
+    pub fn as_str(&self) -> &str {
+        // only ever built from a valid &str cut on a char boundary
+        std::str::from_utf8(&self.bytes[..self.len as usize]).unwrap_or_default()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+}
+
+impl From<&str> for Symbol {
+    fn from(symbol: &str) -> Self {
+        Symbol::new(symbol)
+    }
+}
+
+impl fmt::Display for Symbol {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+impl fmt::Debug for Symbol {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{:?}", self.as_str())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_symbol() {
+        let symbol = Symbol::from("AAPL");
+        assert_eq!(symbol.as_str(), "AAPL");
+        assert_eq!(symbol.to_string(), "AAPL");
+        assert!(!symbol.is_empty());
+        assert!(Symbol::default().is_empty());
+
+        let long = Symbol::new("ABCDEFGHIJKLMNOPQRSTUVWXYZ");
+        assert_eq!(long.as_str().len(), MAX_SYMBOL_LEN);
+
+        // multi-byte characters are never split
+        let unicode = Symbol::new("ЁЁЁЁЁЁЁЁЁЁЁЁ");
+        assert_eq!(unicode.as_str().chars().count(), 11);
+    }
+}
@@ -0,0 +1,285 @@
+use crate::market_data::order_book::OrderBook;
+use crate::market_data::parser::{AggressorSide, Tick};
+use crate::market_data::symbol::Symbol;
+use std::collections::{HashMap, VecDeque};
+use std::time::Duration;
+
+const NANOS_PER_SEC: f64 = 1_000_000_000.0;
+
+// how the aggressor side of a print was determined
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Classification {
+    // flagged by the venue
+    Venue,
+    // Lee-Ready quote rule: above the mid is a buy, below a sell
+    Quote,
+    // tick rule: uptick (or zero-uptick) is a buy, downtick a sell
+    Tick,
+    // first print at the mid with no price history
+    Unknown,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct TradePrint {
+    pub price: f64,
+    pub volume: u64,
+    pub timestamp: u64,
+    pub aggressor: Option<AggressorSide>,
+    pub classification: Classification,
+}
+
+// time and sales plus rolling trade-flow metrics for one instrument
+#[derive(Debug, Clone)]
+pub struct TradeFlow {
+    prints: VecDeque<TradePrint>,
+    capacity: usize,
+    window_ns: u64,
+    // (timestamp, aggressor, volume) inside the metrics window
+    window: VecDeque<(u64, Option<AggressorSide>, u64)>,
+    buy_volume: u64,
+    sell_volume: u64,
+    // last price that differed from its predecessor and the direction of that move, for the tick rule
+    last_price: Option<f64>,
+    last_direction: Option<AggressorSide>,
+}
+
+impl TradeFlow {
+    fn new(capacity: usize, window: Duration) -> Self {
+        TradeFlow {
+            prints: VecDeque::with_capacity(capacity),
+            capacity,
+            window_ns: window.as_nanos() as u64,
+            window: VecDeque::new(),
+            buy_volume: 0,
+            sell_volume: 0,
+            last_price: None,
+            last_direction: None,
+        }
+    }
+
+    // Lee-Ready: quote rule against the mid, tick rule for prints at the mid or without a book
+    fn classify(&mut self, price: f64, mid: Option<f64>) -> (Option<AggressorSide>, Classification) {
+        let tick_direction = match self.last_price {
+            Some(last) if price > last => Some(AggressorSide::Buy),
+            Some(last) if price < last => Some(AggressorSide::Sell),
+            // zero tick: same side as the last price change
+            _ => self.last_direction,
+        };
+        if tick_direction.is_some() {
+            self.last_direction = tick_direction;
+        }
+        self.last_price = Some(price);
+
+        match mid {
+            Some(mid) if price > mid => (Some(AggressorSide::Buy), Classification::Quote),
+            Some(mid) if price < mid => (Some(AggressorSide::Sell), Classification::Quote),
+            _ => match tick_direction {
+                Some(side) => (Some(side), Classification::Tick),
+                None => (None, Classification::Unknown),
+            },
+        }
+    }
+
+    fn record(&mut self, tick: &Tick, mid: Option<f64>) -> TradePrint {
+        let (aggressor, classification) = match tick.aggressor {
+            Some(side) => {
+                // keep the tick rule history current even when the venue flags the side
+                self.classify(tick.price, None);
+                (Some(side), Classification::Venue)
+            }
+            None => self.classify(tick.price, mid),
+        };
+        let print = TradePrint {
+            price: tick.price,
+            volume: tick.volume,
+            timestamp: tick.timestamp,
+            aggressor,
+            classification,
+        };
+
+        if self.prints.len() == self.capacity {
+            self.prints.pop_front();
+        }
+        self.prints.push_back(print);
+
+        self.window.push_back((print.timestamp, aggressor, print.volume));
+        match aggressor {
+            Some(AggressorSide::Buy) => self.buy_volume += print.volume,
+            Some(AggressorSide::Sell) => self.sell_volume += print.volume,
+            None => {}
+        }
+        self.expire(print.timestamp);
+        print
+    }
+
+    // drops prints older than the metrics window from the rolling figures
+    fn expire(&mut self, now: u64) {
+        let Some(cutoff) = now.checked_sub(self.window_ns) else {
+            return;
+        };
+        while let Some(&(timestamp, aggressor, volume)) = self.window.front() {
+            if timestamp > cutoff {
+                break;
+            }
+            match aggressor {
+                Some(AggressorSide::Buy) => self.buy_volume -= volume,
+                Some(AggressorSide::Sell) => self.sell_volume -= volume,
+                None => {}
+            }
+            self.window.pop_front();
+        }
+    }
+
+    // most recent prints, oldest first
+    pub fn prints(&self) -> impl DoubleEndedIterator<Item = &TradePrint> {
+        self.prints.iter()
+    }
+
+    pub fn buy_volume(&self) -> u64 {
+        self.buy_volume
+    }
+
+    pub fn sell_volume(&self) -> u64 {
+        self.sell_volume
+    }
+
+    /// (buy - sell) / (buy + sell) over the window, in [-1, 1]
+    pub fn imbalance(&self) -> Option<f64> {
+        let total = self.buy_volume + self.sell_volume;
+        if total == 0 {
+            return None;
+        }
+        Some((self.buy_volume as f64 - self.sell_volume as f64) / total as f64)
+    }
+
+    /// trade arrivals per second over the window
+    pub fn intensity(&self) -> f64 {
+        if self.window_ns == 0 {
+            return 0.0;
+        }
+        self.window.len() as f64 * NANOS_PER_SEC / self.window_ns as f64
+    }
+}
+
+// records trade prints separately from quotes and classifies their aggressor side.
+// ticks with zero volume are quotes (e.g. a FIX quote mid) and are ignored.
+pub struct TradeTape {
+    capacity: usize,
+    window: Duration,
+    flows: HashMap<Symbol, TradeFlow>,
+}
+
+impl TradeTape {
+    pub fn new(capacity: usize, window: Duration) -> Self {
+        TradeTape {
+            capacity: capacity.max(1),
+            window,
+            flows: HashMap::new(),
+        }
+    }
+
+    /// records a trade print; `book` supplies the mid for the Lee-Ready quote rule
+    pub fn record(&mut self, tick: &Tick, book: Option<&OrderBook>) -> Option<TradePrint> {
+        if tick.volume == 0 {
+            return None;
+        }
+        let (capacity, window) = (self.capacity, self.window);
+        let flow = self
+            .flows
+            .entry(tick.symbol)
+            .or_insert_with(|| TradeFlow::new(capacity, window));
+        Some(flow.record(tick, book.and_then(OrderBook::get_mid_price)))
+    }
+
+    /// ages every instrument's flow window against the wall clock, so a quiet tape does not keep old volume
+    pub fn expire(&mut self, now: u64) {
+        for flow in self.flows.values_mut() {
+            flow.expire(now);
+        }
+    }
+
+    pub fn flow(&self, symbol: &Symbol) -> Option<&TradeFlow> {
+        self.flows.get(symbol)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::market_data::order_book::{Order, OrderSide};
+
+    const SEC: u64 = 1_000_000_000;
+
+    fn trade(price: f64, volume: u64, timestamp: u64, aggressor: Option<AggressorSide>) -> Tick {
+        Tick { symbol: Symbol::new("AAPL"), price, volume, timestamp, aggressor, ..Default::default() }
+    }
+
+    #[test]
+    fn test_aggressor_classification() {
+        let mut tape = TradeTape::new(10, Duration::from_secs(1));
+        let mut book = OrderBook::new();
+        book.add_order(Order { id: 1, price: 100.0, quantity: 1, side: OrderSide::Bid });
+        book.add_order(Order { id: 2, price: 100.2, quantity: 1, side: OrderSide::Ask });
+
+        // quotes are not prints
+        assert_eq!(tape.record(&trade(100.1, 0, 0, None), Some(&book)), None);
+
+        let print = tape.record(&trade(100.15, 5, 1, None), Some(&book)).unwrap();
+        assert_eq!((print.aggressor, print.classification), (Some(AggressorSide::Buy), Classification::Quote));
+
+        // at the mid: downtick from 100.15
+        let print = tape.record(&trade(100.1, 5, 2, None), Some(&book)).unwrap();
+        assert_eq!((print.aggressor, print.classification), (Some(AggressorSide::Sell), Classification::Tick));
+
+        // zero tick keeps the previous direction
+        let print = tape.record(&trade(100.1, 5, 3, None), None).unwrap();
+        assert_eq!((print.aggressor, print.classification), (Some(AggressorSide::Sell), Classification::Tick));
+
+        // the venue flag wins over the quote rule
+        let print = tape.record(&trade(100.0, 5, 4, Some(AggressorSide::Buy)), Some(&book)).unwrap();
+        assert_eq!((print.aggressor, print.classification), (Some(AggressorSide::Buy), Classification::Venue));
+
+        let mut fresh = TradeTape::new(10, Duration::from_secs(1));
+        let print = fresh.record(&trade(100.1, 5, 0, None), Some(&book)).unwrap();
+        assert_eq!((print.aggressor, print.classification), (None, Classification::Unknown));
+    }
+
+    #[test]
+    fn test_rolling_flow_metrics() {
+        let mut tape = TradeTape::new(2, Duration::from_secs(1));
+        let symbol = Symbol::new("AAPL");
+        tape.record(&trade(100.0, 30, SEC, Some(AggressorSide::Buy)), None);
+        tape.record(&trade(100.0, 10, SEC + SEC / 2, Some(AggressorSide::Sell)), None);
+
+        let flow = tape.flow(&symbol).unwrap();
+        assert_eq!((flow.buy_volume(), flow.sell_volume()), (30, 10));
+        assert_eq!(flow.imbalance(), Some(0.5));
+        assert_eq!(flow.intensity(), 2.0);
+
+        // the first print leaves the one-second window
+        tape.record(&trade(100.0, 10, 2 * SEC + SEC / 4, Some(AggressorSide::Sell)), None);
+        let flow = tape.flow(&symbol).unwrap();
+        assert_eq!((flow.buy_volume(), flow.sell_volume()), (0, 20));
+        assert_eq!(flow.imbalance(), Some(-1.0));
+        // time and sales keeps only `capacity` prints
+        assert_eq!(flow.prints().count(), 2);
+        assert_eq!(flow.prints().next_back().unwrap().timestamp, 2 * SEC + SEC / 4);
+    }
+
+    #[test]
+    fn test_quiet_tape_expires_from_the_clock() {
+        let mut tape = TradeTape::new(10, Duration::from_secs(1));
+        let symbol = Symbol::new("AAPL");
+        tape.record(&trade(100.0, 30, SEC, Some(AggressorSide::Buy)), None);
+
+        tape.expire(SEC + SEC / 2);
+        assert_eq!(tape.flow(&symbol).unwrap().buy_volume(), 30);
+
+        // no new print, but the window has moved past the only one
+        tape.expire(2 * SEC);
+        let flow = tape.flow(&symbol).unwrap();
+        assert_eq!((flow.buy_volume(), flow.imbalance(), flow.intensity()), (0, None, 0.0));
+        // time and sales is not a rolling figure
+        assert_eq!(flow.prints().count(), 1);
+    }
+}
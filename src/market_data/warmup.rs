@@ -0,0 +1,201 @@
+use crate::exchange::binary::MARKET_DATA_TICK;
+use crate::market_data::consolidated_book::ConsolidatedBook;
+use crate::market_data::parser::{MarketDataFormat, MarketDataParser, Tick};
+use crate::market_data::symbol::Symbol;
+use crate::storage::reader::TickReader;
+use byteorder::{ByteOrder, LittleEndian};
+use log::{info, warn};
+use std::collections::HashMap;
+use std::path::Path;
+
+// message type u8 | payload length u32, as on the live feed
+const BINARY_HEADER_LEN: usize = 5;
+
+// how much history each symbol gets
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum WarmupWindow {
+    Ticks(usize),
+    // every tick of the last N bar intervals, the current one included
+    Bars { count: usize, interval_ns: u64 },
+}
+
+/// reads recorded market data in the parser's format, one message per line or binary frames back to back,
+/// and returns the last ticks of each symbol in file order. unparseable messages are skipped.
+pub fn load_history(
+    path: &Path,
+    parser: &MarketDataParser,
+    window: WarmupWindow,
+) -> Result<Vec<Tick>, Box<dyn std::error::Error>> {
+    let data = std::fs::read(path).map_err(|e| format!("cannot read {}: {}", path.display(), e))?;
+    let binary = match parser.format() {
+        MarketDataFormat::Binary => true,
+        MarketDataFormat::Auto => data.first() == Some(&MARKET_DATA_TICK),
+        _ => false,
+    };
+    let messages = if binary { binary_frames(&data)? } else { data.split(|&byte| byte == b'\n').collect() };
+
+    // FIX quotes are priced from the book they build up, as on the live feed
+    let mut books = ConsolidatedBook::new();
+    let mut ticks = Vec::new();
+    let mut skipped = 0;
+    for message in messages.into_iter().filter(|message| !message.trim_ascii().is_empty()) {
+        match parser.parse_update(message) {
+            Ok(update) => {
+                let touched = update.book.as_ref().map_or_else(Vec::new, |md| books.apply("history", md));
+                ticks.extend(books.ticks(&update, &touched));
+            }
+            Err(_) => skipped += 1,
+        }
+    }
+    if skipped > 0 {
+        warn!("Skipped {} unreadable messages in {}", skipped, path.display());
+    }
+
+    let ticks = last_per_symbol(ticks, window);
+    info!("Loaded {} warm-up ticks from {}", ticks.len(), path.display());
+    Ok(ticks)
+}
+
+/// reads the last ticks of each symbol from the tick store and returns them in timestamp order.
+/// days are read newest first until the window is covered.
+pub fn load_stored(
+    store: &TickReader,
+    symbols: &[Symbol],
+    window: WarmupWindow,
+) -> Result<Vec<Tick>, Box<dyn std::error::Error>> {
+    let mut ticks = Vec::new();
+    for symbol in symbols {
+        let mut days: Vec<Vec<Tick>> = Vec::new();
+        for day in store.days(symbol)?.into_iter().rev() {
+            days.push(store.read_day(symbol, day)?.collect::<std::io::Result<_>>()?);
+            let covered = match window {
+                WarmupWindow::Ticks(count) => days.iter().map(Vec::len).sum::<usize>() >= count,
+                WarmupWindow::Bars { count: 0, .. } => true,
+                WarmupWindow::Bars { count, interval_ns } => {
+                    let last = days.iter().find_map(|ticks| ticks.iter().map(|tick| tick.timestamp).max());
+                    let oldest = days.last().and_then(|ticks| ticks.iter().map(|tick| tick.timestamp).min());
+                    matches!((last, oldest), (Some(last), Some(oldest)) if oldest <= first_bar(last, count, interval_ns))
+                }
+            };
+            if covered {
+                break;
+            }
+        }
+        ticks.extend(days.into_iter().rev().flatten());
+    }
+
+    let mut ticks = last_per_symbol(ticks, window);
+    ticks.sort_by_key(|tick| tick.timestamp);
+    info!("Loaded {} warm-up ticks from the tick store", ticks.len());
+    Ok(ticks)
+}
+
+fn binary_frames(data: &[u8]) -> Result<Vec<&[u8]>, Box<dyn std::error::Error>> {
+    let mut frames = Vec::new();
+    let mut rest = data;
+    while !rest.is_empty() {
+        if rest.len() < BINARY_HEADER_LEN {
+            return Err("truncated binary frame header".into());
+        }
+        let end = BINARY_HEADER_LEN + LittleEndian::read_u32(&rest[1..BINARY_HEADER_LEN]) as usize;
+        if rest.len() < end {
+            return Err("truncated binary frame".into());
+        }
+        let (frame, next) = rest.split_at(end);
+        frames.push(frame);
+        rest = next;
+    }
+    Ok(frames)
+}
+
+fn last_per_symbol(ticks: Vec<Tick>, window: WarmupWindow) -> Vec<Tick> {
+    let mut keep = vec![false; ticks.len()];
+    let mut by_symbol: HashMap<Symbol, Vec<usize>> = HashMap::new();
+    for (index, tick) in ticks.iter().enumerate() {
+        by_symbol.entry(tick.symbol).or_default().push(index);
+    }
+    for indices in by_symbol.values() {
+        match window {
+            WarmupWindow::Ticks(count) => {
+                for &index in indices.iter().rev().take(count) {
+                    keep[index] = true;
+                }
+            }
+            WarmupWindow::Bars { count: 0, .. } => {}
+            WarmupWindow::Bars { count, interval_ns } => {
+                let last = indices.iter().map(|&index| ticks[index].timestamp).max().unwrap_or(0);
+                let first_bar = first_bar(last, count, interval_ns);
+                for &index in indices.iter().filter(|&&index| ticks[index].timestamp >= first_bar) {
+                    keep[index] = true;
+                }
+            }
+        }
+    }
+    ticks.into_iter().zip(keep).filter_map(|(tick, keep)| keep.then_some(tick)).collect()
+}
+
+// start of the oldest of `count` (at least one) bars ending with the one holding `last`
+fn first_bar(last: u64, count: usize, interval_ns: u64) -> u64 {
+    let interval_ns = interval_ns.max(1);
+    (last - last % interval_ns).saturating_sub((count as u64 - 1).saturating_mul(interval_ns))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::storage::writer::TickWriter;
+
+    const SEC: u64 = 1_000_000_000;
+
+    #[test]
+    fn test_last_ticks_and_bars_per_symbol() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("history.csv");
+        let mut lines = Vec::new();
+        for second in 0..10u64 {
+            lines.push(format!("AAPL,{},1,{}", 100 + second, second * SEC));
+            if second % 3 == 0 {
+                lines.push(format!("MSFT,{},1,{}", 400 + second, second * SEC));
+            }
+        }
+        lines.insert(4, "garbage".to_string());
+        std::fs::write(&path, lines.join("\n") + "\n").unwrap();
+        let parser = MarketDataParser::new(MarketDataFormat::CSV);
+
+        let prices = |window| {
+            load_history(&path, &parser, window).unwrap().iter().map(|tick| tick.price).collect::<Vec<_>>()
+        };
+        assert_eq!(prices(WarmupWindow::Ticks(2)), vec![406.0, 108.0, 109.0, 409.0]);
+        // 5s bars: only the one from 5s on, then that one and the one before
+        assert_eq!(
+            prices(WarmupWindow::Bars { count: 1, interval_ns: 5 * SEC }),
+            vec![105.0, 106.0, 406.0, 107.0, 108.0, 109.0, 409.0]
+        );
+        assert_eq!(prices(WarmupWindow::Bars { count: 2, interval_ns: 5 * SEC }).len(), 14);
+        assert!(load_history(&dir.path().join("missing.csv"), &parser, WarmupWindow::Ticks(1)).is_err());
+    }
+
+    #[test]
+    fn test_last_ticks_and_bars_from_tick_store() {
+        let dir = tempfile::tempdir().unwrap();
+        let (aapl, msft) = (Symbol::new("AAPL"), Symbol::new("MSFT"));
+        let day = 86_400 * SEC;
+        {
+            let mut writer = TickWriter::new(dir.path(), 2).unwrap();
+            // two ticks at the end of one day, three at the start of the next
+            for (i, timestamp) in [day - 2 * SEC, day - SEC, day, day + SEC, day + 2 * SEC].into_iter().enumerate() {
+                writer.append(&Tick { symbol: aapl, price: 100.0 + i as f64, volume: 1, timestamp, ..Default::default() }).unwrap();
+            }
+            writer.append(&Tick { symbol: msft, price: 400.0, volume: 1, timestamp: day + SEC / 2, ..Default::default() }).unwrap();
+        }
+        let store = TickReader::new(dir.path());
+
+        let prices = |symbols: &[Symbol], window| {
+            load_stored(&store, symbols, window).unwrap().iter().map(|tick| tick.price).collect::<Vec<_>>()
+        };
+        // the window reaches back into the day before, the symbols are merged in time
+        assert_eq!(prices(&[aapl, msft], WarmupWindow::Ticks(4)), vec![101.0, 102.0, 400.0, 103.0, 104.0]);
+        assert_eq!(prices(&[aapl], WarmupWindow::Bars { count: 3, interval_ns: 3 * SEC / 2 }), vec![101.0, 102.0, 103.0, 104.0]);
+        assert!(prices(&[Symbol::new("GOOG")], WarmupWindow::Ticks(4)).is_empty());
+    }
+}
@@ -0,0 +1,508 @@
+use super::open_orders::{OpenOrder, OpenOrders};
+use super::{ParentOrders, WorkingParent};
+use crate::config::ExecutionConfig;
+use crate::market_data::consolidated_book::ConsolidatedBook;
+use crate::market_data::order_book::OrderBook;
+use crate::market_data::parser::Tick;
+use crate::market_data::symbol::Symbol;
+use crate::strategy::{Order, OrderSide, OrderStatus, OrderUpdate, StrategyAction};
+use log::{debug, info, warn};
+use std::collections::{BTreeMap, HashMap};
+
+const NANOS_PER_MS: u64 = 1_000_000;
+const NANOS_PER_SEC: f64 = 1_000_000_000.0;
+const NANOS_PER_DAY: u64 = 86_400 * 1_000_000_000;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum AlgoKind {
+    // even slices over the duration
+    Twap,
+    // slices following the configured intraday volume curve
+    Vwap,
+    // a fraction of the volume traded in the symbol since the start
+    Pov { participation: f64 },
+}
+
+// how children are priced: at our own touch, the mid, or across the spread
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Urgency {
+    Low,
+    Medium,
+    High,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct AlgoParams {
+    pub kind: AlgoKind,
+    // the schedule spans this long; whatever is left one child timeout later is cancelled
+    pub duration_secs: f64,
+    // children never buy above / sell below this
+    pub limit_price: Option<f64>,
+    pub urgency: Urgency,
+    // smallest child sent, except for the remainder
+    pub min_clip: u64,
+}
+
+impl AlgoParams {
+    pub fn new(kind: AlgoKind, duration_secs: f64) -> Self {
+        AlgoParams { kind, duration_secs, limit_price: None, urgency: Urgency::Medium, min_clip: 1 }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct AlgoProgress {
+    pub quantity: u64,
+    pub filled: u64,
+    pub working: u64,
+    // quantity the schedule wants done by now
+    pub target: u64,
+    pub average_price: Option<f64>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct WorkingChild {
+    client_id: u64,
+    quantity: u64,
+    // when the child was sent, or its last cancel requested
+    sent_at: u64,
+}
+
+// touch and last trade the children are priced from
+#[derive(Debug, Clone, Copy, Default)]
+struct Quote {
+    bid: Option<f64>,
+    ask: Option<f64>,
+    last: Option<f64>,
+}
+
+// one parent order being worked
+struct ExecutionAlgo {
+    parent: Order,
+    symbol: Symbol,
+    params: AlgoParams,
+    start: u64,
+    end: u64,
+    filled: u64,
+    notional: f64,
+    // volume printed in the symbol since the start, for POV
+    market_volume: u64,
+    child: Option<WorkingChild>,
+    rejects: u32,
+}
+
+impl ExecutionAlgo {
+    // share of the parent the schedule wants done by `now`
+    fn target(&self, now: u64, curve: &[f64]) -> u64 {
+        let quantity = self.parent.quantity as f64;
+        let target = match self.params.kind {
+            AlgoKind::Pov { participation } => (self.market_volume as f64 * participation).floor(),
+            AlgoKind::Twap => {
+                let (elapsed, duration) = (now.saturating_sub(self.start).min(self.end - self.start), self.end - self.start);
+                (u128::from(self.parent.quantity) * u128::from(elapsed)).div_ceil(u128::from(duration)) as f64
+            }
+            AlgoKind::Vwap => {
+                let expected = curve_volume(curve, self.end) - curve_volume(curve, self.start);
+                let fraction = if expected > 0.0 {
+                    (curve_volume(curve, now.min(self.end)) - curve_volume(curve, self.start)) / expected
+                } else {
+                    // no volume expected in the window at all
+                    self.elapsed(now)
+                };
+                (quantity * fraction).ceil()
+            }
+        };
+        (target.max(0.0) as u64).min(self.parent.quantity)
+    }
+
+    fn elapsed(&self, now: u64) -> f64 {
+        (now.saturating_sub(self.start) as f64 / (self.end - self.start) as f64).min(1.0)
+    }
+
+    fn progress(&self, now: u64, curve: &[f64]) -> AlgoProgress {
+        AlgoProgress {
+            quantity: self.parent.quantity,
+            filled: self.filled,
+            working: self.child.map_or(0, |child| child.quantity),
+            target: self.target(now, curve),
+            average_price: (self.filled > 0).then(|| self.notional / self.filled as f64),
+        }
+    }
+
+    fn remaining(&self) -> u64 {
+        self.parent.quantity - self.filled
+    }
+
+    fn child_price(&self, quote: Quote) -> Option<f64> {
+        let buy = self.parent.side == OrderSide::Buy;
+        let (near, far) = if buy { (quote.bid, quote.ask) } else { (quote.ask, quote.bid) };
+        let mid = quote.bid.zip(quote.ask).map(|(bid, ask)| (bid + ask) / 2.0);
+        let price = match self.params.urgency {
+            Urgency::Low => near,
+            Urgency::Medium => mid,
+            Urgency::High => far,
+        };
+        match (price.or(quote.last), self.params.limit_price) {
+            (Some(price), Some(limit)) if buy => Some(price.min(limit)),
+            (Some(price), Some(limit)) => Some(price.max(limit)),
+            (price, limit) => price.or(limit),
+        }
+    }
+}
+
+// cumulative curve weight from the epoch to `t`, the curve repeating every UTC day
+fn curve_volume(curve: &[f64], t: u64) -> f64 {
+    if curve.is_empty() {
+        return 0.0;
+    }
+    let bucket = NANOS_PER_DAY / curve.len() as u64;
+    let (days, time_of_day) = (t / NANOS_PER_DAY, t % NANOS_PER_DAY);
+    let full = ((time_of_day / bucket) as usize).min(curve.len());
+    let partial = (time_of_day % bucket) as f64 / bucket as f64;
+    days as f64 * curve.iter().sum::<f64>()
+        + curve[..full].iter().sum::<f64>()
+        + curve.get(full).map_or(0.0, |weight| weight * partial)
+}
+
+// parent orders handed over by the strategy, worked as child orders through the normal order path.
+// parent and child ids come from the same client id sequence.
+pub struct AlgoEngine {
+    config: ExecutionConfig,
+    algos: BTreeMap<u64, ExecutionAlgo>,
+    // child client id -> parent client id
+    children: HashMap<u64, u64>,
+}
+
+impl AlgoEngine {
+    pub fn new(config: ExecutionConfig) -> Result<Self, Box<dyn std::error::Error>> {
+        if !config.volume_curve.is_empty() && 1440 % config.volume_curve.len() != 0 {
+            return Err("execution.volume_curve must split the day into whole-minute buckets".into());
+        }
+        if config.volume_curve.iter().any(|weight| !(weight.is_finite() && *weight >= 0.0)) {
+            return Err("execution.volume_curve weights must be non-negative".into());
+        }
+        if config.child_timeout_ms == 0 || config.max_child_rejects == 0 {
+            return Err("execution.child_timeout_ms and max_child_rejects must be positive".into());
+        }
+        Ok(AlgoEngine { config, algos: BTreeMap::new(), children: HashMap::new() })
+    }
+
+    /// starts working `order` as the parent `client_id`
+    pub fn start(&mut self, client_id: u64, order: Order, params: AlgoParams, now: u64) -> Result<(), String> {
+        if order.quantity == 0 || params.min_clip == 0 {
+            return Err("algo orders need a positive quantity and min_clip".to_string());
+        }
+        if !(params.duration_secs.is_finite() && params.duration_secs > 0.0) {
+            return Err("algo duration must be positive".to_string());
+        }
+        if params.limit_price.is_some_and(|limit| !(limit.is_finite() && limit > 0.0)) {
+            return Err("algo limit price must be positive".to_string());
+        }
+        match params.kind {
+            AlgoKind::Pov { participation } if !(0.0 < participation && participation <= 1.0) => {
+                return Err("POV participation must be in (0, 1]".to_string());
+            }
+            AlgoKind::Vwap if self.config.volume_curve.is_empty() => {
+                return Err("VWAP needs execution.volume_curve".to_string());
+            }
+            _ => {}
+        }
+        if self.algos.contains_key(&client_id) {
+            return Err(format!("algo order {} already exists", client_id));
+        }
+
+        info!(
+            "Starting {:?} for order {}: {} {} {} over {}s, limit {:?}, urgency {:?}",
+            params.kind, client_id, order.side, order.quantity, order.symbol, params.duration_secs, params.limit_price, params.urgency
+        );
+        let end = now + ((params.duration_secs * NANOS_PER_SEC) as u64).max(1);
+        let algo = ExecutionAlgo {
+            symbol: Symbol::new(&order.symbol),
+            parent: order,
+            params,
+            start: now,
+            end,
+            filled: 0,
+            notional: 0.0,
+            market_volume: 0,
+            child: None,
+            rejects: 0,
+        };
+        self.algos.insert(client_id, algo);
+        Ok(())
+    }
+
+    // traded volume for POV
+    pub fn on_tick(&mut self, tick: &Tick) {
+        for algo in self.algos.values_mut().filter(|algo| algo.symbol == tick.symbol && tick.timestamp >= algo.start) {
+            algo.market_volume += tick.volume;
+        }
+    }
+
+    /// next child orders and cancels of stale ones, plus parents that expired unfinished
+    pub fn poll(
+        &mut self,
+        now: u64,
+        open_orders: &OpenOrders,
+        books: &ConsolidatedBook,
+        last_prices: &HashMap<Symbol, f64>,
+    ) -> (Vec<StrategyAction>, Vec<OrderUpdate>) {
+        let mut actions = Vec::new();
+        let mut expired = Vec::new();
+        let timeout = self.config.child_timeout_ms * NANOS_PER_MS;
+        for (&parent_id, algo) in self.algos.iter_mut() {
+            if now >= algo.end + timeout {
+                if let Some(child) = algo.child {
+                    actions.push(StrategyAction::Cancel { client_id: child.client_id });
+                }
+                warn!("{:?} order {} expired with {} of {} filled", algo.params.kind, parent_id, algo.filled, algo.parent.quantity);
+                let order = Order { quantity: algo.remaining(), ..algo.parent.clone() };
+                expired.push(OrderUpdate { client_id: parent_id, order, status: OrderStatus::Cancelled });
+                continue;
+            }
+            if let Some(child) = algo.child.as_mut() {
+                if now.saturating_sub(child.sent_at) >= timeout {
+                    debug!("Child {} of order {} not filled in time, cancelling", child.client_id, parent_id);
+                    child.sent_at = now;
+                    actions.push(StrategyAction::Cancel { client_id: child.client_id });
+                }
+                continue;
+            }
+
+            let due = algo.target(now, &self.config.volume_curve).saturating_sub(algo.filled);
+            let remaining = algo.remaining();
+            if due == 0 || due < algo.params.min_clip.min(remaining) {
+                continue;
+            }
+            let book = books.book(&algo.symbol);
+            let quote = Quote {
+                bid: book.and_then(OrderBook::get_best_bid),
+                ask: book.and_then(OrderBook::get_best_ask),
+                last: last_prices.get(&algo.symbol).copied(),
+            };
+            let Some(price) = algo.child_price(quote) else {
+                continue;
+            };
+            let quantity = due.max(algo.params.min_clip).min(remaining);
+            let client_id = open_orders.allocate_id();
+            debug!("Order {} child {}: {} {} @ {}", parent_id, client_id, algo.parent.side, quantity, price);
+            algo.child = Some(WorkingChild { client_id, quantity, sent_at: now });
+            self.children.insert(client_id, parent_id);
+            actions.push(StrategyAction::Place { client_id, order: Order { price, quantity, ..algo.parent.clone() } });
+        }
+        for update in &expired {
+            self.remove(update.client_id);
+        }
+        (actions, expired)
+    }
+
+    // a child still working stays mapped to its parent until it is done
+    fn remove(&mut self, parent_id: u64) {
+        self.algos.remove(&parent_id);
+    }
+}
+
+impl ParentOrders for AlgoEngine {
+    fn is_parent(&self, client_id: u64) -> bool {
+        self.algos.contains_key(&client_id)
+    }
+
+    fn parent_of(&self, child_id: u64) -> Option<u64> {
+        self.children.get(&child_id).copied()
+    }
+
+    fn parents(&self, symbol: Option<&str>) -> Vec<u64> {
+        self.algos
+            .iter()
+            .filter(|(_, algo)| symbol.is_none_or(|symbol| algo.parent.symbol == symbol))
+            .map(|(client_id, _)| *client_id)
+            .collect()
+    }
+
+    fn working(&self) -> Vec<WorkingParent> {
+        self.algos
+            .iter()
+            .map(|(&client_id, algo)| WorkingParent {
+                remainder: OpenOrder { client_id, order: Order { quantity: algo.remaining(), ..algo.parent.clone() } },
+                child: algo.child.map(|child| child.client_id),
+            })
+            .collect()
+    }
+
+    fn cancel(&mut self, client_id: u64) -> Option<(Order, Option<u64>)> {
+        let algo = self.algos.get(&client_id)?;
+        let remainder = Order { quantity: algo.remaining(), ..algo.parent.clone() };
+        let child = algo.child.map(|child| child.client_id);
+        info!("Cancelling {:?} order {} with {} left", algo.params.kind, client_id, remainder.quantity);
+        self.remove(client_id);
+        Some((remainder, child))
+    }
+
+    fn child_filled(&mut self, child_id: u64, quantity: u64, price: f64, now: u64) -> Option<u64> {
+        let parent_id = self.children.remove(&child_id)?;
+        let Some(algo) = self.algos.get_mut(&parent_id) else {
+            return Some(parent_id);
+        };
+        algo.filled = (algo.filled + quantity).min(algo.parent.quantity);
+        algo.notional += quantity as f64 * price;
+        algo.rejects = 0;
+        if algo.child.is_some_and(|child| child.client_id == child_id) {
+            algo.child = None;
+        }
+        let progress = algo.progress(now, &self.config.volume_curve);
+        info!("{:?} order {} progress: {:?}", algo.params.kind, parent_id, progress);
+        if algo.remaining() == 0 {
+            info!("{:?} order {} completed", algo.params.kind, parent_id);
+            self.remove(parent_id);
+        }
+        Some(parent_id)
+    }
+
+    fn child_closed(&mut self, child_id: u64) {
+        if let Some(algo) = self.children.remove(&child_id).and_then(|parent_id| self.algos.get_mut(&parent_id)) {
+            if algo.child.is_some_and(|child| child.client_id == child_id) {
+                algo.child = None;
+            }
+        }
+    }
+
+    fn child_rejected(&mut self, child_id: u64) -> Option<(u64, Order)> {
+        let parent_id = self.parent_of(child_id)?;
+        self.child_closed(child_id);
+        let algo = self.algos.get_mut(&parent_id)?;
+        algo.rejects += 1;
+        if algo.rejects < self.config.max_child_rejects {
+            return None;
+        }
+        warn!("Abandoning {:?} order {} after {} rejected children", algo.params.kind, parent_id, algo.rejects);
+        let remainder = Order { quantity: algo.remaining(), ..algo.parent.clone() };
+        self.remove(parent_id);
+        Some((parent_id, remainder))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::strategy::context::ContextHarness;
+
+    const SEC: u64 = 1_000_000_000;
+
+    fn engine(volume_curve: Vec<f64>) -> AlgoEngine {
+        AlgoEngine::new(ExecutionConfig { volume_curve, child_timeout_ms: 5_000, ..ExecutionConfig::default() }).unwrap()
+    }
+
+    fn buy(quantity: u64) -> Order {
+        Order { symbol: "AAPL".to_string(), price: 0.0, quantity, side: OrderSide::Buy }
+    }
+
+    fn progress(algos: &AlgoEngine, parent_id: u64, now: u64) -> Option<AlgoProgress> {
+        algos.algos.get(&parent_id).map(|algo| algo.progress(now, &algos.config.volume_curve))
+    }
+
+    fn children(actions: Vec<StrategyAction>) -> Vec<(u64, Order)> {
+        actions
+            .into_iter()
+            .filter_map(|action| match action {
+                StrategyAction::Place { client_id, order } => Some((client_id, order)),
+                _ => None,
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_twap_slices_evenly() {
+        let mut algos = engine(Vec::new());
+        let open_orders = OpenOrders::new();
+        let prices = HashMap::from([(Symbol::new("AAPL"), 50.0)]);
+        let params = AlgoParams { min_clip: 10, urgency: Urgency::High, ..AlgoParams::new(AlgoKind::Twap, 100.0) };
+        algos.start(open_orders.allocate_id(), buy(100), params, 0).unwrap();
+
+        let mut clips = Vec::new();
+        for second in 0..=100 {
+            for (child_id, child) in children(algos.poll(second * SEC, &open_orders, &ConsolidatedBook::new(), &prices).0) {
+                assert_eq!(algos.child_filled(child_id, child.quantity, child.price, second * SEC), Some(1));
+                clips.push((second, child.quantity, child.price));
+            }
+            if second == 55 {
+                let progress = progress(&algos, 1, second * SEC).unwrap();
+                assert_eq!((progress.filled, progress.target, progress.average_price), (50, 55, Some(50.0)));
+            }
+        }
+        assert_eq!(clips.len(), 10);
+        assert!(clips.iter().all(|&(_, quantity, price)| quantity == 10 && price == 50.0));
+        assert_eq!((clips[0].0, clips[9].0), (10, 100));
+        assert!(progress(&algos, 1, 100 * SEC).is_none(), "completed parents are dropped");
+    }
+
+    #[test]
+    fn test_vwap_curve_and_pov_participation() {
+        // four 6-hour buckets, a quarter of the day's volume in the first
+        let mut algos = engine(vec![1.0, 3.0, 0.0, 0.0]);
+        let open_orders = OpenOrders::new();
+        let hour = 3_600 * SEC;
+        algos.start(1, buy(100), AlgoParams::new(AlgoKind::Vwap, 12.0 * 3_600.0), 0).unwrap();
+        assert_eq!(progress(&algos, 1, 3 * hour).unwrap().target, 13);
+        assert_eq!(progress(&algos, 1, 6 * hour).unwrap().target, 25);
+        assert_eq!(progress(&algos, 1, 9 * hour).unwrap().target, 63);
+        assert!(engine(Vec::new()).start(1, buy(100), AlgoParams::new(AlgoKind::Vwap, 60.0), 0).is_err());
+
+        // POV follows printed volume; children are capped at the limit
+        let params = AlgoParams {
+            limit_price: Some(49.5),
+            ..AlgoParams::new(AlgoKind::Pov { participation: 0.1 }, 60.0)
+        };
+        algos.start(2, buy(100), params, 0).unwrap();
+        let tick = Tick { symbol: Symbol::new("AAPL"), price: 50.0, volume: 300, timestamp: SEC, ..Default::default() };
+        algos.on_tick(&tick);
+        let mut book = ConsolidatedBook::new();
+        book.update_level(Symbol::new("AAPL"), "XNAS", crate::market_data::order_book::OrderSide::Bid, 49.0, 10);
+        book.update_level(Symbol::new("AAPL"), "XNAS", crate::market_data::order_book::OrderSide::Ask, 51.0, 10);
+        let (actions, _) = algos.poll(SEC, &open_orders, &book, &HashMap::new());
+        let pov: Vec<_> = children(actions).into_iter().filter(|(id, _)| algos.parent_of(*id) == Some(2)).collect();
+        assert_eq!(pov.len(), 1);
+        assert_eq!((pov[0].1.quantity, pov[0].1.price), (30, 49.5));
+    }
+
+    #[test]
+    fn test_rejects_cancel_and_expiry() {
+        let mut algos = engine(Vec::new());
+        let harness = ContextHarness::default();
+        let prices = HashMap::from([(Symbol::new("AAPL"), 50.0)]);
+
+        let mut ctx = harness.ctx(0);
+        let parent = ctx.execute(buy(10), AlgoParams { urgency: Urgency::Low, ..AlgoParams::new(AlgoKind::Twap, 10.0) });
+        let Some(StrategyAction::Execute { client_id, order, algo }) = ctx.into_actions().pop() else {
+            panic!("no algo order")
+        };
+        assert_eq!(client_id, parent);
+        algos.start(client_id, order, algo, 0).unwrap();
+
+        // rejected children are retried until the parent is abandoned
+        for attempt in 1..=3 {
+            let (child_id, _) = children(algos.poll(attempt * SEC, &harness.open_orders, &ConsolidatedBook::new(), &prices).0)[0].clone();
+            let abandoned = algos.child_rejected(child_id);
+            assert_eq!(abandoned.is_some(), attempt == 3);
+        }
+        assert!(!algos.is_parent(parent));
+
+        // cancelling pulls the working child and returns the remainder
+        algos.start(parent, buy(10), AlgoParams::new(AlgoKind::Twap, 10.0), 0).unwrap();
+        let (child_id, child) = children(algos.poll(5 * SEC, &harness.open_orders, &ConsolidatedBook::new(), &prices).0)[0].clone();
+        assert_eq!(algos.child_filled(child_id, 2, child.price, 5 * SEC), Some(parent));
+        let (child_id, _) = children(algos.poll(6 * SEC, &harness.open_orders, &ConsolidatedBook::new(), &prices).0)[0].clone();
+        let (remainder, working) = algos.cancel(parent).unwrap();
+        assert_eq!((remainder.quantity, working), (8, Some(child_id)));
+        assert_eq!(algos.parent_of(child_id), Some(parent), "the child reports until it is closed");
+        algos.child_closed(child_id);
+        assert_eq!(algos.parent_of(child_id), None);
+
+        // a parent still working at the end of its duration expires
+        algos.start(parent, buy(10), AlgoParams::new(AlgoKind::Twap, 10.0), 0).unwrap();
+        let (child_id, _) = children(algos.poll(9 * SEC, &harness.open_orders, &ConsolidatedBook::new(), &prices).0)[0].clone();
+        assert!(algos.poll(14 * SEC, &harness.open_orders, &ConsolidatedBook::new(), &prices).1.is_empty(), "the last child gets a timeout to fill");
+        let (actions, expired) = algos.poll(15 * SEC, &harness.open_orders, &ConsolidatedBook::new(), &prices);
+        assert_eq!(actions, vec![StrategyAction::Cancel { client_id: child_id }]);
+        assert_eq!((expired[0].client_id, expired[0].order.quantity, expired[0].status), (parent, 10, OrderStatus::Cancelled));
+        assert!(algos.parents(None).is_empty());
+    }
+}
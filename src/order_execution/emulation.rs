@@ -0,0 +1,370 @@
+use super::open_orders::{OpenOrder, OpenOrders};
+use super::{ParentOrders, WorkingParent};
+use crate::config::ExecutionConfig;
+use crate::market_data::consolidated_book::ConsolidatedBook;
+use crate::market_data::order_book::OrderBook;
+use crate::market_data::symbol::Symbol;
+use crate::strategy::{Order, OrderSide, StrategyAction};
+use log::{debug, info, warn};
+use std::collections::{BTreeMap, HashMap};
+
+const NANOS_PER_MS: u64 = 1_000_000;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PegReference {
+    // our own side of the touch: best bid for a buy
+    Primary,
+    Mid,
+    // the opposite side: best ask for a buy
+    Market,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Peg {
+    pub reference: PegReference,
+    // added to the reference price, whatever the side
+    pub offset: f64,
+}
+
+// order types the venue may not support, emulated with plain limit orders.
+// the parent's price is the limit: a pegged buy never goes above it, a pegged sell never below.
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub struct EmulationParams {
+    // iceberg: only this much of the parent is shown at a time, replenished on fills
+    pub display_quantity: Option<u64>,
+    pub peg: Option<Peg>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct VisibleChild {
+    client_id: u64,
+    quantity: u64,
+    price: f64,
+    // the venue holds the child and it can be amended
+    acknowledged: bool,
+}
+
+struct EmulatedOrder {
+    parent: Order,
+    params: EmulationParams,
+    filled: u64,
+    child: Option<VisibleChild>,
+    last_amend: Option<u64>,
+    rejects: u32,
+    // the peg reference is missing from the book, reported once until it is back
+    unpriced: bool,
+}
+
+impl EmulatedOrder {
+    fn remaining(&self) -> u64 {
+        self.parent.quantity - self.filled
+    }
+
+    // pegged price capped at the limit, else the limit itself; `None` while the reference is missing
+    fn target_price(&self, book: Option<&OrderBook>) -> Option<f64> {
+        let Some(peg) = self.params.peg else {
+            return Some(self.parent.price);
+        };
+        let book = book?;
+        let buy = self.parent.side == OrderSide::Buy;
+        let (near, far) = if buy {
+            (book.get_best_bid(), book.get_best_ask())
+        } else {
+            (book.get_best_ask(), book.get_best_bid())
+        };
+        let reference = match peg.reference {
+            PegReference::Primary => near,
+            PegReference::Mid => book.get_mid_price(),
+            PegReference::Market => far,
+        }?;
+        let price = reference + peg.offset;
+        Some(if buy { price.min(self.parent.price) } else { price.max(self.parent.price) })
+    }
+}
+
+// iceberg and pegged orders kept client-side: one visible child per parent, re-priced as the book moves
+pub struct OrderEmulator {
+    config: ExecutionConfig,
+    orders: BTreeMap<u64, EmulatedOrder>,
+    // child client id -> parent client id
+    children: HashMap<u64, u64>,
+}
+
+impl OrderEmulator {
+    pub fn new(config: ExecutionConfig) -> Result<Self, Box<dyn std::error::Error>> {
+        if !(config.peg_min_price_change.is_finite() && config.peg_min_price_change >= 0.0) {
+            return Err("execution.peg_min_price_change must be non-negative".into());
+        }
+        Ok(OrderEmulator { config, orders: BTreeMap::new(), children: HashMap::new() })
+    }
+
+    /// starts emulating `order` as the parent `client_id`; a peg needs its reference price in the book
+    pub fn start(&mut self, client_id: u64, order: Order, params: EmulationParams, books: &ConsolidatedBook) -> Result<(), String> {
+        if order.quantity == 0 || params.display_quantity == Some(0) {
+            return Err("emulated orders need a positive quantity and display quantity".to_string());
+        }
+        if !(order.price.is_finite() && order.price > 0.0) {
+            return Err("emulated orders need a positive limit price".to_string());
+        }
+        if params.peg.is_some_and(|peg| !peg.offset.is_finite()) {
+            return Err("peg offset must be finite".to_string());
+        }
+        if self.orders.contains_key(&client_id) {
+            return Err(format!("emulated order {} already exists", client_id));
+        }
+        info!(
+            "Emulating order {}: {} {} {} limit {}, display {:?}, peg {:?}",
+            client_id, order.side, order.quantity, order.symbol, order.price, params.display_quantity, params.peg
+        );
+        let emulated = EmulatedOrder { parent: order, params, filled: 0, child: None, last_amend: None, rejects: 0, unpriced: false };
+        if let Some(peg) = params.peg.filter(|_| emulated.target_price(books.book(&Symbol::new(&emulated.parent.symbol))).is_none()) {
+            return Err(format!("no {:?} price in the book of {} to peg to", peg.reference, emulated.parent.symbol));
+        }
+        self.orders.insert(client_id, emulated);
+        Ok(())
+    }
+
+    /// shows the next slice of icebergs without a visible child and re-prices pegs whose reference moved
+    pub fn poll(&mut self, now: u64, open_orders: &OpenOrders, books: &ConsolidatedBook) -> Vec<StrategyAction> {
+        let mut actions = Vec::new();
+        let amend_interval = self.config.peg_amend_interval_ms * NANOS_PER_MS;
+        for (&parent_id, emulated) in self.orders.iter_mut() {
+            // children are placed synchronously, so one still unacknowledged never reached the venue
+            if let Some(lost) = emulated.child.filter(|child| !child.acknowledged) {
+                self.children.remove(&lost.client_id);
+                emulated.child = None;
+            }
+            let Some(price) = emulated.target_price(books.book(&Symbol::new(&emulated.parent.symbol))) else {
+                if !emulated.unpriced {
+                    warn!("Order {} has nothing to peg to in the book of {}, waiting", parent_id, emulated.parent.symbol);
+                    emulated.unpriced = true;
+                }
+                continue;
+            };
+            emulated.unpriced = false;
+            match emulated.child.as_mut() {
+                None => {
+                    let quantity = emulated.params.display_quantity.map_or(emulated.remaining(), |display| {
+                        display.min(emulated.remaining())
+                    });
+                    let client_id = open_orders.allocate_id();
+                    debug!("Order {} shows child {}: {} @ {}", parent_id, client_id, quantity, price);
+                    emulated.child = Some(VisibleChild { client_id, quantity, price, acknowledged: false });
+                    self.children.insert(client_id, parent_id);
+                    actions.push(StrategyAction::Place { client_id, order: Order { price, quantity, ..emulated.parent.clone() } });
+                }
+                Some(child) => {
+                    let throttled = emulated.last_amend.is_some_and(|last| now.saturating_sub(last) < amend_interval);
+                    let moved = (price - child.price).abs();
+                    if throttled || moved == 0.0 || moved < self.config.peg_min_price_change {
+                        continue;
+                    }
+                    debug!("Re-pegging child {} of order {}: {} -> {}", child.client_id, parent_id, child.price, price);
+                    child.price = price;
+                    emulated.last_amend = Some(now);
+                    actions.push(StrategyAction::Amend { client_id: child.client_id, price, quantity: child.quantity });
+                }
+            }
+        }
+        actions
+    }
+
+    fn close_child(&mut self, child_id: u64) -> Option<(u64, Option<&mut EmulatedOrder>)> {
+        let parent_id = self.children.remove(&child_id)?;
+        let mut emulated = self.orders.get_mut(&parent_id);
+        if let Some(emulated) = emulated.as_mut().filter(|emulated| emulated.child.is_some_and(|child| child.client_id == child_id)) {
+            emulated.child = None;
+        }
+        Some((parent_id, emulated))
+    }
+}
+
+impl ParentOrders for OrderEmulator {
+    fn is_parent(&self, client_id: u64) -> bool {
+        self.orders.contains_key(&client_id)
+    }
+
+    fn parent_of(&self, child_id: u64) -> Option<u64> {
+        self.children.get(&child_id).copied()
+    }
+
+    fn parents(&self, symbol: Option<&str>) -> Vec<u64> {
+        self.orders
+            .iter()
+            .filter(|(_, emulated)| symbol.is_none_or(|symbol| emulated.parent.symbol == symbol))
+            .map(|(client_id, _)| *client_id)
+            .collect()
+    }
+
+    fn working(&self) -> Vec<WorkingParent> {
+        self.orders
+            .iter()
+            .map(|(&client_id, emulated)| WorkingParent {
+                remainder: OpenOrder { client_id, order: Order { quantity: emulated.remaining(), ..emulated.parent.clone() } },
+                child: emulated.child.map(|child| child.client_id),
+            })
+            .collect()
+    }
+
+    fn cancel(&mut self, client_id: u64) -> Option<(Order, Option<u64>)> {
+        let emulated = self.orders.remove(&client_id)?;
+        info!("Cancelling emulated order {} with {} left", client_id, emulated.remaining());
+        let remainder = Order { quantity: emulated.remaining(), ..emulated.parent };
+        Some((remainder, emulated.child.map(|child| child.client_id)))
+    }
+
+    fn child_acknowledged(&mut self, child_id: u64, order: &Order) {
+        let Some(parent_id) = self.parent_of(child_id) else {
+            return;
+        };
+        let child = self.orders.get_mut(&parent_id).and_then(|emulated| emulated.child.as_mut());
+        if let Some(child) = child.filter(|child| child.client_id == child_id) {
+            child.price = order.price;
+            child.quantity = order.quantity;
+            child.acknowledged = true;
+        }
+    }
+
+    fn child_filled(&mut self, child_id: u64, quantity: u64, _price: f64, _now: u64) -> Option<u64> {
+        let (parent_id, emulated) = self.close_child(child_id)?;
+        let Some(emulated) = emulated else {
+            return Some(parent_id);
+        };
+        emulated.filled = (emulated.filled + quantity).min(emulated.parent.quantity);
+        emulated.rejects = 0;
+        debug!("Emulated order {} filled {} of {}", parent_id, emulated.filled, emulated.parent.quantity);
+        if emulated.remaining() == 0 {
+            info!("Emulated order {} completed", parent_id);
+            self.orders.remove(&parent_id);
+        }
+        Some(parent_id)
+    }
+
+    fn child_closed(&mut self, child_id: u64) {
+        self.close_child(child_id);
+    }
+
+    fn child_rejected(&mut self, child_id: u64) -> Option<(u64, Order)> {
+        let max_rejects = self.config.max_child_rejects;
+        let (parent_id, emulated) = self.close_child(child_id)?;
+        let emulated = emulated?;
+        emulated.rejects += 1;
+        if emulated.rejects < max_rejects {
+            return None;
+        }
+        warn!("Abandoning emulated order {} after {} rejected children", parent_id, emulated.rejects);
+        let remainder = Order { quantity: emulated.remaining(), ..emulated.parent.clone() };
+        self.orders.remove(&parent_id);
+        Some((parent_id, remainder))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::market_data::order_book::OrderSide as BookSide;
+    use crate::strategy::context::ContextHarness;
+
+    const MS: u64 = NANOS_PER_MS;
+
+    fn emulator() -> OrderEmulator {
+        OrderEmulator::new(ExecutionConfig { peg_amend_interval_ms: 100, peg_min_price_change: 0.01, ..ExecutionConfig::default() })
+            .unwrap()
+    }
+
+    fn book(bid: f64, ask: f64) -> ConsolidatedBook {
+        let mut book = ConsolidatedBook::new();
+        book.update_level(Symbol::new("AAPL"), "XNAS", BookSide::Bid, bid, 100);
+        book.update_level(Symbol::new("AAPL"), "XNAS", BookSide::Ask, ask, 100);
+        book
+    }
+
+    fn single_place(actions: Vec<StrategyAction>) -> (u64, Order) {
+        match actions.as_slice() {
+            [StrategyAction::Place { client_id, order }] => (*client_id, order.clone()),
+            other => panic!("expected one child, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_iceberg_replenishes_on_fills() {
+        let mut emulator = emulator();
+        let open_orders = OpenOrders::new();
+        let parent = open_orders.allocate_id();
+        let order = Order { symbol: "AAPL".to_string(), price: 100.0, quantity: 25, side: OrderSide::Sell };
+        let params = EmulationParams { display_quantity: Some(10), peg: None };
+        emulator.start(parent, order, params, &ConsolidatedBook::new()).unwrap();
+
+        let mut shown = Vec::new();
+        for step in 0..5 {
+            let actions = emulator.poll(step * MS, &open_orders, &ConsolidatedBook::new());
+            if actions.is_empty() {
+                break;
+            }
+            let (child_id, child) = single_place(actions);
+            emulator.child_acknowledged(child_id, &child);
+            assert!(emulator.poll(step * MS, &open_orders, &ConsolidatedBook::new()).is_empty(), "one visible child at a time");
+            assert_eq!(emulator.child_filled(child_id, child.quantity, child.price, step * MS), Some(parent));
+            shown.push((child.quantity, child.price));
+        }
+        assert_eq!(shown, vec![(10, 100.0), (10, 100.0), (5, 100.0)]);
+        assert!(!emulator.is_parent(parent));
+    }
+
+    #[test]
+    fn test_peg_follows_the_book_with_throttled_amends() {
+        let mut emulator = emulator();
+        let open_orders = OpenOrders::new();
+        let order = Order { symbol: "AAPL".to_string(), price: 100.5, quantity: 10, side: OrderSide::Buy };
+        let peg = Peg { reference: PegReference::Primary, offset: 0.01 };
+        let params = EmulationParams { display_quantity: None, peg: Some(peg) };
+        // no book yet: nothing to peg to
+        assert!(emulator.start(1, order.clone(), params, &ConsolidatedBook::new()).is_err());
+        emulator.start(1, order, params, &book(99.0, 101.0)).unwrap();
+        let (child_id, child) = single_place(emulator.poll(0, &open_orders, &book(99.0, 101.0)));
+        assert_eq!(child.price, 99.01);
+        emulator.child_acknowledged(child_id, &child);
+
+        // the bid moves up: amended, then throttled until the interval has passed
+        let moved = book(99.5, 101.0);
+        let amend = emulator.poll(10 * MS, &open_orders, &moved);
+        assert_eq!(amend, vec![StrategyAction::Amend { client_id: child_id, price: 99.51, quantity: 10 }]);
+        assert!(emulator.poll(50 * MS, &open_orders, &book(99.8, 101.0)).is_empty());
+        let amend = emulator.poll(110 * MS, &open_orders, &book(99.8, 101.0));
+        assert_eq!(amend, vec![StrategyAction::Amend { client_id: child_id, price: 99.81, quantity: 10 }]);
+        // moves below peg_min_price_change are ignored, the limit caps the peg
+        assert!(emulator.poll(300 * MS, &open_orders, &book(99.805, 101.0)).is_empty());
+        let amend = emulator.poll(400 * MS, &open_orders, &book(100.8, 101.0));
+        assert_eq!(amend, vec![StrategyAction::Amend { client_id: child_id, price: 100.5, quantity: 10 }]);
+
+        let (remainder, working) = emulator.cancel(1).unwrap();
+        assert_eq!((remainder.quantity, working), (10, Some(child_id)));
+        let mid_peg = EmulationParams { display_quantity: None, peg: Some(Peg { reference: PegReference::Mid, offset: f64::NAN }) };
+        assert!(emulator.start(2, remainder, mid_peg, &moved).is_err());
+    }
+
+    #[test]
+    fn test_market_peg_from_context_abandoned_after_rejects() {
+        let mut emulator = emulator();
+        let harness = ContextHarness::default();
+
+        let mut ctx = harness.ctx(0);
+        let order = Order { symbol: "AAPL".to_string(), price: 99.0, quantity: 5, side: OrderSide::Sell };
+        let emulation = EmulationParams { peg: Some(Peg { reference: PegReference::Market, offset: -0.02 }), ..Default::default() };
+        let parent = ctx.emulate(order, emulation);
+        let Some(StrategyAction::Emulate { client_id, order, emulation }) = ctx.into_actions().pop() else {
+            panic!("no emulated order")
+        };
+        assert_eq!(client_id, parent);
+        // a market-pegged sell crosses to the best bid; rejected children are re-shown until the parent is abandoned
+        let book = book(99.5, 100.0);
+        emulator.start(client_id, order, emulation, &book).unwrap();
+        for attempt in 1..=3 {
+            let (child_id, child) = single_place(emulator.poll(attempt * MS, &harness.open_orders, &book));
+            assert_eq!((child.price, child.quantity), (99.48, 5));
+            let abandoned = emulator.child_rejected(child_id);
+            assert_eq!(abandoned.map(|(id, remainder)| (id, remainder.quantity)), (attempt == 3).then_some((parent, 5)));
+        }
+        assert!(!emulator.is_parent(parent));
+    }
+}
@@ -1,2 +1,43 @@
 pub mod executor;
 pub mod order_formatter;
+pub mod open_orders;
+pub mod algo;
+pub mod emulation;
+
+use crate::strategy::Order;
+use open_orders::OpenOrder;
+
+// a parent still being worked: its unfilled part under the parent id, and the child showing it at the venue
+#[derive(Debug, Clone, PartialEq)]
+pub struct WorkingParent {
+    pub remainder: OpenOrder,
+    pub child: Option<u64>,
+}
+
+// parent orders worked client-side as child orders sent through the normal order path.
+// parent and child ids come from the same client id sequence as the strategy's own orders.
+pub trait ParentOrders {
+    fn is_parent(&self, client_id: u64) -> bool;
+
+    fn parent_of(&self, child_id: u64) -> Option<u64>;
+
+    // parents in `symbol`, or all of them
+    fn parents(&self, symbol: Option<&str>) -> Vec<u64>;
+
+    fn working(&self) -> Vec<WorkingParent>;
+
+    /// stops working a parent; returns the unfilled remainder and the child still working
+    fn cancel(&mut self, client_id: u64) -> Option<(Order, Option<u64>)>;
+
+    /// the venue holds the child as `order`, after a place or amend or a refused amend
+    fn child_acknowledged(&mut self, _child_id: u64, _order: &Order) {}
+
+    /// books a child fill and returns its parent, which may already have been cancelled
+    fn child_filled(&mut self, child_id: u64, quantity: u64, price: f64, now: u64) -> Option<u64>;
+
+    // the child is no longer working (cancelled or unknown to the venue)
+    fn child_closed(&mut self, child_id: u64);
+
+    /// a child was refused; returns the parent and its remainder once it is abandoned
+    fn child_rejected(&mut self, child_id: u64) -> Option<(u64, Order)>;
+}
@@ -0,0 +1,85 @@
+use crate::strategy::Order;
+use std::cell::Cell;
+use std::collections::BTreeMap;
+
+// working order acknowledged by the venue and not yet filled
+#[derive(Debug, Clone, PartialEq)]
+pub struct OpenOrder {
+    pub client_id: u64,
+    pub order: Order,
+}
+
+// orders working at the venue, keyed by client order id
+#[derive(Debug)]
+pub struct OpenOrders {
+    orders: BTreeMap<u64, OpenOrder>,
+    // ids are handed out through shared references while strategies hold a context
+    next_client_id: Cell<u64>,
+}
+
+impl Default for OpenOrders {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl OpenOrders {
+    pub fn new() -> Self {
+        OpenOrders {
+            orders: BTreeMap::new(),
+            next_client_id: Cell::new(1),
+        }
+    }
+
+    /// reserves the next client order id
+    pub fn allocate_id(&self) -> u64 {
+        let id = self.next_client_id.get();
+        self.next_client_id.set(id + 1);
+        id
+    }
+
+    pub fn insert(&mut self, client_id: u64, order: Order) {
+        self.orders.insert(client_id, OpenOrder { client_id, order });
+    }
+
+    pub fn remove(&mut self, client_id: u64) -> Option<OpenOrder> {
+        self.orders.remove(&client_id)
+    }
+
+    pub fn get(&self, client_id: u64) -> Option<&OpenOrder> {
+        self.orders.get(&client_id)
+    }
+
+    // oldest first
+    pub fn iter(&self) -> impl Iterator<Item = &OpenOrder> {
+        self.orders.values()
+    }
+
+    pub fn for_symbol<'a>(&'a self, symbol: &'a str) -> impl Iterator<Item = &'a OpenOrder> {
+        self.iter().filter(move |open| open.order.symbol == symbol)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::strategy::OrderSide;
+
+    #[test]
+    fn test_open_orders() {
+        let mut open_orders = OpenOrders::new();
+        let first = open_orders.allocate_id();
+        let second = open_orders.allocate_id();
+        assert_eq!((first, second), (1, 2));
+
+        let order = |symbol: &str| Order { symbol: symbol.to_string(), price: 100.0, quantity: 1, side: OrderSide::Buy };
+        open_orders.insert(second, order("MSFT"));
+        open_orders.insert(first, order("AAPL"));
+        assert_eq!(open_orders.iter().map(|open| open.client_id).collect::<Vec<_>>(), vec![1, 2]);
+        assert_eq!(open_orders.for_symbol("MSFT").count(), 1);
+
+        assert_eq!(open_orders.remove(first).unwrap().order.symbol, "AAPL");
+        assert!(open_orders.get(first).is_none());
+        assert_eq!(open_orders.iter().count(), 1);
+    }
+}
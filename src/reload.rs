@@ -0,0 +1,258 @@
+use crate::config::Config;
+use crate::strategy::risk_management::RiskManager;
+use crate::strategy::{Strategy, StrategyRegistry};
+use anyhow::{anyhow, bail, Context, Result};
+use log::{info, warn};
+use std::collections::BTreeSet;
+use std::path::{Path, PathBuf};
+use std::time::SystemTime;
+
+// read once at startup, a change is logged but has no effect until a restart
+const RESTART_ONLY: [&str; 6] = [
+    "strategy.bar_interval_ms",
+    "strategy.timer_interval_ms",
+    "strategy.snapshot_path",
+    "strategy.snapshot_interval_secs",
+    "strategy.snapshot_max_age_secs",
+    "risk.initial_capital",
+];
+
+// applies edits of the `[strategy]` and `[risk]` tables to the running strategy and risk manager.
+// a new configuration is validated in full before anything is swapped in; a rejected one changes nothing.
+pub struct ConfigReloader {
+    path: PathBuf,
+    modified: Option<SystemTime>,
+    // tables as last applied, the base of the next diff
+    strategy: toml::Value,
+    risk: toml::Value,
+}
+
+impl ConfigReloader {
+    pub fn new(path: &Path) -> Result<Self> {
+        let modified = modified(path);
+        let (_, raw) = read(path)?;
+        Ok(ConfigReloader { path: path.to_path_buf(), modified, strategy: table(&raw, "strategy"), risk: table(&raw, "risk") })
+    }
+
+    /// true when the file was written since it was last read
+    pub fn changed(&self) -> bool {
+        modified(&self.path) != self.modified
+    }
+
+    /// re-reads the file and applies what changed, returning one `key: old -> new` line per change.
+    /// a strategy takes new parameters in place, or else is rebuilt and handed the old one's state;
+    /// on error the running strategy and risk limits are left as they were.
+    pub fn reload(
+        &mut self,
+        registry: &StrategyRegistry,
+        strategy: &mut Box<dyn Strategy>,
+        risk_manager: &mut RiskManager,
+    ) -> Result<Vec<String>> {
+        // a broken file is reported once, not on every check
+        self.modified = modified(&self.path);
+        let (config, raw) = read(&self.path)?;
+        let (strategy_table, risk_table) = (table(&raw, "strategy"), table(&raw, "risk"));
+        let mut strategy_changes = Vec::new();
+        diff("strategy", Some(&self.strategy), Some(&strategy_table), &mut strategy_changes);
+        let mut risk_changes = Vec::new();
+        diff("risk", Some(&self.risk), Some(&risk_table), &mut risk_changes);
+        if strategy_changes.is_empty() && risk_changes.is_empty() {
+            return Ok(Vec::new());
+        }
+
+        if self.strategy.get("name") != strategy_table.get("name") {
+            bail!("switching to strategy {} needs a restart", config.strategy.name);
+        }
+        config.risk.validate().context("invalid [risk]")?;
+        let rebuilt = if strategy_changes.is_empty() { None } else { reconfigure(registry, &config, strategy)? };
+
+        for change in strategy_changes.iter().chain(&risk_changes) {
+            info!("Config change {}", change);
+            if RESTART_ONLY.iter().any(|key| change.starts_with(&format!("{}:", key))) {
+                warn!("{} only takes effect on restart", change.split(':').next().unwrap_or_default());
+            }
+        }
+        if let Some(rebuilt) = rebuilt {
+            *strategy = rebuilt;
+        }
+        if !risk_changes.is_empty() {
+            let risk = &config.risk;
+            risk_manager.set_limits(risk.max_position_size, risk.max_loss_per_trade, risk.stop_loss_percentage);
+        }
+        self.strategy = strategy_table;
+        self.risk = risk_table;
+        strategy_changes.extend(risk_changes);
+        Ok(strategy_changes)
+    }
+}
+
+// validates the new parameters, then hands them to the running strategy, which keeps its working orders.
+// a strategy that cannot take them in place is rebuilt with the running one's state, which keeps windows and signals warm
+fn reconfigure(registry: &StrategyRegistry, config: &Config, running: &mut Box<dyn Strategy>) -> Result<Option<Box<dyn Strategy>>> {
+    let mut rebuilt = registry
+        .build_from_config(&config.strategy)
+        .map_err(|e| anyhow!("invalid [strategy]: {}", e))?;
+    let (old_symbols, new_symbols) = (running.symbols(), rebuilt.symbols());
+    if old_symbols.iter().collect::<BTreeSet<_>>() != new_symbols.iter().collect::<BTreeSet<_>>() {
+        bail!("changing symbols from {:?} to {:?} needs a restart", old_symbols, new_symbols);
+    }
+    let params = &config.strategy.params[&config.strategy.name];
+    let in_place = match running.reconfigure(params) {
+        Ok(()) => return Ok(None),
+        Err(e) => e,
+    };
+    let state = running.snapshot().ok_or_else(|| {
+        anyhow!(
+            "strategy {} cannot take new parameters while running ({}) and has no state to hand over, changing it needs a restart",
+            config.strategy.name,
+            in_place
+        )
+    })?;
+    rebuilt
+        .restore(state)
+        .map_err(|e| anyhow!("new parameters cannot take over the running state: {}", e))?;
+    Ok(Some(rebuilt))
+}
+
+fn read(path: &Path) -> Result<(Config, toml::Value)> {
+    let content = std::fs::read_to_string(path).with_context(|| format!("Failed to read config file at {}", path.display()))?;
+    let raw: toml::Value = toml::from_str(&content).with_context(|| format!("Failed to parse config file at {}", path.display()))?;
+    let config = raw.clone().try_into().with_context(|| format!("Failed to parse config file at {}", path.display()))?;
+    Ok((config, raw))
+}
+
+fn modified(path: &Path) -> Option<SystemTime> {
+    std::fs::metadata(path).and_then(|metadata| metadata.modified()).ok()
+}
+
+fn table(raw: &toml::Value, name: &str) -> toml::Value {
+    raw.get(name).cloned().unwrap_or_else(|| toml::Value::Table(Default::default()))
+}
+
+// changed leaves of two tables as `path: old -> new`, in key order
+fn diff(path: &str, old: Option<&toml::Value>, new: Option<&toml::Value>, changes: &mut Vec<String>) {
+    match (old, new) {
+        (Some(toml::Value::Table(old)), Some(toml::Value::Table(new))) => {
+            for key in old.keys().chain(new.keys()).collect::<BTreeSet<_>>() {
+                diff(&format!("{}.{}", path, key), old.get(key), new.get(key), changes);
+            }
+        }
+        _ if old == new => {}
+        _ => {
+            let show = |value: Option<&toml::Value>| value.map_or("(unset)".to_string(), toml::Value::to_string);
+            changes.push(format!("{}: {} -> {}", path, show(old), show(new)));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::market_data::parser::Tick;
+    use crate::strategy::context::ContextHarness;
+    use crate::strategy::{Order, OrderSide, StrategyAction};
+
+    const CONFIG: &str = include_str!("../config.toml");
+
+    // what a tick at `price` makes the strategy do
+    fn tick(strategy: &mut dyn Strategy, harness: &ContextHarness, price: f64, timestamp: u64) -> Vec<StrategyAction> {
+        let mut ctx = harness.ctx(timestamp);
+        strategy.on_tick(&Tick { symbol: "AAPL".into(), price, volume: 1, timestamp, ..Default::default() }, &mut ctx);
+        ctx.into_actions()
+    }
+
+    // the risk manager of the harness has the config's limits
+    fn setup(dir: &Path, config: &str) -> (PathBuf, ConfigReloader, StrategyRegistry, Box<dyn Strategy>, ContextHarness) {
+        let path = dir.join("config.toml");
+        std::fs::write(&path, config).unwrap();
+        let config = Config::load(path.to_str().unwrap()).unwrap();
+        let registry = StrategyRegistry::new();
+        let strategy = registry.build_from_config(&config.strategy).unwrap();
+        let risk = &config.risk;
+        let risk_manager = RiskManager::new(risk.max_position_size, risk.max_loss_per_trade, risk.stop_loss_percentage, risk.initial_capital);
+        let harness = ContextHarness { risk: risk_manager, ..Default::default() };
+        (path.clone(), ConfigReloader::new(&path).unwrap(), registry, strategy, harness)
+    }
+
+    #[test]
+    fn test_reload_keeps_state_and_logs_diff() {
+        let dir = tempfile::tempdir().unwrap();
+        let (path, mut reloader, registry, mut strategy, mut harness) = setup(dir.path(), CONFIG);
+        assert!(!reloader.changed());
+        for price in [100.0, 101.0, 102.0] {
+            assert!(tick(&mut *strategy, &harness, price, 0).is_empty());
+        }
+        assert!(reloader.reload(&registry, &mut strategy, &mut harness.risk).unwrap().is_empty());
+
+        // a 3-tick window is already full with the prices seen before the reload
+        let edited = CONFIG
+            .replace("window_size = 10", "window_size = 3")
+            .replace("max_position_size = 100", "max_position_size = 5")
+            .replace("quantity = 10\nlong_period", "quantity = 20\nlong_period");
+        std::fs::write(&path, edited).unwrap();
+        let changes = reloader.reload(&registry, &mut strategy, &mut harness.risk).unwrap();
+        assert_eq!(
+            changes,
+            vec![
+                "strategy.sma.window_size: 10 -> 3",
+                "strategy.trend.quantity: 10 -> 20",
+                "risk.max_position_size: 100 -> 5",
+            ]
+        );
+        assert_eq!(tick(&mut *strategy, &harness, 110.0, 0).len(), 1);
+        let mut order = Order { symbol: "AAPL".to_string(), price: 100.0, quantity: 10, side: OrderSide::Buy };
+        assert_eq!(harness.risk.evaluate_order(&mut order, 100.0).unwrap().quantity, 5);
+    }
+
+    #[test]
+    fn test_rejected_reload_changes_nothing() {
+        let dir = tempfile::tempdir().unwrap();
+        let (path, mut reloader, registry, mut strategy, mut harness) = setup(dir.path(), CONFIG);
+        for price in [100.0, 101.0] {
+            tick(&mut *strategy, &harness, price, 0);
+        }
+        let before = strategy.snapshot();
+
+        // a good risk limit next to a bad strategy parameter: neither is applied
+        let rejected = [
+            CONFIG.replace("window_size = 10", "window_size = 0").replace("max_position_size = 100", "max_position_size = 5"),
+            CONFIG.replace("stop_loss_percentage = 0.01", "stop_loss_percentage = 1.5"),
+            CONFIG.replace("symbol = \"AAPL\"\nquantity = 1\nwindow_size", "symbol = \"MSFT\"\nquantity = 1\nwindow_size"),
+            CONFIG.replace("name = \"sma\" ", "name = \"trend\" "),
+            CONFIG.replace("[risk]", "[risk"),
+        ];
+        for edited in rejected {
+            std::fs::write(&path, edited).unwrap();
+            assert!(reloader.reload(&registry, &mut strategy, &mut harness.risk).is_err());
+            assert_eq!(strategy.snapshot(), before);
+            let mut order = Order { symbol: "AAPL".to_string(), price: 100.0, quantity: 10, side: OrderSide::Buy };
+            assert_eq!(harness.risk.evaluate_order(&mut order, 100.0).unwrap().quantity, 10);
+        }
+    }
+
+    #[test]
+    fn test_reload_reconfigures_in_place() {
+        // the market maker has no state to hand over, so it takes new parameters while running
+        let config = CONFIG
+            .replace("name = \"sma\" ", "name = \"avellaneda_stoikov\" ")
+            .replace("volatility_window = 100", "volatility_window = 2");
+        let dir = tempfile::tempdir().unwrap();
+        let (path, mut reloader, registry, mut strategy, mut harness) = setup(dir.path(), &config);
+        let sec = 1_000_000_000;
+        tick(&mut *strategy, &harness, 100.0, 0);
+        tick(&mut *strategy, &harness, 100.2, sec);
+        assert_eq!(tick(&mut *strategy, &harness, 100.0, 2 * sec).len(), 2);
+
+        std::fs::write(&path, config.replace("quote_size = 1", "quote_size = 2")).unwrap();
+        let changes = reloader.reload(&registry, &mut strategy, &mut harness.risk).unwrap();
+        assert_eq!(changes, vec!["strategy.avellaneda_stoikov.quote_size: 1 -> 2"]);
+
+        // the working quotes are amended to the new size, not sent again
+        let actions = tick(&mut *strategy, &harness, 100.0, 3 * sec);
+        assert_eq!(actions.len(), 2);
+        assert!(actions.iter().all(|action| matches!(action, StrategyAction::Amend { quantity: 2, .. })));
+
+        std::fs::write(&path, config.replace("risk_aversion = 0.1", "risk_aversion = -0.1")).unwrap();
+        assert!(reloader.reload(&registry, &mut strategy, &mut harness.risk).is_err());
+    }
+}
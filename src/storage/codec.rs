@@ -0,0 +1,122 @@
+use crate::market_data::parser::Tick;
+use crate::market_data::symbol::Symbol;
+use std::io;
+
+// prices are stored as fixed-point integers with 8 decimal places
+pub const PRICE_SCALE: f64 = 100_000_000.0;
+
+pub fn price_to_fixed(price: f64) -> i64 {
+    (price * PRICE_SCALE).round() as i64
+}
+
+pub fn fixed_to_price(fixed: i64) -> f64 {
+    fixed as f64 / PRICE_SCALE
+}
+
+fn zigzag_encode(value: i64) -> u64 {
+    ((value << 1) ^ (value >> 63)) as u64
+}
+
+fn zigzag_decode(value: u64) -> i64 {
+    ((value >> 1) as i64) ^ -((value & 1) as i64)
+}
+
+pub fn write_varint(buffer: &mut Vec<u8>, mut value: u64) {
+    while value >= 0x80 {
+        buffer.push((value as u8) | 0x80);
+        value >>= 7;
+    }
+    buffer.push(value as u8);
+}
+
+pub fn read_varint(data: &[u8], pos: &mut usize) -> io::Result<u64> {
+    let mut value = 0u64;
+    let mut shift = 0;
+    loop {
+        let byte = *data
+            .get(*pos)
+            .ok_or_else(|| io::Error::new(io::ErrorKind::UnexpectedEof, "Truncated varint"))?;
+        *pos += 1;
+        if shift >= 64 {
+            return Err(io::Error::new(io::ErrorKind::InvalidData, "Varint overflow"));
+        }
+        value |= ((byte & 0x7f) as u64) << shift;
+        if byte & 0x80 == 0 {
+            return Ok(value);
+        }
+        shift += 7;
+    }
+}
+
+// encodes ticks of one symbol as a block: the first tick is stored relative to zero,
+// every following one as zigzag varint deltas of timestamp and price against its predecessor.
+// volumes are plain varints.
+pub fn encode_block(ticks: &[Tick]) -> Vec<u8> {
+    let mut buffer = Vec::with_capacity(ticks.len() * 6);
+    let mut prev_timestamp = 0i64;
+    let mut prev_price = 0i64;
+    for tick in ticks {
+        let timestamp = tick.timestamp as i64;
+        let price = price_to_fixed(tick.price);
+        write_varint(&mut buffer, zigzag_encode(timestamp.wrapping_sub(prev_timestamp)));
+        write_varint(&mut buffer, zigzag_encode(price.wrapping_sub(prev_price)));
+        write_varint(&mut buffer, tick.volume);
+        prev_timestamp = timestamp;
+        prev_price = price;
+    }
+    buffer
+}
+
+pub fn decode_block(data: &[u8], count: usize, symbol: Symbol) -> io::Result<Vec<Tick>> {
+    let mut ticks = Vec::with_capacity(count);
+    let mut pos = 0;
+    let mut timestamp = 0i64;
+    let mut price = 0i64;
+    for _ in 0..count {
+        timestamp = timestamp.wrapping_add(zigzag_decode(read_varint(data, &mut pos)?));
+        price = price.wrapping_add(zigzag_decode(read_varint(data, &mut pos)?));
+        let volume = read_varint(data, &mut pos)?;
+        ticks.push(Tick {
+            symbol,
+            price: fixed_to_price(price),
+            volume,
+            timestamp: timestamp as u64,
+            sequence: None,
+            aggressor: None,
+        });
+    }
+    Ok(ticks)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_varint_zigzag_round_trip() {
+        for value in [0i64, 1, -1, 63, -64, 300, -300, i64::MAX, i64::MIN] {
+            let mut buffer = Vec::new();
+            write_varint(&mut buffer, zigzag_encode(value));
+            let mut pos = 0;
+            assert_eq!(zigzag_decode(read_varint(&buffer, &mut pos).unwrap()), value);
+            assert_eq!(pos, buffer.len());
+        }
+        let mut pos = 0;
+        assert!(read_varint(&[0x80], &mut pos).is_err());
+    }
+
+    #[test]
+    fn test_block_round_trip() {
+        let symbol = Symbol::new("AAPL");
+        let base = 1_700_000_000_000_000_000u64;
+        let ticks = vec![
+            Tick { symbol, price: 189.25, volume: 100, timestamp: base, ..Default::default() },
+            Tick { symbol, price: 189.26, volume: 5, timestamp: base + 1_500, ..Default::default() },
+            Tick { symbol, price: 189.24, volume: 70, timestamp: base + 1_200, ..Default::default() }, // out of order
+        ];
+        let encoded = encode_block(&ticks);
+        // absolute first tick plus a few bytes per delta
+        assert!(encoded.len() < 30);
+        assert_eq!(decode_block(&encoded, ticks.len(), symbol).unwrap(), ticks);
+    }
+}
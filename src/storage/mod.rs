@@ -0,0 +1,74 @@
+pub mod codec;
+pub mod writer;
+pub mod reader;
+
+use crate::market_data::symbol::Symbol;
+use byteorder::{LittleEndian, ReadBytesExt, WriteBytesExt};
+use chrono::{DateTime, NaiveDate, Utc};
+use std::io::{self, Read, Write};
+use std::path::{Path, PathBuf};
+
+// on-disk layout of the tick store:
+//   <root>/<SYMBOL>/<YYYY-MM-DD>.ticks  append-only sequence of delta-encoded blocks
+//   <root>/<SYMBOL>/<YYYY-MM-DD>.idx    one fixed-size `BlockIndex` record per block
+// trading days are UTC calendar days of the tick's event timestamp.
+// feed sequence numbers are not stored.
+
+pub const DATA_EXTENSION: &str = "ticks";
+pub const INDEX_EXTENSION: &str = "idx";
+
+// index entry describing one block in a data file
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct BlockIndex {
+    pub min_timestamp: u64,
+    pub max_timestamp: u64,
+    pub offset: u64,
+    pub count: u32,
+    pub length: u32,
+}
+
+impl BlockIndex {
+    pub const SIZE: usize = 32;
+
+    pub fn write_to<W: Write>(&self, writer: &mut W) -> io::Result<()> {
+        writer.write_u64::<LittleEndian>(self.min_timestamp)?;
+        writer.write_u64::<LittleEndian>(self.max_timestamp)?;
+        writer.write_u64::<LittleEndian>(self.offset)?;
+        writer.write_u32::<LittleEndian>(self.count)?;
+        writer.write_u32::<LittleEndian>(self.length)
+    }
+
+    pub fn read_from<R: Read>(reader: &mut R) -> io::Result<Self> {
+        Ok(BlockIndex {
+            min_timestamp: reader.read_u64::<LittleEndian>()?,
+            max_timestamp: reader.read_u64::<LittleEndian>()?,
+            offset: reader.read_u64::<LittleEndian>()?,
+            count: reader.read_u32::<LittleEndian>()?,
+            length: reader.read_u32::<LittleEndian>()?,
+        })
+    }
+
+    pub fn overlaps(&self, start: u64, end: u64) -> bool {
+        self.min_timestamp <= end && self.max_timestamp >= start
+    }
+}
+
+pub fn trading_day(timestamp: u64) -> NaiveDate {
+    DateTime::<Utc>::from_timestamp_nanos(timestamp.min(i64::MAX as u64) as i64).date_naive()
+}
+
+// keeps symbols like "BTC/USD" from escaping the store directory
+pub fn symbol_dir(root: &Path, symbol: &Symbol) -> PathBuf {
+    let name: String = symbol
+        .as_str()
+        .chars()
+        .map(|c| if c.is_ascii_alphanumeric() || c == '-' || c == '.' { c } else { '_' })
+        .collect();
+    root.join(name)
+}
+
+pub fn partition_path(root: &Path, symbol: &Symbol, day: NaiveDate, extension: &str) -> PathBuf {
+    symbol_dir(root, symbol).join(format!("{}.{}", day.format("%Y-%m-%d"), extension))
+}
+
+pub use writer::TickWriter;
@@ -0,0 +1,204 @@
+use super::codec::decode_block;
+use super::{partition_path, symbol_dir, trading_day, BlockIndex, DATA_EXTENSION, INDEX_EXTENSION};
+use crate::market_data::parser::Tick;
+use crate::market_data::symbol::Symbol;
+use chrono::NaiveDate;
+use std::collections::VecDeque;
+use std::fs::{self, File};
+use std::io::{self, BufReader, Read, Seek, SeekFrom};
+use std::path::{Path, PathBuf};
+
+// read side of the tick store, used by the warm-up, backtests and replays
+pub struct TickReader {
+    root: PathBuf,
+}
+
+impl TickReader {
+    pub fn new(root: impl AsRef<Path>) -> Self {
+        TickReader { root: root.as_ref().to_path_buf() }
+    }
+
+    // trading days stored for a symbol, oldest first
+    pub fn days(&self, symbol: &Symbol) -> io::Result<Vec<NaiveDate>> {
+        let dir = symbol_dir(&self.root, symbol);
+        if !dir.exists() {
+            return Ok(Vec::new());
+        }
+        let mut days = Vec::new();
+        for entry in fs::read_dir(dir)? {
+            let path = entry?.path();
+            if path.extension().and_then(|e| e.to_str()) != Some(DATA_EXTENSION) {
+                continue;
+            }
+            if let Some(day) = path
+                .file_stem()
+                .and_then(|s| s.to_str())
+                .and_then(|s| NaiveDate::parse_from_str(s, "%Y-%m-%d").ok())
+            {
+                days.push(day);
+            }
+        }
+        days.sort();
+        Ok(days)
+    }
+
+    /// ticks of `symbol` with `start <= timestamp <= end` (nanoseconds), in the order they were written.
+    /// only blocks whose index range overlaps the query are read from disk.
+    pub fn read_range(&self, symbol: &Symbol, start: u64, end: u64) -> io::Result<TickIter> {
+        let (first_day, last_day) = (trading_day(start), trading_day(end));
+        let mut files = Vec::new();
+        let mut blocks = VecDeque::new();
+        for day in self.days(symbol)? {
+            if day < first_day || day > last_day {
+                continue;
+            }
+            let index = read_index(&partition_path(&self.root, symbol, day, INDEX_EXTENSION))?;
+            let overlapping: Vec<BlockIndex> =
+                index.into_iter().filter(|block| block.overlaps(start, end)).collect();
+            if overlapping.is_empty() {
+                continue;
+            }
+            files.push(File::open(partition_path(&self.root, symbol, day, DATA_EXTENSION))?);
+            blocks.extend(overlapping.into_iter().map(|block| (files.len() - 1, block)));
+        }
+        Ok(TickIter {
+            symbol: *symbol,
+            start,
+            end,
+            files,
+            blocks,
+            current: Vec::new().into_iter(),
+        })
+    }
+
+    pub fn read_day(&self, symbol: &Symbol, day: NaiveDate) -> io::Result<TickIter> {
+        let start = day.and_hms_opt(0, 0, 0).and_then(|t| t.and_utc().timestamp_nanos_opt()).unwrap_or(0);
+        let end = start + 86_400 * 1_000_000_000 - 1;
+        self.read_range(symbol, start as u64, end as u64)
+    }
+}
+
+fn read_index(path: &Path) -> io::Result<Vec<BlockIndex>> {
+    let mut reader = BufReader::new(File::open(path)?);
+    let mut entries = Vec::new();
+    let mut record = [0u8; BlockIndex::SIZE];
+    loop {
+        match reader.read_exact(&mut record) {
+            Ok(()) => entries.push(BlockIndex::read_from(&mut &record[..])?),
+            // a torn trailing record from a crash is ignored
+            Err(e) if e.kind() == io::ErrorKind::UnexpectedEof => break,
+            Err(e) => return Err(e),
+        }
+    }
+    Ok(entries)
+}
+
+// lazily decodes one block at a time
+pub struct TickIter {
+    symbol: Symbol,
+    start: u64,
+    end: u64,
+    files: Vec<File>,
+    blocks: VecDeque<(usize, BlockIndex)>,
+    current: std::vec::IntoIter<Tick>,
+}
+
+impl TickIter {
+    fn load_next_block(&mut self) -> io::Result<bool> {
+        let (file_index, block) = match self.blocks.pop_front() {
+            Some(next) => next,
+            None => return Ok(false),
+        };
+        let file = &mut self.files[file_index];
+        file.seek(SeekFrom::Start(block.offset))?;
+        let mut data = vec![0u8; block.length as usize];
+        file.read_exact(&mut data)?;
+        self.current = decode_block(&data, block.count as usize, self.symbol)?.into_iter();
+        Ok(true)
+    }
+}
+
+impl Iterator for TickIter {
+    type Item = io::Result<Tick>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            for tick in self.current.by_ref() {
+                if tick.timestamp >= self.start && tick.timestamp <= self.end {
+                    return Some(Ok(tick));
+                }
+            }
+            match self.load_next_block() {
+                Ok(true) => continue,
+                Ok(false) => return None,
+                Err(e) => {
+                    self.blocks.clear();
+                    return Some(Err(e));
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::storage::writer::TickWriter;
+
+    const DAY: u64 = 86_400 * 1_000_000_000;
+
+    fn tick(symbol: Symbol, price: f64, timestamp: u64) -> Tick {
+        Tick { symbol, price, volume: 10, timestamp, ..Default::default() }
+    }
+
+    #[test]
+    fn test_write_and_read_range() {
+        let dir = tempfile::tempdir().unwrap();
+        let aapl = Symbol::new("AAPL");
+        let msft = Symbol::new("MSFT");
+        let day_start = 20_000 * DAY; // 2024-10-04
+
+        {
+            let mut writer = TickWriter::new(dir.path(), 2).unwrap();
+            for i in 0..5 {
+                writer.append(&tick(aapl, 100.0 + i as f64, day_start + i * 1_000)).unwrap();
+            }
+            writer.append(&tick(msft, 400.0, day_start + 500)).unwrap();
+            // next trading day goes to its own file
+            writer.append(&tick(aapl, 110.0, day_start + DAY + 1)).unwrap();
+        } // dropping the writer flushes the partial blocks
+
+        let reader = TickReader::new(dir.path());
+        assert_eq!(reader.days(&aapl).unwrap().len(), 2);
+
+        let all: Vec<Tick> = reader.read_range(&aapl, 0, u64::MAX).unwrap().map(Result::unwrap).collect();
+        assert_eq!(all.len(), 6);
+        assert_eq!(all[0], tick(aapl, 100.0, day_start));
+        assert_eq!(all[5].price, 110.0);
+
+        let prices: Vec<f64> = reader
+            .read_range(&aapl, day_start + 1_000, day_start + 3_000)
+            .unwrap()
+            .map(|t| t.unwrap().price)
+            .collect();
+        assert_eq!(prices, vec![101.0, 102.0, 103.0]);
+
+        let msft_ticks: Vec<Tick> = reader.read_day(&msft, trading_day(day_start)).unwrap().map(Result::unwrap).collect();
+        assert_eq!(msft_ticks, vec![tick(msft, 400.0, day_start + 500)]);
+        assert_eq!(reader.read_range(&Symbol::new("GOOG"), 0, u64::MAX).unwrap().count(), 0);
+    }
+
+    #[test]
+    fn test_append_after_reopen() {
+        let dir = tempfile::tempdir().unwrap();
+        let symbol = Symbol::new("ES");
+        for price in [1.0, 2.0] {
+            let mut writer = TickWriter::new(dir.path(), 100).unwrap();
+            writer.append(&tick(symbol, price, DAY + price as u64)).unwrap();
+            writer.flush().unwrap();
+        }
+        let reader = TickReader::new(dir.path());
+        let prices: Vec<f64> = reader.read_range(&symbol, 0, u64::MAX).unwrap().map(|t| t.unwrap().price).collect();
+        assert_eq!(prices, vec![1.0, 2.0]);
+    }
+}
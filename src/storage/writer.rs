@@ -0,0 +1,124 @@
+use super::codec::encode_block;
+use super::{partition_path, symbol_dir, trading_day, BlockIndex, DATA_EXTENSION, INDEX_EXTENSION};
+use crate::market_data::parser::Tick;
+use crate::market_data::symbol::Symbol;
+use chrono::NaiveDate;
+use log::{debug, error};
+use std::collections::HashMap;
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+
+// open data/index files of one symbol and trading day, plus the ticks not yet written
+struct Partition {
+    data: File,
+    index: File,
+    offset: u64,
+    pending: Vec<Tick>,
+}
+
+impl Partition {
+    fn open(root: &Path, symbol: &Symbol, day: NaiveDate) -> io::Result<Self> {
+        fs::create_dir_all(symbol_dir(root, symbol))?;
+        let data = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(partition_path(root, symbol, day, DATA_EXTENSION))?;
+        let index = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(partition_path(root, symbol, day, INDEX_EXTENSION))?;
+        let offset = data.metadata()?.len();
+        Ok(Partition { data, index, offset, pending: Vec::new() })
+    }
+
+    // data is written before its index record, so a crash never leaves
+    // an index entry pointing at a half-written block
+    fn flush(&mut self) -> io::Result<()> {
+        if self.pending.is_empty() {
+            return Ok(());
+        }
+        let block = encode_block(&self.pending);
+        let entry = BlockIndex {
+            min_timestamp: self.pending.iter().map(|t| t.timestamp).min().unwrap_or_default(),
+            max_timestamp: self.pending.iter().map(|t| t.timestamp).max().unwrap_or_default(),
+            offset: self.offset,
+            count: self.pending.len() as u32,
+            length: block.len() as u32,
+        };
+        self.data.write_all(&block)?;
+        self.data.flush()?;
+        let mut record = Vec::with_capacity(BlockIndex::SIZE);
+        entry.write_to(&mut record)?;
+        self.index.write_all(&record)?;
+        self.index.flush()?;
+
+        self.offset += block.len() as u64;
+        self.pending.clear();
+        Ok(())
+    }
+}
+
+// append-only writer partitioning ticks by symbol and trading day.
+// ticks are buffered per partition and written as one compressed block every `block_size` ticks.
+pub struct TickWriter {
+    root: PathBuf,
+    block_size: usize,
+    partitions: HashMap<(Symbol, NaiveDate), Partition>,
+}
+
+impl TickWriter {
+    pub fn new(root: impl AsRef<Path>, block_size: usize) -> io::Result<Self> {
+        fs::create_dir_all(root.as_ref())?;
+        Ok(TickWriter {
+            root: root.as_ref().to_path_buf(),
+            block_size: block_size.max(1),
+            partitions: HashMap::new(),
+        })
+    }
+
+    pub fn append(&mut self, tick: &Tick) -> io::Result<()> {
+        let key = (tick.symbol, trading_day(tick.timestamp));
+        let partition = match self.partitions.get_mut(&key) {
+            Some(partition) => partition,
+            None => {
+                // a new day for this symbol: close out the previous day's file
+                self.flush_symbol(&tick.symbol)?;
+                self.partitions.retain(|(symbol, _), _| *symbol != tick.symbol);
+                let partition = Partition::open(&self.root, &key.0, key.1)?;
+                self.partitions.entry(key).or_insert(partition)
+            }
+        };
+        partition.pending.push(*tick);
+        if partition.pending.len() >= self.block_size {
+            partition.flush()?;
+        }
+        Ok(())
+    }
+
+    fn flush_symbol(&mut self, symbol: &Symbol) -> io::Result<()> {
+        for ((partition_symbol, _), partition) in self.partitions.iter_mut() {
+            if partition_symbol == symbol {
+                partition.flush()?;
+            }
+        }
+        Ok(())
+    }
+
+    // writes every partially filled block
+    pub fn flush(&mut self) -> io::Result<()> {
+        for partition in self.partitions.values_mut() {
+            partition.flush()?;
+        }
+        debug!("Tick store flushed {} partitions", self.partitions.len());
+        Ok(())
+    }
+}
+
+impl Drop for TickWriter {
+    fn drop(&mut self) {
+        if let Err(e) = self.flush() {
+            error!("Failed to flush tick store: {}", e);
+        }
+    }
+}
@@ -0,0 +1,387 @@
+use super::{Fill, Order, OrderReject, OrderSide, OrderStatus, OrderUpdate, RequestKind, Strategy, StrategyContext};
+use crate::market_data::order_book::OrderBook;
+use crate::market_data::parser::Tick;
+use crate::market_data::symbol::Symbol;
+use log::{debug, info};
+use serde::Deserialize;
+
+const NANOS_PER_SEC: f64 = 1_000_000_000.0;
+
+// `[strategy.avellaneda_stoikov]`
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct MarketMakerParams {
+    pub symbol: String,
+    #[serde(default = "default_quote_size")]
+    pub quote_size: u64,
+    // no bid is quoted at or above +max_inventory, no ask at or below -max_inventory
+    pub max_inventory: i32,
+    // gamma: how strongly inventory skews the quotes and widens the spread
+    #[serde(default = "default_risk_aversion")]
+    pub risk_aversion: f64,
+    // k: decay of the fill probability with distance from the mid
+    #[serde(default = "default_order_arrival_intensity")]
+    pub order_arrival_intensity: f64,
+    // T - t, kept constant (rolling horizon)
+    #[serde(default = "default_horizon_secs")]
+    pub horizon_secs: f64,
+    // span of the EWMA volatility estimate, also the number of samples needed before quoting
+    #[serde(default = "default_volatility_window")]
+    pub volatility_window: usize,
+    // a working quote is amended only when its target moves at least this far
+    #[serde(default = "default_refresh_threshold")]
+    pub refresh_threshold: f64,
+}
+
+fn default_quote_size() -> u64 {
+    1
+}
+
+fn default_risk_aversion() -> f64 {
+    0.1
+}
+
+fn default_order_arrival_intensity() -> f64 {
+    1.5
+}
+
+fn default_horizon_secs() -> f64 {
+    60.0
+}
+
+fn default_volatility_window() -> usize {
+    100
+}
+
+fn default_refresh_threshold() -> f64 {
+    0.01
+}
+
+// EWMA of squared price changes per second of event time
+struct EwmaVolatility {
+    alpha: f64,
+    warmup: usize,
+    samples: usize,
+    last: Option<(f64, u64)>,
+    variance: f64,
+}
+
+impl EwmaVolatility {
+    fn new(span: usize) -> Self {
+        EwmaVolatility {
+            alpha: 2.0 / (span as f64 + 1.0),
+            warmup: span,
+            samples: 0,
+            last: None,
+            variance: 0.0,
+        }
+    }
+
+    fn update(&mut self, price: f64, timestamp: u64) {
+        let Some((last_price, last_timestamp)) = self.last else {
+            self.last = Some((price, timestamp));
+            return;
+        };
+        // moves within the same timestamp are measured against the next distinct one
+        if timestamp <= last_timestamp {
+            return;
+        }
+        let elapsed = (timestamp - last_timestamp) as f64 / NANOS_PER_SEC;
+        let rate = (price - last_price).powi(2) / elapsed;
+        self.variance = if self.samples == 0 { rate } else { self.alpha * rate + (1.0 - self.alpha) * self.variance };
+        self.samples += 1;
+        self.last = Some((price, timestamp));
+    }
+
+    // price variance per second once warmed up
+    fn variance(&self) -> Option<f64> {
+        (self.samples >= self.warmup).then_some(self.variance)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct Quote {
+    client_id: u64,
+    price: f64,
+}
+
+// Avellaneda-Stoikov market maker: quotes both sides around a reservation price that is
+// shifted against the current inventory, with a spread from volatility and order arrival intensity
+pub struct AvellanedaStoikovStrategy {
+    params: MarketMakerParams,
+    symbol: Symbol,
+    volatility: EwmaVolatility,
+    bid: Option<Quote>,
+    ask: Option<Quote>,
+}
+
+impl AvellanedaStoikovStrategy {
+    pub fn from_params(params: MarketMakerParams) -> Result<Self, Box<dyn std::error::Error>> {
+        let positive = |value: f64| value.is_finite() && value > 0.0;
+        if !positive(params.risk_aversion) || !positive(params.order_arrival_intensity) || !positive(params.horizon_secs) {
+            return Err("risk_aversion, order_arrival_intensity and horizon_secs must be positive".into());
+        }
+        if params.quote_size == 0 || params.max_inventory <= 0 {
+            return Err("quote_size and max_inventory must be greater than zero".into());
+        }
+        if params.volatility_window < 2 {
+            return Err("volatility_window must be at least 2".into());
+        }
+        if !(params.refresh_threshold.is_finite() && params.refresh_threshold >= 0.0) {
+            return Err("refresh_threshold must not be negative".into());
+        }
+        Ok(AvellanedaStoikovStrategy {
+            symbol: Symbol::new(&params.symbol),
+            volatility: EwmaVolatility::new(params.volatility_window),
+            params,
+            bid: None,
+            ask: None,
+        })
+    }
+
+    /// (bid, ask) for a mid price and inventory, `None` until the volatility estimate is warmed up
+    pub fn quotes(&self, mid: f64, inventory: i32) -> Option<(f64, f64)> {
+        let variance = self.volatility.variance()?;
+        let gamma = self.params.risk_aversion;
+        let horizon = self.params.horizon_secs;
+        let reservation = mid - inventory as f64 * gamma * variance * horizon;
+        let spread = gamma * variance * horizon + (2.0 / gamma) * (1.0 + gamma / self.params.order_arrival_intensity).ln();
+        Some((reservation - spread / 2.0, reservation + spread / 2.0))
+    }
+
+    fn requote(&mut self, mid: f64, ctx: &mut StrategyContext) {
+        let inventory = ctx.position(&self.params.symbol);
+        let Some((bid, ask)) = self.quotes(mid, inventory) else {
+            return;
+        };
+        debug!("{} mid {:.4} inventory {} quotes {:.4} / {:.4}", self.params.symbol, mid, inventory, bid, ask);
+        let bid = (inventory < self.params.max_inventory).then_some(bid);
+        let ask = (inventory > -self.params.max_inventory).then_some(ask);
+        self.bid = self.update_side(self.bid, bid, OrderSide::Buy, ctx);
+        self.ask = self.update_side(self.ask, ask, OrderSide::Sell, ctx);
+    }
+
+    fn update_side(
+        &self,
+        working: Option<Quote>,
+        target: Option<f64>,
+        side: OrderSide,
+        ctx: &mut StrategyContext,
+    ) -> Option<Quote> {
+        match (working, target) {
+            (None, None) => None,
+            (Some(quote), None) => {
+                info!("Pulling {} quote for {}: inventory limit", side, self.params.symbol);
+                ctx.cancel(quote.client_id);
+                None
+            }
+            (None, Some(price)) => {
+                let client_id = ctx.submit(Order {
+                    symbol: self.params.symbol.clone(),
+                    price,
+                    quantity: self.params.quote_size,
+                    side,
+                });
+                Some(Quote { client_id, price })
+            }
+            (Some(quote), Some(price)) if (price - quote.price).abs() >= self.params.refresh_threshold => {
+                ctx.amend(quote.client_id, price, self.params.quote_size);
+                Some(Quote { price, ..quote })
+            }
+            (Some(quote), Some(_)) => Some(quote),
+        }
+    }
+
+    // applies `f` to whichever side holds the order
+    fn with_quote(&mut self, client_id: u64, f: impl FnOnce(Quote) -> Option<Quote>) {
+        let side = [&mut self.bid, &mut self.ask]
+            .into_iter()
+            .find(|quote| quote.is_some_and(|working| working.client_id == client_id));
+        if let Some(quote) = side {
+            *quote = quote.and_then(f);
+        }
+    }
+}
+
+impl Strategy for AvellanedaStoikovStrategy {
+    fn symbols(&self) -> Vec<String> {
+        vec![self.params.symbol.clone()]
+    }
+
+    fn on_tick(&mut self, tick: &Tick, ctx: &mut StrategyContext) {
+        if tick.symbol != self.symbol {
+            return;
+        }
+        let mid = ctx.book(&self.params.symbol).and_then(OrderBook::get_mid_price).unwrap_or(tick.price);
+        self.volatility.update(mid, tick.timestamp);
+        self.requote(mid, ctx);
+    }
+
+    fn on_book_update(&mut self, symbol: &Symbol, ctx: &mut StrategyContext) {
+        if *symbol != self.symbol {
+            return;
+        }
+        if let Some(mid) = ctx.book(&self.params.symbol).and_then(OrderBook::get_mid_price) {
+            self.requote(mid, ctx);
+        }
+    }
+
+    fn on_fill(&mut self, fill: &Fill, _ctx: &mut StrategyContext) {
+        self.with_quote(fill.client_id, |_| None);
+    }
+
+    fn on_order_update(&mut self, update: &OrderUpdate, _ctx: &mut StrategyContext) {
+        let price = update.order.price;
+        match update.status {
+            OrderStatus::Cancelled => self.with_quote(update.client_id, |_| None),
+            // the venue price after tick rounding
+            OrderStatus::Accepted | OrderStatus::Amended => {
+                self.with_quote(update.client_id, |quote| Some(Quote { price, ..quote }))
+            }
+        }
+    }
+
+    fn on_order_reject(&mut self, reject: &OrderReject, _ctx: &mut StrategyContext) {
+        match (reject.request, reject.order.as_ref()) {
+            // the quote keeps working at its old price
+            (RequestKind::Amend, Some(order)) => {
+                let price = order.price;
+                self.with_quote(reject.client_id, |quote| Some(Quote { price, ..quote }))
+            }
+            (RequestKind::Cancel, Some(_)) => {}
+            _ => self.with_quote(reject.client_id, |_| None),
+        }
+    }
+
+    fn on_stop(&mut self, ctx: &mut StrategyContext) {
+        ctx.cancel_all(Some(&self.params.symbol));
+    }
+
+    // working quotes are kept and move to the new parameters on their next refresh
+    fn reconfigure(&mut self, params: &toml::Value) -> Result<(), Box<dyn std::error::Error>> {
+        let fresh = Self::from_params(params.clone().try_into()?)?;
+        if fresh.params.symbol != self.params.symbol {
+            return Err("changing the symbol needs a restart".into());
+        }
+        if fresh.params.volatility_window != self.params.volatility_window {
+            self.volatility = fresh.volatility;
+        }
+        self.params = fresh.params;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::strategy::context::ContextHarness;
+    use crate::strategy::StrategyAction;
+
+    const SEC: u64 = 1_000_000_000;
+
+    fn strategy() -> AvellanedaStoikovStrategy {
+        AvellanedaStoikovStrategy::from_params(MarketMakerParams {
+            symbol: "AAPL".to_string(),
+            quote_size: 2,
+            max_inventory: 5,
+            risk_aversion: 0.1,
+            order_arrival_intensity: 1.5,
+            horizon_secs: 10.0,
+            volatility_window: 2,
+            refresh_threshold: 0.02,
+        })
+        .unwrap()
+    }
+
+    fn tick(price: f64, timestamp: u64) -> Tick {
+        Tick { symbol: Symbol::new("AAPL"), price, volume: 1, timestamp, ..Default::default() }
+    }
+
+    // runs one tick through the strategy with the given inventory and returns its actions
+    fn on_tick(strategy: &mut AvellanedaStoikovStrategy, tick: &Tick, inventory: i32) -> Vec<StrategyAction> {
+        let mut harness = ContextHarness::default();
+        harness.fill("AAPL", inventory);
+        let mut ctx = harness.ctx(tick.timestamp);
+        strategy.on_tick(tick, &mut ctx);
+        ctx.into_actions()
+    }
+
+    #[test]
+    fn test_reservation_price_and_spread() {
+        let mut strategy = strategy();
+        strategy.volatility.update(100.0, 0);
+        assert_eq!(strategy.quotes(100.0, 0), None);
+        strategy.volatility.update(100.2, SEC);
+        strategy.volatility.update(100.0, 2 * SEC);
+        let variance = strategy.volatility.variance().unwrap();
+        assert!((variance - 0.04).abs() < 1e-9);
+
+        let (bid, ask) = strategy.quotes(100.0, 0).unwrap();
+        let spread = 0.1 * 0.04 * 10.0 + 20.0 * (1.0 + 0.1 / 1.5f64).ln();
+        assert!((ask - bid - spread).abs() < 1e-9);
+        assert!(((bid + ask) / 2.0 - 100.0).abs() < 1e-9);
+
+        // long inventory shifts both quotes down by q * gamma * sigma^2 * T
+        let (long_bid, long_ask) = strategy.quotes(100.0, 3).unwrap();
+        assert!((bid - long_bid - 3.0 * 0.1 * 0.04 * 10.0).abs() < 1e-9);
+        assert!(long_ask < ask);
+    }
+
+    #[test]
+    fn test_quote_lifecycle() {
+        let mut strategy = strategy();
+        for (i, price) in [100.0, 100.2, 100.0].into_iter().enumerate() {
+            let actions = on_tick(&mut strategy, &tick(price, i as u64 * SEC), 0);
+            assert_eq!(actions.len(), if i < 2 { 0 } else { 2 });
+        }
+        let (bid, ask) = (strategy.bid.unwrap(), strategy.ask.unwrap());
+
+        // falling volatility narrows the spread by less than the refresh threshold
+        assert!(on_tick(&mut strategy, &tick(100.0, 3 * SEC), 0).is_empty());
+
+        // at the inventory limit the bid is pulled and the ask is amended lower
+        let actions = on_tick(&mut strategy, &tick(100.0, 4 * SEC), 5);
+        assert_eq!(actions[0], StrategyAction::Cancel { client_id: bid.client_id });
+        assert!(matches!(actions[1], StrategyAction::Amend { client_id, price, quantity: 2 } if client_id == ask.client_id && price < ask.price));
+        assert_eq!(strategy.bid, None);
+
+        // a fill frees the side for a new quote
+        let harness = ContextHarness::default();
+        let mut ctx = harness.ctx(0);
+        strategy.on_fill(
+            &Fill { client_id: ask.client_id, symbol: "AAPL".to_string(), side: OrderSide::Sell, price: ask.price, quantity: 2, timestamp: 0 },
+            &mut ctx,
+        );
+        assert_eq!(strategy.ask, None);
+        strategy.on_stop(&mut ctx);
+        assert_eq!(ctx.into_actions(), vec![StrategyAction::CancelAll { symbol: Some("AAPL".to_string()) }]);
+    }
+
+    #[test]
+    fn test_reconfigure_keeps_working_quotes() {
+        let mut strategy = strategy();
+        for (i, price) in [100.0, 100.2, 100.0].into_iter().enumerate() {
+            on_tick(&mut strategy, &tick(price, i as u64 * SEC), 0);
+        }
+        let (bid, ask) = (strategy.bid.unwrap(), strategy.ask.unwrap());
+        let table = |quote_size: u64, risk_aversion: f64| -> toml::Value {
+            let content = format!(
+                "symbol = \"AAPL\"\nquote_size = {}\nmax_inventory = 5\nrisk_aversion = {}\norder_arrival_intensity = 1.5\n\
+                 horizon_secs = 10.0\nvolatility_window = 2\nrefresh_threshold = 0.02",
+                quote_size, risk_aversion
+            );
+            toml::from_str(&content).unwrap()
+        };
+
+        // a bad table changes nothing
+        assert!(strategy.reconfigure(&table(3, -1.0)).is_err());
+        assert_eq!(strategy.params.quote_size, 2);
+
+        // the quotes are amended to the new size in place instead of being sent again
+        strategy.reconfigure(&table(3, 0.1)).unwrap();
+        assert!(on_tick(&mut strategy, &tick(100.0, 3 * SEC), 0).is_empty());
+        let actions = on_tick(&mut strategy, &tick(100.0, 4 * SEC), 5);
+        assert_eq!(actions[0], StrategyAction::Cancel { client_id: bid.client_id });
+        assert!(matches!(actions[1], StrategyAction::Amend { client_id, quantity: 3, .. } if client_id == ask.client_id));
+    }
+}
@@ -0,0 +1,438 @@
+use super::{Order, OrderReject, OrderSide, RequestKind, Strategy, StrategyContext};
+use crate::market_data::order_book::OrderBook;
+use crate::market_data::symbol::Symbol;
+use log::{debug, info, warn};
+use serde::Deserialize;
+use std::collections::VecDeque;
+
+const NANOS_PER_MS: u64 = 1_000_000;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum EntryStyle {
+    // join the touch on our side and wait to be filled
+    Passive,
+    // cross the spread
+    Aggressive,
+}
+
+// `[strategy.book_imbalance]`
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct BookImbalanceParams {
+    pub symbol: String,
+    #[serde(default = "default_quantity")]
+    pub quantity: u64,
+    // price levels per side in the depth imbalance
+    #[serde(default = "default_depth_levels")]
+    pub depth_levels: usize,
+    // (bid depth - ask depth) / total depth, in [-1, 1]
+    #[serde(default = "default_imbalance_threshold")]
+    pub imbalance_threshold: f64,
+    // microprice distance from the mid in basis points of the mid
+    #[serde(default = "default_microprice_threshold_bps")]
+    pub microprice_threshold_bps: f64,
+    // book updates over which queue changes at the touch are summed
+    #[serde(default = "default_queue_window")]
+    pub queue_window: usize,
+    // summed touch queue flow relative to the current touch depth
+    #[serde(default = "default_queue_threshold")]
+    pub queue_threshold: f64,
+    // signals (out of imbalance, microprice and queue flow) that must agree before entering
+    #[serde(default = "default_min_signals")]
+    pub min_signals: usize,
+    // a position is closed this long after entry
+    #[serde(default = "default_holding_ms")]
+    pub holding_ms: u64,
+    #[serde(default = "default_entry")]
+    pub entry: EntryStyle,
+}
+
+fn default_quantity() -> u64 {
+    1
+}
+
+fn default_depth_levels() -> usize {
+    5
+}
+
+fn default_imbalance_threshold() -> f64 {
+    0.3
+}
+
+fn default_microprice_threshold_bps() -> f64 {
+    0.5
+}
+
+fn default_queue_window() -> usize {
+    10
+}
+
+fn default_queue_threshold() -> f64 {
+    0.5
+}
+
+fn default_min_signals() -> usize {
+    2
+}
+
+fn default_holding_ms() -> u64 {
+    500
+}
+
+fn default_entry() -> EntryStyle {
+    EntryStyle::Aggressive
+}
+
+// best (price, quantity) on each side
+type Touch = ((f64, u64), (f64, u64));
+
+// short-horizon features of the book; positive values point up
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct BookSignals {
+    imbalance: f64,
+    microprice_bps: f64,
+    queue_flow: f64,
+}
+
+// order flow at the touch between two snapshots: queue added on the bid minus queue added on the ask,
+// where a better price counts its whole queue as added and a worse one the whole old queue as removed
+fn touch_flow(previous: Touch, current: Touch) -> f64 {
+    let (((prev_bid, prev_bid_qty), (prev_ask, prev_ask_qty)), ((bid, bid_qty), (ask, ask_qty))) = (previous, current);
+    let bid_flow = if bid > prev_bid {
+        bid_qty as f64
+    } else if bid == prev_bid {
+        bid_qty as f64 - prev_bid_qty as f64
+    } else {
+        -(prev_bid_qty as f64)
+    };
+    let ask_flow = if ask < prev_ask {
+        ask_qty as f64
+    } else if ask == prev_ask {
+        ask_qty as f64 - prev_ask_qty as f64
+    } else {
+        -(prev_ask_qty as f64)
+    };
+    bid_flow - ask_flow
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Trade {
+    Flat,
+    Open { side: OrderSide, entry_id: u64, entered_at: u64 },
+}
+
+// trades the next few moves of the mid from depth imbalance, microprice and queue changes at the touch
+pub struct BookImbalanceStrategy {
+    params: BookImbalanceParams,
+    symbol: Symbol,
+    last_touch: Option<Touch>,
+    flows: VecDeque<f64>,
+    trade: Trade,
+}
+
+impl BookImbalanceStrategy {
+    pub fn from_params(params: BookImbalanceParams) -> Result<Self, Box<dyn std::error::Error>> {
+        if params.quantity == 0 || params.depth_levels == 0 || params.queue_window == 0 || params.holding_ms == 0 {
+            return Err("quantity, depth_levels, queue_window and holding_ms must be positive".into());
+        }
+        if !(0.0 < params.imbalance_threshold && params.imbalance_threshold < 1.0) {
+            return Err("imbalance_threshold must be in (0, 1)".into());
+        }
+        let positive = |value: f64| value.is_finite() && value > 0.0;
+        if !positive(params.microprice_threshold_bps) || !positive(params.queue_threshold) {
+            return Err("microprice_threshold_bps and queue_threshold must be positive".into());
+        }
+        if !(1..=3).contains(&params.min_signals) {
+            return Err("min_signals must be between 1 and 3".into());
+        }
+        Ok(BookImbalanceStrategy {
+            symbol: Symbol::new(&params.symbol),
+            last_touch: None,
+            flows: VecDeque::with_capacity(params.queue_window + 1),
+            trade: Trade::Flat,
+            params,
+        })
+    }
+
+    // updates the queue flow with the new touch and computes the features, `None` for a one-sided book
+    fn signals(&mut self, book: &OrderBook) -> Option<BookSignals> {
+        let touch = (book.bid_levels().next()?, book.ask_levels().next()?);
+        if let Some(previous) = self.last_touch.replace(touch) {
+            if previous != touch {
+                self.flows.push_back(touch_flow(previous, touch));
+                if self.flows.len() > self.params.queue_window {
+                    self.flows.pop_front();
+                }
+            }
+        }
+
+        let ((bid, bid_qty), (ask, ask_qty)) = touch;
+        let touch_depth = (bid_qty + ask_qty) as f64;
+        if touch_depth == 0.0 {
+            return None;
+        }
+        let bid_depth: u64 = book.bid_levels().take(self.params.depth_levels).map(|(_, qty)| qty).sum();
+        let ask_depth: u64 = book.ask_levels().take(self.params.depth_levels).map(|(_, qty)| qty).sum();
+        let mid = (bid + ask) / 2.0;
+        let microprice = (bid * ask_qty as f64 + ask * bid_qty as f64) / touch_depth;
+        Some(BookSignals {
+            imbalance: (bid_depth as f64 - ask_depth as f64) / (bid_depth + ask_depth) as f64,
+            microprice_bps: (microprice - mid) / mid * 10_000.0,
+            queue_flow: self.flows.iter().sum::<f64>() / touch_depth,
+        })
+    }
+
+    // +1 buy, -1 sell, 0 when fewer than `min_signals` features agree
+    fn direction(&self, signals: &BookSignals) -> i32 {
+        let votes = [
+            (signals.imbalance, self.params.imbalance_threshold),
+            (signals.microprice_bps, self.params.microprice_threshold_bps),
+            (signals.queue_flow, self.params.queue_threshold),
+        ];
+        let up = votes.iter().filter(|(value, threshold)| *value >= *threshold).count();
+        let down = votes.iter().filter(|(value, threshold)| *value <= -*threshold).count();
+        if up >= self.params.min_signals && down == 0 {
+            1
+        } else if down >= self.params.min_signals && up == 0 {
+            -1
+        } else {
+            0
+        }
+    }
+
+    fn enter(&mut self, side: OrderSide, book: &OrderBook, ctx: &mut StrategyContext) {
+        let price = match (self.params.entry, side) {
+            (EntryStyle::Passive, OrderSide::Buy) | (EntryStyle::Aggressive, OrderSide::Sell) => book.get_best_bid(),
+            (EntryStyle::Passive, OrderSide::Sell) | (EntryStyle::Aggressive, OrderSide::Buy) => book.get_best_ask(),
+        };
+        let Some(price) = price else {
+            return;
+        };
+        let entry_id = ctx.submit(Order { symbol: self.params.symbol.clone(), price, quantity: self.params.quantity, side });
+        self.trade = Trade::Open { side, entry_id, entered_at: ctx.now() };
+    }
+
+    // pulls an unfilled entry and crosses the spread to close whatever was filled
+    fn exit(&mut self, ctx: &mut StrategyContext) {
+        let Trade::Open { entry_id, .. } = self.trade else {
+            return;
+        };
+        if ctx.open_orders().get(entry_id).is_some() {
+            ctx.cancel(entry_id);
+        }
+        let position = ctx.position(&self.params.symbol);
+        let side = if position > 0 { OrderSide::Sell } else { OrderSide::Buy };
+        let price = ctx.book(&self.params.symbol).and_then(|book| match side {
+            OrderSide::Sell => book.get_best_bid(),
+            OrderSide::Buy => book.get_best_ask(),
+        });
+        if let (Some(price), true) = (price, position != 0) {
+            ctx.submit(Order {
+                symbol: self.params.symbol.clone(),
+                price,
+                quantity: u64::from(position.unsigned_abs()),
+                side,
+            });
+        }
+        self.trade = Trade::Flat;
+    }
+
+    fn horizon_elapsed(&self, now: u64) -> bool {
+        matches!(self.trade, Trade::Open { entered_at, .. } if now.saturating_sub(entered_at) >= self.params.holding_ms * NANOS_PER_MS)
+    }
+}
+
+impl Strategy for BookImbalanceStrategy {
+    fn symbols(&self) -> Vec<String> {
+        vec![self.params.symbol.clone()]
+    }
+
+    fn on_book_update(&mut self, symbol: &Symbol, ctx: &mut StrategyContext) {
+        if *symbol != self.symbol {
+            return;
+        }
+        let Some(book) = ctx.book(&self.params.symbol) else {
+            return;
+        };
+        let Some(signals) = self.signals(book) else {
+            return;
+        };
+        let direction = self.direction(&signals);
+        debug!("{} book signals {:?}, direction {}", self.params.symbol, signals, direction);
+
+        match self.trade {
+            Trade::Open { side, .. } => {
+                let reversed = (side == OrderSide::Buy && direction < 0) || (side == OrderSide::Sell && direction > 0);
+                if reversed || self.horizon_elapsed(ctx.now()) {
+                    info!("Closing {} book imbalance trade{}", self.params.symbol, if reversed { " on a reversed signal" } else { "" });
+                    self.exit(ctx);
+                }
+            }
+            // the previous exit has not completed yet
+            Trade::Flat if ctx.position(&self.params.symbol) != 0 => {}
+            Trade::Flat if direction != 0 => {
+                let side = if direction > 0 { OrderSide::Buy } else { OrderSide::Sell };
+                info!("{} {} on book signals {:?}", side, self.params.symbol, signals);
+                self.enter(side, book, ctx);
+            }
+            Trade::Flat => {}
+        }
+    }
+
+    fn on_order_reject(&mut self, reject: &OrderReject, _ctx: &mut StrategyContext) {
+        if let Trade::Open { entry_id, .. } = self.trade {
+            if reject.request == RequestKind::Place && reject.client_id == entry_id {
+                warn!("Book imbalance entry {} rejected: {}", entry_id, reject.reason);
+                self.trade = Trade::Flat;
+            }
+        }
+    }
+
+    fn on_timer(&mut self, now: u64, ctx: &mut StrategyContext) {
+        if self.horizon_elapsed(now) {
+            info!("Closing {} book imbalance trade at the holding horizon", self.params.symbol);
+            self.exit(ctx);
+        }
+    }
+
+    fn on_stop(&mut self, ctx: &mut StrategyContext) {
+        self.exit(ctx);
+    }
+
+    // an open trade is kept and closed after the new holding time
+    fn reconfigure(&mut self, params: &toml::Value) -> Result<(), Box<dyn std::error::Error>> {
+        let fresh = Self::from_params(params.clone().try_into()?)?;
+        if fresh.params.symbol != self.params.symbol {
+            return Err("changing the symbol needs a restart".into());
+        }
+        self.params = fresh.params;
+        while self.flows.len() > self.params.queue_window {
+            self.flows.pop_front();
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::market_data::consolidated_book::ConsolidatedBook;
+    use crate::market_data::order_book::OrderSide as BookSide;
+    use crate::strategy::context::ContextHarness;
+    use crate::strategy::StrategyAction;
+
+    fn params(entry: EntryStyle) -> BookImbalanceParams {
+        BookImbalanceParams {
+            symbol: "AAPL".to_string(),
+            quantity: 2,
+            depth_levels: 3,
+            imbalance_threshold: 0.3,
+            microprice_threshold_bps: 0.5,
+            queue_window: 5,
+            queue_threshold: 0.5,
+            min_signals: 2,
+            holding_ms: 500,
+            entry,
+        }
+    }
+
+    // `levels` of (price, quantity) per side of AAPL
+    fn book(bids: &[(f64, u64)], asks: &[(f64, u64)]) -> ConsolidatedBook {
+        let mut book = ConsolidatedBook::new();
+        let levels = bids.iter().map(|&(price, quantity)| (price, quantity, BookSide::Bid));
+        for (price, quantity, side) in levels.chain(asks.iter().map(|&(price, quantity)| (price, quantity, BookSide::Ask))) {
+            book.update_level(Symbol::new("AAPL"), "XNAS", side, price, quantity);
+        }
+        book
+    }
+
+    fn places(actions: Vec<StrategyAction>) -> Vec<Order> {
+        actions
+            .into_iter()
+            .map(|action| match action {
+                StrategyAction::Place { order, .. } => order,
+                other => panic!("unexpected action {:?}", other),
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_book_signals() {
+        let mut strategy = BookImbalanceStrategy::from_params(params(EntryStyle::Aggressive)).unwrap();
+        let aapl = Symbol::new("AAPL");
+        let balanced = book(&[(99.99, 100), (99.98, 100)], &[(100.01, 100), (100.02, 100)]);
+        let signals = strategy.signals(balanced.book(&aapl).unwrap()).unwrap();
+        assert_eq!(signals, BookSignals { imbalance: 0.0, microprice_bps: 0.0, queue_flow: 0.0 });
+
+        // the bid queue grows and the ask is lifted to a worse price
+        let heavy = book(&[(99.99, 300), (99.98, 100)], &[(100.02, 100)]);
+        let signals = strategy.signals(heavy.book(&aapl).unwrap()).unwrap();
+        assert!((signals.imbalance - 0.6).abs() < 1e-9);
+        assert!(signals.microprice_bps > 0.5);
+        // bid +200, ask level 100 gone: flow 300 over a touch depth of 400
+        assert!((signals.queue_flow - 0.75).abs() < 1e-9);
+        assert_eq!(strategy.direction(&signals), 1);
+        assert!(strategy.signals(book(&[(99.99, 300)], &[]).book(&aapl).unwrap()).is_none());
+    }
+
+    #[test]
+    fn test_aggressive_entry_and_horizon_exit() {
+        let mut strategy = BookImbalanceStrategy::from_params(params(EntryStyle::Aggressive)).unwrap();
+        let mut harness = ContextHarness::default();
+        let symbol = Symbol::new("AAPL");
+
+        let balanced = book(&[(99.99, 100), (99.98, 100)], &[(100.01, 100), (100.02, 100)]);
+        let mut ctx = harness.ctx_with_books(0, &balanced);
+        strategy.on_book_update(&symbol, &mut ctx);
+        assert!(ctx.into_actions().is_empty());
+
+        // sellers thin out: lift the offer
+        let heavy = book(&[(99.99, 300), (99.98, 100)], &[(100.01, 20), (100.02, 100)]);
+        let mut ctx = harness.ctx_with_books(NANOS_PER_MS, &heavy);
+        strategy.on_book_update(&symbol, &mut ctx);
+        let entry = places(ctx.into_actions());
+        assert_eq!(entry.len(), 1);
+        assert_eq!((entry[0].side, entry[0].price, entry[0].quantity), (OrderSide::Buy, 100.01, 2));
+        harness.risk.update_position(&entry[0]);
+
+        let mut ctx = harness.ctx_with_books(100 * NANOS_PER_MS, &heavy);
+        strategy.on_timer(100 * NANOS_PER_MS, &mut ctx);
+        assert!(ctx.into_actions().is_empty());
+
+        // at the horizon the position is sold into the bid
+        let mut ctx = harness.ctx_with_books(501 * NANOS_PER_MS, &heavy);
+        strategy.on_timer(501 * NANOS_PER_MS, &mut ctx);
+        let exit = places(ctx.into_actions());
+        assert_eq!((exit[0].side, exit[0].price, exit[0].quantity), (OrderSide::Sell, 99.99, 2));
+        assert_eq!(strategy.trade, Trade::Flat);
+    }
+
+    #[test]
+    fn test_passive_entry_is_pulled_unfilled() {
+        let mut strategy = BookImbalanceStrategy::from_params(params(EntryStyle::Passive)).unwrap();
+        let mut harness = ContextHarness::default();
+        let symbol = Symbol::new("AAPL");
+
+        let balanced = book(&[(99.99, 100)], &[(100.01, 100)]);
+        let mut ctx = harness.ctx_with_books(0, &balanced);
+        strategy.on_book_update(&symbol, &mut ctx);
+        // buyers pull their bids: join the offer
+        let light = book(&[(99.99, 10)], &[(100.01, 300)]);
+        let mut ctx = harness.ctx_with_books(NANOS_PER_MS, &light);
+        strategy.on_book_update(&symbol, &mut ctx);
+        let actions = ctx.into_actions();
+        let StrategyAction::Place { client_id, order } = actions[0].clone() else { panic!("no entry") };
+        assert_eq!((order.side, order.price), (OrderSide::Sell, 100.01));
+        harness.open_orders.insert(client_id, order);
+
+        // never filled: only the working order is cancelled
+        let mut ctx = harness.ctx_with_books(600 * NANOS_PER_MS, &light);
+        strategy.on_timer(600 * NANOS_PER_MS, &mut ctx);
+        assert_eq!(ctx.into_actions(), vec![StrategyAction::Cancel { client_id }]);
+
+        let mut invalid = params(EntryStyle::Passive);
+        invalid.min_signals = 4;
+        assert!(BookImbalanceStrategy::from_params(invalid).is_err());
+    }
+}
@@ -0,0 +1,352 @@
+use super::risk_management::RiskManager;
+use super::routed::ExecutionRoute;
+use super::{Order, OrderSide};
+use crate::market_data::aggregator::MarketDataAggregator;
+use crate::market_data::consolidated_book::ConsolidatedBook;
+use crate::market_data::order_book::OrderBook;
+use crate::market_data::symbol::Symbol;
+use crate::market_data::trade_tape::{TradeFlow, TradeTape};
+use crate::order_execution::algo::AlgoParams;
+use crate::order_execution::emulation::EmulationParams;
+use crate::order_execution::open_orders::{OpenOrder, OpenOrders};
+use crate::order_execution::WorkingParent;
+use log::debug;
+use std::collections::HashSet;
+
+// execution of one of the strategy's orders
+#[derive(Debug, Clone, PartialEq)]
+pub struct Fill {
+    pub client_id: u64,
+    pub symbol: String,
+    pub side: OrderSide,
+    pub price: f64,
+    pub quantity: u64,
+    pub timestamp: u64,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum OrderStatus {
+    // acknowledged by the venue and working
+    Accepted,
+    // still working with the new price/quantity
+    Amended,
+    Cancelled,
+}
+
+// order request a strategy can make; cancels are always allowed, places and amends go through risk
+#[derive(Debug, Clone, PartialEq)]
+pub enum StrategyAction {
+    Place { client_id: u64, order: Order },
+    Cancel { client_id: u64 },
+    Amend { client_id: u64, price: f64, quantity: u64 },
+    // every open order, or only those in one symbol
+    CancelAll { symbol: Option<String> },
+    // parent order worked by an execution algo as child orders
+    Execute { client_id: u64, order: Order, algo: AlgoParams },
+    // iceberg and/or pegged parent order emulated with plain limit orders
+    Emulate { client_id: u64, order: Order, emulation: EmulationParams },
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RequestKind {
+    Place,
+    Cancel,
+    Amend,
+}
+
+// state change of an order that is neither a fill nor a reject
+#[derive(Debug, Clone, PartialEq)]
+pub struct OrderUpdate {
+    pub client_id: u64,
+    pub order: Order,
+    pub status: OrderStatus,
+}
+
+// request refused by the calendar, instrument checks, risk or the venue
+#[derive(Debug, Clone, PartialEq)]
+pub struct OrderReject {
+    pub client_id: u64,
+    pub request: RequestKind,
+    // order the request was for, `None` when the client id is not a working order
+    pub order: Option<Order>,
+    pub reason: String,
+}
+
+// what a strategy callback can see of the bot, and where it submits actions.
+// actions are collected and sent through risk and execution in order after the callback returns.
+pub struct StrategyContext<'a> {
+    now: u64,
+    risk: &'a RiskManager,
+    open_orders: &'a OpenOrders,
+    books: Option<&'a ConsolidatedBook>,
+    market_data: &'a MarketDataAggregator,
+    trades: Option<&'a TradeTape>,
+    // algo and emulated orders being worked
+    parents: Vec<WorkingParent>,
+    // submitted orders go to this execution algo instead of the venue
+    route: Option<ExecutionRoute>,
+    actions: Vec<StrategyAction>,
+    // position reported in one symbol instead of the risk manager's
+    position_override: Option<(String, i32)>,
+}
+
+impl<'a> StrategyContext<'a> {
+    pub fn new(
+        now: u64,
+        risk: &'a RiskManager,
+        open_orders: &'a OpenOrders,
+        books: Option<&'a ConsolidatedBook>,
+        market_data: &'a MarketDataAggregator,
+    ) -> Self {
+        StrategyContext {
+            now,
+            risk,
+            open_orders,
+            books,
+            market_data,
+            trades: None,
+            parents: Vec::new(),
+            route: None,
+            actions: Vec::new(),
+            position_override: None,
+        }
+    }
+
+    // time and sales the context reads trade flow from
+    pub fn with_trade_tape(self, trades: &'a TradeTape) -> Self {
+        StrategyContext { trades: Some(trades), ..self }
+    }
+
+    pub fn with_parents(self, parents: Vec<WorkingParent>) -> Self {
+        StrategyContext { parents, ..self }
+    }
+
+    // context with no actions yet for a strategy run by another strategy, which keeps its own orders and position in `symbol`
+    pub fn nested<'b>(&self, open_orders: &'b OpenOrders, symbol: &str, position: i32) -> StrategyContext<'b>
+    where
+        'a: 'b,
+    {
+        StrategyContext {
+            open_orders,
+            position_override: Some((symbol.to_string(), position)),
+            trades: self.trades,
+            route: self.route.clone(),
+            ..StrategyContext::new(self.now, self.risk, self.open_orders, self.books, self.market_data)
+        }
+    }
+
+    // event time of the callback in ns
+    pub fn now(&self) -> u64 {
+        self.now
+    }
+
+    pub fn position(&self, symbol: &str) -> i32 {
+        match &self.position_override {
+            Some((overridden, position)) if overridden == symbol => *position,
+            _ => self.risk.get_position(symbol),
+        }
+    }
+
+    pub fn open_orders(&self) -> &OpenOrders {
+        self.open_orders
+    }
+
+    /// what works towards the position in `symbol`, oldest first: orders at the venue and the unfilled part
+    /// of algo and emulated parents, whose children are left out
+    pub fn working_orders(&self, symbol: &str) -> Vec<OpenOrder> {
+        let children = self.parents.iter().filter_map(|parent| parent.child).collect::<HashSet<_>>();
+        let mut working = self
+            .open_orders
+            .for_symbol(symbol)
+            .filter(|open| !children.contains(&open.client_id))
+            .cloned()
+            .collect::<Vec<_>>();
+        working.extend(self.parents.iter().map(|parent| &parent.remainder).filter(|open| open.order.symbol == symbol).cloned());
+        working.sort_by_key(|open| open.client_id);
+        working
+    }
+
+    // algo or emulated order, which can be cancelled but not amended
+    pub fn is_parent(&self, client_id: u64) -> bool {
+        self.parents.iter().any(|parent| parent.remainder.client_id == client_id)
+    }
+
+    // book of one instrument, all venues merged
+    pub fn book(&self, symbol: &str) -> Option<&'a OrderBook> {
+        self.books?.book(&Symbol::new(symbol))
+    }
+
+    /// recent prints of one instrument with rolling buy/sell volume, imbalance and arrival intensity
+    pub fn trade_flow(&self, symbol: &str) -> Option<&'a TradeFlow> {
+        self.trades?.flow(&Symbol::new(symbol))
+    }
+
+    pub fn market_data(&self) -> &MarketDataAggregator {
+        self.market_data
+    }
+
+    /// queues an order and returns its client order id
+    pub fn submit(&mut self, order: Order) -> u64 {
+        match self.route.clone() {
+            Some(ExecutionRoute::Algo { algo, limit }) => {
+                let algo = AlgoParams { limit_price: limit.then_some(order.price), ..algo };
+                return self.execute(order, algo);
+            }
+            Some(ExecutionRoute::Emulate(emulation)) => return self.emulate(order, emulation),
+            None => {}
+        }
+        let client_id = self.open_orders.allocate_id();
+        debug!("Strategy submitted order {}: {:?}", client_id, order);
+        self.actions.push(StrategyAction::Place { client_id, order });
+        client_id
+    }
+
+    pub fn cancel(&mut self, client_id: u64) {
+        self.actions.push(StrategyAction::Cancel { client_id });
+    }
+
+    /// changes price and quantity of a working order, keeping its client order id
+    pub fn amend(&mut self, client_id: u64, price: f64, quantity: u64) {
+        self.actions.push(StrategyAction::Amend { client_id, price, quantity });
+    }
+
+    /// hands the order to an execution algo; fills of its children are reported under the returned id
+    pub fn execute(&mut self, order: Order, algo: AlgoParams) -> u64 {
+        let client_id = self.open_orders.allocate_id();
+        debug!("Strategy submitted {:?} order {}: {:?}", algo.kind, client_id, order);
+        self.actions.push(StrategyAction::Execute { client_id, order, algo });
+        client_id
+    }
+
+    /// iceberg and pegged orders for venues without native support; `order.price` is the limit
+    pub fn emulate(&mut self, order: Order, emulation: EmulationParams) -> u64 {
+        let client_id = self.open_orders.allocate_id();
+        debug!("Strategy submitted emulated order {}: {:?} {:?}", client_id, order, emulation);
+        self.actions.push(StrategyAction::Emulate { client_id, order, emulation });
+        client_id
+    }
+
+    /// orders submitted from now on are handed to the route's execution algo or the order emulator
+    pub fn route(&mut self, route: ExecutionRoute) {
+        self.route = Some(route);
+    }
+
+    // cancels every order working when the action is processed, optionally only in one symbol
+    pub fn cancel_all(&mut self, symbol: Option<&str>) {
+        self.actions.push(StrategyAction::CancelAll { symbol: symbol.map(str::to_string) });
+    }
+
+    // actions taken during the callback, in order
+    pub fn into_actions(self) -> Vec<StrategyAction> {
+        self.actions
+    }
+}
+
+// what a context borrows, owned in one place so a test builds one in a line:
+// loose risk limits, flat positions, no open orders, no indicators
+#[cfg(test)]
+pub struct ContextHarness {
+    pub risk: RiskManager,
+    pub open_orders: OpenOrders,
+    pub market_data: MarketDataAggregator,
+}
+
+#[cfg(test)]
+impl Default for ContextHarness {
+    fn default() -> Self {
+        ContextHarness {
+            risk: RiskManager::new(1_000, 1e9, 0.0, 1e6),
+            open_orders: OpenOrders::new(),
+            market_data: MarketDataAggregator::new(1, None),
+        }
+    }
+}
+
+#[cfg(test)]
+impl ContextHarness {
+    pub fn ctx(&self, now: u64) -> StrategyContext<'_> {
+        StrategyContext::new(now, &self.risk, &self.open_orders, None, &self.market_data)
+    }
+
+    pub fn ctx_with_books<'a>(&'a self, now: u64, books: &'a ConsolidatedBook) -> StrategyContext<'a> {
+        StrategyContext::new(now, &self.risk, &self.open_orders, Some(books), &self.market_data)
+    }
+
+    // moves the position in `symbol` by `quantity`, negative to sell
+    pub fn fill(&mut self, symbol: &str, quantity: i32) {
+        let side = if quantity < 0 { OrderSide::Sell } else { OrderSide::Buy };
+        self.risk.update_position(&Order { symbol: symbol.to_string(), price: 1.0, quantity: quantity.unsigned_abs().into(), side });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::{IndicatorConfig, IndicatorKind};
+    use crate::market_data::parser::Tick;
+    use crate::strategy::Strategy;
+
+    struct Echo;
+
+    impl Strategy for Echo {
+        fn evaluate(&mut self, tick: &Tick) -> Option<Order> {
+            Some(Order { symbol: tick.symbol.to_string(), price: tick.price, quantity: 1, side: OrderSide::Buy })
+        }
+    }
+
+    #[test]
+    fn test_context_reads_and_submits() {
+        let mut harness = ContextHarness { market_data: MarketDataAggregator::new(2, None), ..Default::default() };
+        harness.fill("AAPL", -3);
+        harness
+            .market_data
+            .add_indicators_from_config(&[IndicatorConfig { name: "fast".to_string(), symbol: None, kind: IndicatorKind::Sma { period: 1 } }])
+            .unwrap();
+        let tick = Tick { symbol: "AAPL".into(), price: 101.0, volume: 5, timestamp: 7, ..Default::default() };
+        harness.market_data.update(&tick);
+        let order = Order { symbol: "AAPL".to_string(), price: 100.0, quantity: 3, side: OrderSide::Sell };
+
+        let mut ctx = harness.ctx(tick.timestamp);
+        assert_eq!((ctx.now(), ctx.position("AAPL"), ctx.position("MSFT")), (7, -3, 0));
+        assert_eq!(ctx.market_data().get_indicator_value("fast"), Some(101.0));
+        assert_eq!(ctx.market_data().get_sma(), Some(101.0));
+        assert!(ctx.book("AAPL").is_none() && ctx.open_orders().iter().next().is_none());
+
+        // the default on_tick submits whatever evaluate returns
+        Echo.on_tick(&tick, &mut ctx);
+        let second = ctx.submit(order.clone());
+        assert_eq!(
+            ctx.into_actions(),
+            vec![
+                StrategyAction::Place {
+                    client_id: 1,
+                    order: Order { symbol: "AAPL".to_string(), price: 101.0, quantity: 1, side: OrderSide::Buy },
+                },
+                StrategyAction::Place { client_id: second, order },
+            ]
+        );
+    }
+
+    #[test]
+    fn test_action_batch() {
+        let mut harness = ContextHarness::default();
+        let stale = harness.open_orders.allocate_id();
+        harness.open_orders.insert(stale, Order { symbol: "AAPL".to_string(), price: 99.0, quantity: 1, side: OrderSide::Buy });
+
+        // requote: pull the stale bid, quote both sides, then shrink the ask
+        let mut ctx = harness.ctx(0);
+        ctx.cancel(stale);
+        let bid = ctx.submit(Order { symbol: "AAPL".to_string(), price: 99.5, quantity: 2, side: OrderSide::Buy });
+        let ask = ctx.submit(Order { symbol: "AAPL".to_string(), price: 100.5, quantity: 2, side: OrderSide::Sell });
+        ctx.amend(ask, 100.4, 1);
+        ctx.cancel_all(Some("AAPL"));
+
+        let actions = ctx.into_actions();
+        assert_eq!((bid, ask), (2, 3));
+        assert_eq!(actions.len(), 5);
+        assert_eq!(actions[0], StrategyAction::Cancel { client_id: stale });
+        assert!(matches!(actions[2], StrategyAction::Place { client_id: 3, ref order } if order.side == OrderSide::Sell));
+        assert_eq!(actions[3], StrategyAction::Amend { client_id: ask, price: 100.4, quantity: 1 });
+        assert_eq!(actions[4], StrategyAction::CancelAll { symbol: Some("AAPL".to_string()) });
+    }
+}
@@ -0,0 +1,438 @@
+use super::target::translate;
+use super::{Fill, OrderReject, OrderSide, RequestKind, Strategy, StrategyAction, StrategyContext};
+use crate::market_data::bar::Bar;
+use crate::market_data::parser::Tick;
+use crate::market_data::symbol::Symbol;
+use crate::order_execution::open_orders::OpenOrders;
+use log::{debug, info, warn};
+use serde::Deserialize;
+
+// name of the `[strategy.ensemble]` table; built by the registry from the tables of its members
+pub const ENSEMBLE: &str = "ensemble";
+
+// orders members may submit while reacting to their own virtual fills in one callback
+const MAX_MEMBER_ORDERS: usize = 100;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Combine {
+    // sum of member positions times their weights
+    Weighted,
+    // `quantity` in the direction of the weighted majority, flat on a tie
+    Vote,
+    // position of the first member in the list that is not flat
+    Priority,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct MemberParams {
+    // registered strategy name
+    pub strategy: String,
+    #[serde(default = "default_weight")]
+    pub weight: f64,
+    // parameters of the member, `[strategy.<strategy>]` when left out
+    #[serde(default)]
+    pub params: Option<toml::Value>,
+}
+
+// `[strategy.ensemble]`
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct EnsembleParams {
+    pub symbol: String,
+    #[serde(default = "default_combine")]
+    pub combine: Combine,
+    // largest position of each member in `weighted` and `priority` mode, size of the net position in `vote` mode
+    #[serde(default = "default_quantity")]
+    pub quantity: u64,
+    pub members: Vec<MemberParams>,
+}
+
+fn default_weight() -> f64 {
+    1.0
+}
+
+fn default_combine() -> Combine {
+    Combine::Weighted
+}
+
+fn default_quantity() -> u64 {
+    1
+}
+
+struct Member {
+    name: String,
+    weight: f64,
+    strategy: Box<dyn Strategy>,
+    // what the member would hold if all its orders had filled
+    position: i32,
+    // never holds an order, members see none working
+    open_orders: OpenOrders,
+}
+
+// runs several strategies on one symbol and trades only the combined net position.
+// member orders are never sent: each one fills virtually at its own price, moving the member's position
+// (which is also what the member's context reports), so opposing members cancel out instead of crossing
+// the spread against each other. the combined position is a target reconciled with the real position and
+// working orders on every event, so rejected and unfilled orders are made up for.
+pub struct EnsembleStrategy {
+    symbol: String,
+    combine: Combine,
+    quantity: u64,
+    members: Vec<Member>,
+    // combined net position of the members
+    target: i64,
+    last_price: Option<f64>,
+}
+
+impl EnsembleStrategy {
+    pub fn new(params: EnsembleParams, strategies: Vec<Box<dyn Strategy>>) -> Result<Self, Box<dyn std::error::Error>> {
+        if params.members.is_empty() || params.members.len() != strategies.len() {
+            return Err("an ensemble needs a strategy for each of its members".into());
+        }
+        if params.quantity == 0 || params.quantity > i32::MAX as u64 {
+            return Err("quantity must be greater than zero and fit a position".into());
+        }
+        let mut members = Vec::new();
+        for (member, strategy) in params.members.into_iter().zip(strategies) {
+            if !(member.weight.is_finite() && member.weight >= 0.0) {
+                return Err(format!("weight of member {} must be finite and not negative", member.strategy).into());
+            }
+            if !strategy.symbols().contains(&params.symbol) {
+                return Err(format!("member {} does not trade {}", member.strategy, params.symbol).into());
+            }
+            members.push(Member { name: member.strategy, weight: member.weight, strategy, position: 0, open_orders: OpenOrders::new() });
+        }
+        Ok(EnsembleStrategy {
+            symbol: params.symbol,
+            combine: params.combine,
+            quantity: params.quantity,
+            members,
+            target: 0,
+            last_price: None,
+        })
+    }
+
+    // runs the callback on every member, then orders the change of the combined position
+    fn run<F>(&mut self, ctx: &mut StrategyContext, mut callback: F)
+    where
+        F: FnMut(&mut dyn Strategy, &mut StrategyContext),
+    {
+        // vote only counts the direction of each member
+        let limit = (self.combine != Combine::Vote).then_some(self.quantity as i32);
+        for member in self.members.iter_mut() {
+            let mut member_ctx = ctx.nested(&member.open_orders, &self.symbol, member.position);
+            callback(member.strategy.as_mut(), &mut member_ctx);
+            let actions = member_ctx.into_actions();
+            member.fill_virtually(&self.symbol, actions, limit, ctx);
+        }
+        self.rebalance(ctx);
+    }
+
+    fn combined(&self) -> i64 {
+        match self.combine {
+            Combine::Weighted => {
+                self.members.iter().map(|member| member.weight * member.position as f64).sum::<f64>().round() as i64
+            }
+            Combine::Vote => {
+                let votes: f64 = self.members.iter().map(|member| member.weight * member.position.signum() as f64).sum();
+                if votes > 0.0 {
+                    self.quantity as i64
+                } else if votes < 0.0 {
+                    -(self.quantity as i64)
+                } else {
+                    0
+                }
+            }
+            Combine::Priority => self.members.iter().map(|member| i64::from(member.position)).find(|&position| position != 0).unwrap_or(0),
+        }
+    }
+
+    fn rebalance(&mut self, ctx: &mut StrategyContext) {
+        let target = self.combined();
+        if target != self.target {
+            let contributions = self
+                .members
+                .iter()
+                .map(|member| format!("{} {:+} x {}", member.name, member.position, member.weight))
+                .collect::<Vec<_>>()
+                .join(", ");
+            info!("Ensemble {} target {} -> {} ({:?}: {})", self.symbol, self.target, target, self.combine, contributions);
+            self.target = target;
+        }
+        let Some(price) = self.last_price else {
+            return;
+        };
+        let target = target.clamp(i64::from(i32::MIN), i64::from(i32::MAX)) as i32;
+        translate(ctx, &self.symbol, target, price);
+    }
+}
+
+impl Member {
+    // fills the member's orders at their own price and passes the fills back, which may bring more orders.
+    // with a limit, orders fill only as far as the member's position stays within it and are rejected beyond
+    fn fill_virtually(&mut self, symbol: &str, actions: Vec<StrategyAction>, limit: Option<i32>, ctx: &StrategyContext) {
+        let mut queue = actions;
+        let mut filled = 0;
+        while !queue.is_empty() && filled < MAX_MEMBER_ORDERS {
+            for action in std::mem::take(&mut queue) {
+                let (client_id, order) = match action {
+                    StrategyAction::Place { client_id, order }
+                    | StrategyAction::Execute { client_id, order, .. }
+                    | StrategyAction::Emulate { client_id, order, .. } => (client_id, order),
+                    // nothing of a member is ever working
+                    other => {
+                        debug!("Ensemble member {} action ignored: {:?}", self.name, other);
+                        continue;
+                    }
+                };
+                if order.symbol != symbol {
+                    warn!("Ensemble member {} ordered {} outside the ensemble symbol {}", self.name, order.symbol, symbol);
+                    continue;
+                }
+                let signed = match order.side {
+                    OrderSide::Buy => order.quantity as i32,
+                    OrderSide::Sell => -(order.quantity as i32),
+                };
+                let position = match limit {
+                    Some(limit) => self.position.saturating_add(signed).clamp(-limit, limit),
+                    None => self.position + signed,
+                };
+                let quantity = u64::from(position.abs_diff(self.position));
+                filled += 1;
+                let mut fill_ctx = ctx.nested(&self.open_orders, symbol, position);
+                if quantity == 0 {
+                    let reason = format!("ensemble member position limit of {} reached", limit.unwrap_or_default());
+                    let reject = OrderReject { client_id, request: RequestKind::Place, order: Some(order), reason };
+                    self.strategy.on_order_reject(&reject, &mut fill_ctx);
+                } else {
+                    self.position = position;
+                    let fill = Fill {
+                        client_id,
+                        symbol: order.symbol,
+                        side: order.side,
+                        price: order.price,
+                        quantity,
+                        timestamp: ctx.now(),
+                    };
+                    self.strategy.on_fill(&fill, &mut fill_ctx);
+                }
+                queue.extend(fill_ctx.into_actions());
+            }
+        }
+        if !queue.is_empty() {
+            warn!("Ensemble member {} kept ordering on its own fills, {} orders dropped", self.name, queue.len());
+        }
+    }
+}
+
+impl Strategy for EnsembleStrategy {
+    fn symbols(&self) -> Vec<String> {
+        vec![self.symbol.clone()]
+    }
+
+    fn on_start(&mut self, ctx: &mut StrategyContext) {
+        self.run(ctx, |strategy, ctx| strategy.on_start(ctx));
+    }
+
+    fn on_tick(&mut self, tick: &Tick, ctx: &mut StrategyContext) {
+        if tick.symbol.as_str() == self.symbol {
+            self.last_price = Some(tick.price);
+        }
+        self.run(ctx, |strategy, ctx| strategy.on_tick(tick, ctx));
+    }
+
+    fn on_book_update(&mut self, symbol: &Symbol, ctx: &mut StrategyContext) {
+        self.run(ctx, |strategy, ctx| strategy.on_book_update(symbol, ctx));
+    }
+
+    fn on_bar(&mut self, bar: &Bar, ctx: &mut StrategyContext) {
+        self.run(ctx, |strategy, ctx| strategy.on_bar(bar, ctx));
+    }
+
+    // the next event orders what is still missing from the target
+    fn on_order_reject(&mut self, reject: &OrderReject, _ctx: &mut StrategyContext) {
+        warn!("Ensemble {} order {} rejected: {}", self.symbol, reject.client_id, reject.reason);
+    }
+
+    fn on_timer(&mut self, now: u64, ctx: &mut StrategyContext) {
+        self.run(ctx, |strategy, ctx| strategy.on_timer(now, ctx));
+    }
+
+    fn on_stop(&mut self, ctx: &mut StrategyContext) {
+        self.run(ctx, |strategy, ctx| strategy.on_stop(ctx));
+    }
+
+    fn on_phase_change(&mut self, change: &crate::calendar::PhaseChange) {
+        for member in self.members.iter_mut() {
+            member.strategy.on_phase_change(change);
+        }
+    }
+
+    // member states in member order; virtual positions start flat like the ensemble's target
+    fn snapshot(&self) -> Option<serde_json::Value> {
+        let states = self.members.iter().map(|member| member.strategy.snapshot()).collect::<Vec<_>>();
+        states.iter().any(Option::is_some).then(|| serde_json::Value::from(states))
+    }
+
+    fn restore(&mut self, state: serde_json::Value) -> Result<(), Box<dyn std::error::Error>> {
+        let states: Vec<Option<serde_json::Value>> = serde_json::from_value(state)?;
+        if states.len() != self.members.len() {
+            return Err(format!("snapshot has {} ensemble members, configured {}", states.len(), self.members.len()).into());
+        }
+        for (member, state) in self.members.iter_mut().zip(states) {
+            if let Some(state) = state {
+                member.strategy.restore(state).map_err(|e| format!("member {}: {}", member.name, e))?;
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::strategy::context::ContextHarness;
+    use crate::strategy::Order;
+    use std::cell::Cell;
+    use std::rc::Rc;
+
+    // buys above its level and sells below it, one order per crossing
+    struct Breakout {
+        level: f64,
+        quantity: u64,
+        long: Option<bool>,
+    }
+
+    impl Strategy for Breakout {
+        fn symbols(&self) -> Vec<String> {
+            vec!["AAPL".to_string()]
+        }
+
+        fn evaluate(&mut self, tick: &Tick) -> Option<Order> {
+            let long = tick.price > self.level;
+            if self.long == Some(long) {
+                return None;
+            }
+            let quantity = if self.long.is_some() { 2 * self.quantity } else { self.quantity };
+            self.long = Some(long);
+            let side = if long { OrderSide::Buy } else { OrderSide::Sell };
+            Some(Order { symbol: "AAPL".to_string(), price: tick.price, quantity, side })
+        }
+    }
+
+    fn ensemble(combine: Combine, quantity: u64, members: &[(f64, f64, u64)]) -> EnsembleStrategy {
+        let params = EnsembleParams {
+            symbol: "AAPL".to_string(),
+            combine,
+            quantity,
+            members: members
+                .iter()
+                .map(|&(_, weight, _)| MemberParams { strategy: "breakout".to_string(), weight, params: None })
+                .collect(),
+        };
+        let strategies = members
+            .iter()
+            .map(|&(level, _, quantity)| Box::new(Breakout { level, quantity, long: None }) as Box<dyn Strategy>)
+            .collect();
+        EnsembleStrategy::new(params, strategies).unwrap()
+    }
+
+    // places sent by the ensemble on each price; filled right away when `fill` is set
+    fn run(ensemble: &mut EnsembleStrategy, harness: &mut ContextHarness, prices: &[f64], fill: bool) -> Vec<(OrderSide, u64)> {
+        let mut sent = Vec::new();
+        for &price in prices {
+            let tick = Tick { symbol: "AAPL".into(), price, volume: 1, ..Default::default() };
+            let mut ctx = harness.ctx(0);
+            ensemble.on_tick(&tick, &mut ctx);
+            for action in ctx.into_actions() {
+                let StrategyAction::Place { order, .. } = action else {
+                    panic!("unexpected {:?}", action)
+                };
+                sent.push((order.side, order.quantity));
+                if fill {
+                    harness.risk.update_position(&order);
+                }
+            }
+        }
+        sent
+    }
+
+    #[test]
+    fn test_opposing_members_net_out() {
+        // one member is long above 100, the other short below 105: between the two they cancel
+        let mut weighted = ensemble(Combine::Weighted, 10, &[(100.0, 1.0, 10), (105.0, 0.5, 10)]);
+        let mut harness = ContextHarness::default();
+        assert_eq!(run(&mut weighted, &mut harness, &[102.0], true), vec![(OrderSide::Buy, 5)]);
+        assert_eq!(weighted.target, 5);
+        assert_eq!(run(&mut weighted, &mut harness, &[103.0, 110.0], true), vec![(OrderSide::Buy, 10)]);
+        assert_eq!(run(&mut weighted, &mut harness, &[90.0], true), vec![(OrderSide::Sell, 30)]);
+        assert_eq!(weighted.target, -15);
+    }
+
+    // buys on every tick and counts the buys turned down
+    struct Accumulator {
+        quantity: u64,
+        rejected: Rc<Cell<u32>>,
+    }
+
+    impl Strategy for Accumulator {
+        fn symbols(&self) -> Vec<String> {
+            vec!["AAPL".to_string()]
+        }
+
+        fn evaluate(&mut self, tick: &Tick) -> Option<Order> {
+            Some(Order { symbol: "AAPL".to_string(), price: tick.price, quantity: self.quantity, side: OrderSide::Buy })
+        }
+
+        fn on_order_reject(&mut self, _reject: &OrderReject, _ctx: &mut StrategyContext) {
+            self.rejected.set(self.rejected.get() + 1);
+        }
+    }
+
+    #[test]
+    fn test_member_positions_are_capped() {
+        let params = EnsembleParams {
+            symbol: "AAPL".to_string(),
+            combine: Combine::Weighted,
+            quantity: 3,
+            members: vec![MemberParams { strategy: "accumulator".to_string(), weight: 1.0, params: None }],
+        };
+        let rejected = Rc::new(Cell::new(0));
+        let member = Box::new(Accumulator { quantity: 2, rejected: rejected.clone() }) as Box<dyn Strategy>;
+        let mut weighted = EnsembleStrategy::new(params, vec![member]).unwrap();
+        let mut harness = ContextHarness::default();
+
+        // 2, then the 1 that is left up to the limit, then nothing
+        assert_eq!(run(&mut weighted, &mut harness, &[100.0, 100.0, 100.0, 100.0], true), vec![(OrderSide::Buy, 2), (OrderSide::Buy, 1)]);
+        assert_eq!((weighted.target, weighted.members[0].position), (3, 3));
+        assert_eq!(rejected.get(), 2);
+    }
+
+    #[test]
+    fn test_vote_and_priority() {
+        let members = [(100.0, 1.0, 10), (105.0, 1.0, 3), (95.0, 1.0, 7)];
+        let mut vote = ensemble(Combine::Vote, 5, &members);
+        let mut harness = ContextHarness::default();
+        // two of three long
+        assert_eq!(run(&mut vote, &mut harness, &[102.0], true), vec![(OrderSide::Buy, 5)]);
+        assert_eq!(run(&mut vote, &mut harness, &[97.0, 90.0], true), vec![(OrderSide::Sell, 10)]);
+
+        let mut priority = ensemble(Combine::Priority, 10, &members);
+        let mut harness = ContextHarness::default();
+        assert_eq!(run(&mut priority, &mut harness, &[102.0, 97.0], true), vec![(OrderSide::Buy, 10), (OrderSide::Sell, 20)]);
+        assert_eq!(priority.target, -10);
+    }
+
+    #[test]
+    fn test_unfilled_target_is_ordered_again() {
+        let mut weighted = ensemble(Combine::Weighted, 10, &[(100.0, 1.0, 10)]);
+        let mut harness = ContextHarness::default();
+        // rejected: nothing filled and nothing working, the next tick orders the target again
+        assert_eq!(run(&mut weighted, &mut harness, &[102.0], false), vec![(OrderSide::Buy, 10)]);
+        assert_eq!(run(&mut weighted, &mut harness, &[103.0], true), vec![(OrderSide::Buy, 10)]);
+        assert!(run(&mut weighted, &mut harness, &[104.0], true).is_empty());
+        assert_eq!(harness.risk.get_position("AAPL"), 10);
+    }
+}
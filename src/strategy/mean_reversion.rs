@@ -0,0 +1,454 @@
+use super::{Fill, Order, OrderReject, OrderSide, RequestKind, Strategy, StrategyContext};
+use crate::indicators::{BollingerBands, Rsi};
+use crate::market_data::parser::Tick;
+use crate::market_data::symbol::Symbol;
+use log::{debug, info, warn};
+use serde::Deserialize;
+
+const NANOS_PER_SEC: f64 = 1_000_000_000.0;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ExitTarget {
+    // close once the price is back at the middle band
+    Mean,
+    // hold through the mean until the price reaches the other band
+    OppositeBand,
+}
+
+// `[strategy.mean_reversion]`
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct MeanReversionParams {
+    pub symbol: String,
+    #[serde(default = "default_quantity")]
+    pub quantity: u64,
+    #[serde(default = "default_band_period")]
+    pub band_period: usize,
+    // band offset in standard deviations
+    #[serde(default = "default_band_width")]
+    pub band_width: f64,
+    #[serde(default = "default_rsi_period")]
+    pub rsi_period: usize,
+    // a buy below the lower band needs RSI at or below this, a sell above the upper band at or above rsi_overbought
+    #[serde(default = "default_rsi_oversold")]
+    pub rsi_oversold: f64,
+    #[serde(default = "default_rsi_overbought")]
+    pub rsi_overbought: f64,
+    #[serde(default = "default_exit")]
+    pub exit: ExitTarget,
+    // close a trade that is not in profit after this long
+    #[serde(default)]
+    pub time_stop_secs: Option<f64>,
+    // close any trade after this long
+    #[serde(default)]
+    pub max_holding_secs: Option<f64>,
+}
+
+fn default_quantity() -> u64 {
+    1
+}
+
+fn default_band_period() -> usize {
+    20
+}
+
+fn default_band_width() -> f64 {
+    2.0
+}
+
+fn default_rsi_period() -> usize {
+    14
+}
+
+fn default_rsi_oversold() -> f64 {
+    30.0
+}
+
+fn default_rsi_overbought() -> f64 {
+    70.0
+}
+
+fn default_exit() -> ExitTarget {
+    ExitTarget::Mean
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Trade {
+    Flat,
+    // holding time runs on the timer's wall clock, from the first timer after the entry
+    Open { side: OrderSide, entry_id: u64, entry_price: f64, entered_at: Option<u64> },
+    // closing until the entry has stopped working and the position is flat
+    Exiting { entry_id: u64, close_id: Option<u64> },
+}
+
+// fades closes outside the bollinger bands when RSI confirms the move is stretched,
+// and exits at the mean (or the opposite band), on the time stop or at the holding limit
+pub struct MeanReversionStrategy {
+    params: MeanReversionParams,
+    symbol: Symbol,
+    bands: BollingerBands,
+    rsi: Rsi,
+    last_price: Option<f64>,
+    trade: Trade,
+}
+
+impl MeanReversionStrategy {
+    pub fn from_params(params: MeanReversionParams) -> Result<Self, Box<dyn std::error::Error>> {
+        if params.quantity == 0 || params.band_period < 2 || params.rsi_period == 0 {
+            return Err("quantity and rsi_period must be positive and band_period at least 2".into());
+        }
+        if !(params.band_width.is_finite() && params.band_width > 0.0) {
+            return Err("band_width must be positive".into());
+        }
+        if !(0.0 < params.rsi_oversold && params.rsi_oversold < params.rsi_overbought && params.rsi_overbought < 100.0) {
+            return Err("rsi_oversold must be below rsi_overbought, both within (0, 100)".into());
+        }
+        if [params.time_stop_secs, params.max_holding_secs].into_iter().flatten().any(|secs| secs.is_nan() || secs <= 0.0) {
+            return Err("time_stop_secs and max_holding_secs must be positive".into());
+        }
+        Ok(MeanReversionStrategy {
+            symbol: Symbol::new(&params.symbol),
+            bands: BollingerBands::new(params.band_period, params.band_width),
+            rsi: Rsi::new(params.rsi_period),
+            last_price: None,
+            trade: Trade::Flat,
+            params,
+        })
+    }
+
+    fn enter(&mut self, side: OrderSide, price: f64, ctx: &mut StrategyContext) {
+        let entry_id = ctx.submit(Order { symbol: self.params.symbol.clone(), price, quantity: self.params.quantity, side });
+        self.trade = Trade::Open { side, entry_id, entry_price: price, entered_at: None };
+    }
+
+    // pulls the entry if it is still working and unwinds whatever was filled
+    fn exit(&mut self, reason: &str, ctx: &mut StrategyContext) {
+        let Trade::Open { entry_id, .. } = self.trade else {
+            return;
+        };
+        info!("Closing {} mean-reversion trade: {}", self.params.symbol, reason);
+        if ctx.open_orders().get(entry_id).is_some() {
+            ctx.cancel(entry_id);
+        }
+        self.trade = Trade::Exiting { entry_id, close_id: None };
+        self.work_exit(ctx);
+    }
+
+    // sends the close for the remaining position unless one is working, and goes flat once nothing is left.
+    // a rejected or cancelled close is sent again on the next tick or timer
+    fn work_exit(&mut self, ctx: &mut StrategyContext) {
+        let Trade::Exiting { entry_id, close_id } = self.trade else {
+            return;
+        };
+        if close_id.is_some_and(|id| ctx.open_orders().get(id).is_some()) {
+            return;
+        }
+        let position = ctx.position(&self.params.symbol);
+        if position == 0 {
+            if ctx.open_orders().get(entry_id).is_none() {
+                self.trade = Trade::Flat;
+            }
+            return;
+        }
+        let Some(price) = self.last_price else {
+            return;
+        };
+        let close_id = ctx.submit(Order {
+            symbol: self.params.symbol.clone(),
+            price,
+            quantity: u64::from(position.unsigned_abs()),
+            side: if position > 0 { OrderSide::Sell } else { OrderSide::Buy },
+        });
+        self.trade = Trade::Exiting { entry_id, close_id: Some(close_id) };
+    }
+
+    // time stop and holding limit, checked on the timer
+    fn time_exit(&self, now: u64) -> Option<&'static str> {
+        let Trade::Open { side, entry_price, entered_at: Some(entered_at), .. } = self.trade else {
+            return None;
+        };
+        let held = now.saturating_sub(entered_at) as f64 / NANOS_PER_SEC;
+        if self.params.max_holding_secs.is_some_and(|limit| held >= limit) {
+            return Some("max holding time reached");
+        }
+        let in_profit = self.last_price.is_some_and(|price| match side {
+            OrderSide::Buy => price > entry_price,
+            OrderSide::Sell => price < entry_price,
+        });
+        (self.params.time_stop_secs.is_some_and(|limit| held >= limit) && !in_profit).then_some("time stop")
+    }
+}
+
+impl Strategy for MeanReversionStrategy {
+    fn symbols(&self) -> Vec<String> {
+        vec![self.params.symbol.clone()]
+    }
+
+    fn on_tick(&mut self, tick: &Tick, ctx: &mut StrategyContext) {
+        if tick.symbol != self.symbol {
+            return;
+        }
+        let price = tick.price;
+        self.last_price = Some(price);
+        self.bands.push(price);
+        let rsi = self.rsi.push(price);
+        // no new trade until the previous exit has completed
+        if matches!(self.trade, Trade::Exiting { .. }) {
+            self.work_exit(ctx);
+            return;
+        }
+        let (Some((lower, middle, upper)), Some(rsi)) = (self.bands.bands(), rsi) else {
+            return;
+        };
+        debug!("{} bands {:.4}/{:.4}/{:.4} rsi {:.1}", self.params.symbol, lower, middle, upper, rsi);
+
+        match self.trade {
+            Trade::Open { side, .. } => {
+                let target = match (self.params.exit, side) {
+                    (ExitTarget::Mean, OrderSide::Buy) => price >= middle,
+                    (ExitTarget::Mean, OrderSide::Sell) => price <= middle,
+                    (ExitTarget::OppositeBand, OrderSide::Buy) => price >= upper,
+                    (ExitTarget::OppositeBand, OrderSide::Sell) => price <= lower,
+                };
+                if target {
+                    self.exit("exit target reached", ctx);
+                }
+            }
+            Trade::Exiting { .. } => {}
+            Trade::Flat if price < lower && rsi <= self.params.rsi_oversold => {
+                info!("Buying {} at {} below the lower band {:.4}, rsi {:.1}", self.params.symbol, price, lower, rsi);
+                self.enter(OrderSide::Buy, price, ctx);
+            }
+            Trade::Flat if price > upper && rsi >= self.params.rsi_overbought => {
+                info!("Selling {} at {} above the upper band {:.4}, rsi {:.1}", self.params.symbol, price, upper, rsi);
+                self.enter(OrderSide::Sell, price, ctx);
+            }
+            Trade::Flat => {}
+        }
+    }
+
+    fn on_fill(&mut self, fill: &Fill, _ctx: &mut StrategyContext) {
+        // measure profit for the time stop from the actual entry price
+        if let Trade::Open { entry_id, ref mut entry_price, .. } = self.trade {
+            if fill.client_id == entry_id {
+                *entry_price = fill.price;
+            }
+        }
+    }
+
+    fn on_order_reject(&mut self, reject: &OrderReject, _ctx: &mut StrategyContext) {
+        if reject.request != RequestKind::Place {
+            return;
+        }
+        match self.trade {
+            Trade::Open { entry_id, .. } if reject.client_id == entry_id => {
+                warn!("Mean-reversion entry {} rejected: {}", entry_id, reject.reason);
+                self.trade = Trade::Flat;
+            }
+            Trade::Exiting { close_id: Some(close_id), .. } if reject.client_id == close_id => {
+                warn!("Mean-reversion close {} rejected, retrying: {}", close_id, reject.reason);
+            }
+            _ => {}
+        }
+    }
+
+    fn on_timer(&mut self, now: u64, ctx: &mut StrategyContext) {
+        if let Trade::Open { ref mut entered_at, .. } = self.trade {
+            entered_at.get_or_insert(now);
+        }
+        match self.time_exit(now) {
+            Some(reason) => self.exit(reason, ctx),
+            None => self.work_exit(ctx),
+        }
+    }
+
+    fn on_stop(&mut self, ctx: &mut StrategyContext) {
+        self.exit("strategy stopped", ctx);
+    }
+
+    // an open trade is kept and exits by the new rules; an indicator whose period changes starts over
+    fn reconfigure(&mut self, params: &toml::Value) -> Result<(), Box<dyn std::error::Error>> {
+        let fresh = Self::from_params(params.clone().try_into()?)?;
+        let (old, new) = (&self.params, &fresh.params);
+        if new.symbol != old.symbol {
+            return Err("changing the symbol needs a restart".into());
+        }
+        if (new.band_period, new.band_width) != (old.band_period, old.band_width) {
+            self.bands = fresh.bands;
+        }
+        if new.rsi_period != old.rsi_period {
+            self.rsi = fresh.rsi;
+        }
+        self.params = fresh.params;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::strategy::context::ContextHarness;
+    use crate::strategy::StrategyAction;
+
+    const SEC: u64 = 1_000_000_000;
+
+    fn params() -> MeanReversionParams {
+        MeanReversionParams {
+            symbol: "AAPL".to_string(),
+            quantity: 5,
+            band_period: 20,
+            band_width: 2.0,
+            rsi_period: 14,
+            rsi_oversold: 30.0,
+            rsi_overbought: 70.0,
+            exit: ExitTarget::Mean,
+            time_stop_secs: Some(30.0),
+            max_holding_secs: Some(120.0),
+        }
+    }
+
+    fn tick(price: f64, timestamp: u64) -> Tick {
+        Tick { symbol: Symbol::new("AAPL"), price, volume: 1, timestamp, ..Default::default() }
+    }
+
+    // ranging around 100, then a sell-off until the strategy buys it; returns the entry order
+    fn enter_long(strategy: &mut MeanReversionStrategy, harness: &ContextHarness) -> Order {
+        for i in 0..30 {
+            let mut ctx = harness.ctx(i * SEC);
+            strategy.on_tick(&tick(if i.is_multiple_of(2) { 100.1 } else { 99.9 }, i * SEC), &mut ctx);
+            assert!(ctx.into_actions().is_empty(), "no signal inside the bands");
+        }
+        let mut price = 99.9;
+        for i in 30..40 {
+            price -= 1.0;
+            let mut ctx = harness.ctx(i * SEC);
+            strategy.on_tick(&tick(price, i * SEC), &mut ctx);
+            if let Some(StrategyAction::Place { order, .. }) = ctx.into_actions().pop() {
+                return order;
+            }
+        }
+        panic!("no entry on the sell-off");
+    }
+
+    fn closing_order(actions: Vec<StrategyAction>) -> Order {
+        closing(actions).1
+    }
+
+    fn closing(actions: Vec<StrategyAction>) -> (u64, Order) {
+        match actions.as_slice() {
+            [StrategyAction::Place { client_id, order }] => (*client_id, order.clone()),
+            other => panic!("expected one closing order, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_fade_and_exit_at_mean() {
+        let mut strategy = MeanReversionStrategy::from_params(params()).unwrap();
+        let mut harness = ContextHarness::default();
+
+        let entry = enter_long(&mut strategy, &harness);
+        assert_eq!((entry.side, entry.quantity), (OrderSide::Buy, 5));
+        harness.risk.update_position(&entry);
+
+        // still below the mean: hold
+        let mut ctx = harness.ctx(40 * SEC);
+        strategy.on_tick(&tick(entry.price + 0.5, 40 * SEC), &mut ctx);
+        assert!(ctx.into_actions().is_empty());
+
+        let mut ctx = harness.ctx(41 * SEC);
+        strategy.on_tick(&tick(100.0, 41 * SEC), &mut ctx);
+        let exit = closing_order(ctx.into_actions());
+        assert_eq!((exit.side, exit.quantity, exit.price), (OrderSide::Sell, 5, 100.0));
+        assert!(matches!(strategy.trade, Trade::Exiting { .. }));
+
+        // flat once the close has filled
+        harness.risk.update_position(&exit);
+        let mut ctx = harness.ctx(42 * SEC);
+        strategy.on_tick(&tick(100.0, 42 * SEC), &mut ctx);
+        assert!(ctx.into_actions().is_empty());
+        assert_eq!(strategy.trade, Trade::Flat);
+    }
+
+    #[test]
+    fn test_rejected_close_is_retried() {
+        let mut strategy = MeanReversionStrategy::from_params(params()).unwrap();
+        let mut harness = ContextHarness::default();
+        let entry = enter_long(&mut strategy, &harness);
+        harness.risk.update_position(&entry);
+
+        let mut ctx = harness.ctx(41 * SEC);
+        strategy.on_tick(&tick(100.0, 41 * SEC), &mut ctx);
+        let (close_id, close) = closing(ctx.into_actions());
+        let reject = OrderReject { client_id: close_id, request: RequestKind::Place, order: Some(close), reason: "throttled".to_string() };
+        strategy.on_order_reject(&reject, &mut harness.ctx(41 * SEC));
+
+        // a price that would fade into a new long only closes the one still held, on a tick or on the timer
+        let mut ctx = harness.ctx(42 * SEC);
+        strategy.on_tick(&tick(80.0, 42 * SEC), &mut ctx);
+        let (close_id, retry) = closing(ctx.into_actions());
+        assert_eq!((retry.side, retry.quantity, retry.price), (OrderSide::Sell, 5, 80.0));
+        let reject = OrderReject { client_id: close_id, request: RequestKind::Place, order: Some(retry), reason: "throttled".to_string() };
+        strategy.on_order_reject(&reject, &mut harness.ctx(42 * SEC));
+        let mut ctx = harness.ctx(43 * SEC);
+        strategy.on_timer(43 * SEC, &mut ctx);
+        let retry = closing_order(ctx.into_actions());
+
+        // a working close is left alone
+        harness.open_orders.insert(close_id + 1, retry.clone());
+        let mut ctx = harness.ctx(44 * SEC);
+        strategy.on_tick(&tick(80.0, 44 * SEC), &mut ctx);
+        assert!(ctx.into_actions().is_empty());
+
+        harness.open_orders.remove(close_id + 1);
+        harness.risk.update_position(&retry);
+        let mut ctx = harness.ctx(45 * SEC);
+        strategy.on_timer(45 * SEC, &mut ctx);
+        assert!(ctx.into_actions().is_empty());
+        assert_eq!(strategy.trade, Trade::Flat);
+    }
+
+    #[test]
+    fn test_time_stop_and_max_holding() {
+        // a losing trade is closed by the time stop
+        let mut strategy = MeanReversionStrategy::from_params(params()).unwrap();
+        let mut harness = ContextHarness::default();
+        let entry = enter_long(&mut strategy, &harness);
+        harness.risk.update_position(&entry);
+        // the holding time starts at the first timer after the entry
+        let entered_at = 1_000 * SEC;
+        strategy.on_timer(entered_at, &mut harness.ctx(entered_at));
+        let mut ctx = harness.ctx(entered_at + 10 * SEC);
+        strategy.on_tick(&tick(entry.price - 0.5, entered_at + 10 * SEC), &mut ctx);
+        strategy.on_timer(entered_at + 29 * SEC, &mut ctx);
+        assert!(ctx.into_actions().is_empty());
+        let mut ctx = harness.ctx(entered_at + 30 * SEC);
+        strategy.on_timer(entered_at + 30 * SEC, &mut ctx);
+        assert_eq!(closing_order(ctx.into_actions()).side, OrderSide::Sell);
+
+        // a winning trade that never reaches the mean is held until the limit
+        let mut strategy = MeanReversionStrategy::from_params(params()).unwrap();
+        let mut harness = ContextHarness::default();
+        let entry = enter_long(&mut strategy, &harness);
+        harness.risk.update_position(&entry);
+        let entered_at = 1_000 * SEC;
+        strategy.on_timer(entered_at, &mut harness.ctx(entered_at));
+        let mut ctx = harness.ctx(entered_at + SEC);
+        strategy.on_tick(&tick(entry.price + 0.1, entered_at + SEC), &mut ctx);
+        strategy.on_timer(entered_at + 60 * SEC, &mut ctx);
+        assert!(ctx.into_actions().is_empty());
+        let mut ctx = harness.ctx(entered_at + 120 * SEC);
+        strategy.on_timer(entered_at + 120 * SEC, &mut ctx);
+        assert_eq!(closing_order(ctx.into_actions()).quantity, 5);
+    }
+
+    #[test]
+    fn test_invalid_params() {
+        let mut thresholds = params();
+        thresholds.rsi_oversold = 75.0;
+        assert!(MeanReversionStrategy::from_params(thresholds).is_err());
+        let mut time_stop = params();
+        time_stop.time_stop_secs = Some(0.0);
+        assert!(MeanReversionStrategy::from_params(time_stop).is_err());
+    }
+}
@@ -1,10 +1,24 @@
 pub mod strategy_1;
 pub mod strategy_2;
 pub mod risk_management;
+pub mod context;
+pub mod registry;
+pub mod avellaneda_stoikov;
+pub mod pairs;
+pub mod mean_reversion;
+pub mod book_imbalance;
+pub mod ensemble;
+pub mod target;
+pub mod scripted;
+pub mod routed;
+pub mod snapshot;
 
+use crate::market_data::bar::Bar;
+use crate::market_data::parser::Tick;
+use crate::market_data::symbol::Symbol;
 use serde::{Serialize, Deserialize};
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct Order {
     pub symbol: String,
     pub price: f64,
@@ -27,10 +41,77 @@ impl std::fmt::Display for OrderSide {
     }
 }
 
+// lifecycle of a strategy: every callback gets a context to read state and submit orders.
+// all callbacks default to doing nothing, so strategies only implement what they need.
 pub trait Strategy {
-    fn evaluate(&mut self, market_data: &super::market_data::parser::Tick) -> Option<Order>;
+    /// instruments the strategy trades
+    fn symbols(&self) -> Vec<String> {
+        Vec::new()
+    }
+
+    /// single-order signal for a tick; used by the default `on_tick`
+    fn evaluate(&mut self, _market_data: &Tick) -> Option<Order> {
+        None
+    }
+
+    /// called once before the first tick
+    fn on_start(&mut self, _ctx: &mut StrategyContext) {}
+
+    fn on_tick(&mut self, tick: &Tick, ctx: &mut StrategyContext) {
+        if let Some(order) = self.evaluate(tick) {
+            ctx.submit(order);
+        }
+    }
+
+    /// called when the best price or queue size on either side of the order book changes
+    fn on_book_update(&mut self, _symbol: &Symbol, _ctx: &mut StrategyContext) {}
+
+    /// called with each completed time bar
+    fn on_bar(&mut self, _bar: &Bar, _ctx: &mut StrategyContext) {}
+
+    fn on_fill(&mut self, _fill: &Fill, _ctx: &mut StrategyContext) {}
+
+    fn on_order_update(&mut self, _update: &OrderUpdate, _ctx: &mut StrategyContext) {}
+
+    fn on_order_reject(&mut self, _reject: &OrderReject, _ctx: &mut StrategyContext) {}
+
+    /// called every `strategy.timer_interval_ms` with the wall clock in ns
+    fn on_timer(&mut self, _now: u64, _ctx: &mut StrategyContext) {}
+
+    /// called on shutdown; orders submitted here are still sent
+    fn on_stop(&mut self, _ctx: &mut StrategyContext) {}
+
+    /// called when the trading session of the strategy's instrument changes phase
+    fn on_phase_change(&mut self, _change: &crate::calendar::PhaseChange) {}
+
+    /// internal state worth carrying over a restart, `None` when the strategy keeps nothing
+    fn snapshot(&self) -> Option<serde_json::Value> {
+        None
+    }
+
+    /// takes back state from an earlier `snapshot` of the same strategy
+    fn restore(&mut self, _state: serde_json::Value) -> Result<(), Box<dyn std::error::Error>> {
+        Ok(())
+    }
+
+    /// takes new parameters from its `[strategy.<name>]` table while running, keeping state and working orders.
+    /// the whole table is validated before anything changes; an error leaves the strategy as it was
+    fn reconfigure(&mut self, _params: &toml::Value) -> Result<(), Box<dyn std::error::Error>> {
+        Err("parameters cannot change while running".into())
+    }
 }
 
 // re-export strategies for easy access
 pub use strategy_1::SimpleMovingAverageStrategy;
-pub use strategy_2::TrendFollowingStrategy;
\ No newline at end of file
+pub use strategy_2::TrendFollowingStrategy;
+pub use registry::StrategyRegistry;
+pub use avellaneda_stoikov::AvellanedaStoikovStrategy;
+pub use pairs::PairsStrategy;
+pub use mean_reversion::MeanReversionStrategy;
+pub use book_imbalance::BookImbalanceStrategy;
+pub use ensemble::EnsembleStrategy;
+pub use target::TargetPositionStrategy;
+pub use scripted::ScriptedStrategy;
+pub use routed::RoutedStrategy;
+pub use snapshot::SnapshotStore;
+pub use context::{Fill, OrderReject, OrderStatus, OrderUpdate, RequestKind, StrategyAction, StrategyContext};
\ No newline at end of file
@@ -0,0 +1,461 @@
+use super::{Order, OrderReject, OrderSide, RequestKind, Strategy, StrategyContext};
+use crate::indicators::ZScore;
+use crate::market_data::parser::Tick;
+use crate::market_data::symbol::Symbol;
+use log::{debug, info, warn};
+use serde::Deserialize;
+use std::collections::VecDeque;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum HedgeModel {
+    Ols,
+    Kalman,
+}
+
+// `[strategy.pairs]`: spread = leg_a - (alpha + beta * leg_b)
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct PairsParams {
+    pub leg_a: String,
+    pub leg_b: String,
+    // leg_a quantity per trade, leg_b is sized by the hedge ratio
+    pub quantity: u64,
+    #[serde(default = "default_hedge_model")]
+    pub hedge_model: HedgeModel,
+    // observations in the rolling OLS regression
+    #[serde(default = "default_window")]
+    pub ols_window: usize,
+    // kalman state drift; larger values let the hedge ratio move faster
+    #[serde(default = "default_kalman_delta")]
+    pub kalman_delta: f64,
+    #[serde(default = "default_observation_noise")]
+    pub observation_noise: f64,
+    // spread observations in the z-score
+    #[serde(default = "default_window")]
+    pub zscore_window: usize,
+    #[serde(default = "default_entry_z")]
+    pub entry_z: f64,
+    #[serde(default = "default_exit_z")]
+    pub exit_z: f64,
+}
+
+fn default_hedge_model() -> HedgeModel {
+    HedgeModel::Kalman
+}
+
+fn default_window() -> usize {
+    100
+}
+
+fn default_kalman_delta() -> f64 {
+    1e-4
+}
+
+fn default_observation_noise() -> f64 {
+    1e-3
+}
+
+fn default_entry_z() -> f64 {
+    2.0
+}
+
+fn default_exit_z() -> f64 {
+    0.5
+}
+
+// y = alpha + beta * x over the last `window` observations
+struct RollingOls {
+    window: usize,
+    points: VecDeque<(f64, f64)>,
+    sum_x: f64,
+    sum_y: f64,
+    sum_xx: f64,
+    sum_xy: f64,
+}
+
+impl RollingOls {
+    fn new(window: usize) -> Self {
+        RollingOls {
+            window,
+            points: VecDeque::with_capacity(window + 1),
+            sum_x: 0.0,
+            sum_y: 0.0,
+            sum_xx: 0.0,
+            sum_xy: 0.0,
+        }
+    }
+
+    fn update(&mut self, x: f64, y: f64) -> Option<(f64, f64)> {
+        self.points.push_back((x, y));
+        self.sum_x += x;
+        self.sum_y += y;
+        self.sum_xx += x * x;
+        self.sum_xy += x * y;
+        if self.points.len() > self.window {
+            if let Some((old_x, old_y)) = self.points.pop_front() {
+                self.sum_x -= old_x;
+                self.sum_y -= old_y;
+                self.sum_xx -= old_x * old_x;
+                self.sum_xy -= old_x * old_y;
+            }
+        }
+        if self.points.len() < self.window {
+            return None;
+        }
+
+        let n = self.points.len() as f64;
+        let var_x = self.sum_xx - self.sum_x * self.sum_x / n;
+        if var_x.abs() < f64::EPSILON {
+            return None;
+        }
+        let beta = (self.sum_xy - self.sum_x * self.sum_y / n) / var_x;
+        let alpha = (self.sum_y - beta * self.sum_x) / n;
+        Some((beta, alpha))
+    }
+}
+
+// hedge ratio and intercept as a random-walk state observed through y = beta * x + alpha + noise
+struct KalmanHedge {
+    // [beta, alpha]
+    state: [f64; 2],
+    covariance: [[f64; 2]; 2],
+    drift: f64,
+    observation_noise: f64,
+    observations: usize,
+}
+
+impl KalmanHedge {
+    fn new(delta: f64, observation_noise: f64) -> Self {
+        KalmanHedge {
+            state: [0.0, 0.0],
+            covariance: [[1.0, 0.0], [0.0, 1.0]],
+            drift: delta / (1.0 - delta),
+            observation_noise,
+            observations: 0,
+        }
+    }
+
+    fn update(&mut self, x: f64, y: f64) -> Option<(f64, f64)> {
+        let h = [x, 1.0];
+        // predict: the state may have drifted since the last observation
+        let mut p = self.covariance;
+        p[0][0] += self.drift;
+        p[1][1] += self.drift;
+
+        // p * h, innovation and its variance
+        let ph = [p[0][0] * h[0] + p[0][1] * h[1], p[1][0] * h[0] + p[1][1] * h[1]];
+        let innovation = y - (self.state[0] * h[0] + self.state[1] * h[1]);
+        let variance = h[0] * ph[0] + h[1] * ph[1] + self.observation_noise;
+        let gain = [ph[0] / variance, ph[1] / variance];
+
+        self.state[0] += gain[0] * innovation;
+        self.state[1] += gain[1] * innovation;
+        for (i, row) in self.covariance.iter_mut().enumerate() {
+            for (j, cell) in row.iter_mut().enumerate() {
+                *cell = p[i][j] - gain[i] * ph[j];
+            }
+        }
+        self.observations += 1;
+        // the first observations only move the state away from its prior
+        (self.observations > 1).then_some((self.state[0], self.state[1]))
+    }
+}
+
+enum HedgeEstimator {
+    Ols(RollingOls),
+    Kalman(KalmanHedge),
+}
+
+impl HedgeEstimator {
+    fn update(&mut self, x: f64, y: f64) -> Option<(f64, f64)> {
+        match self {
+            HedgeEstimator::Ols(ols) => ols.update(x, y),
+            HedgeEstimator::Kalman(kalman) => kalman.update(x, y),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum SpreadPosition {
+    Flat,
+    // long leg_a, short leg_b
+    Long,
+    Short,
+}
+
+// two-leg statistical arbitrage: trades the z-score of the spread between two instruments,
+// with the hedge ratio re-estimated on every observation
+pub struct PairsStrategy {
+    params: PairsParams,
+    leg_a: Symbol,
+    leg_b: Symbol,
+    last_a: Option<f64>,
+    last_b: Option<f64>,
+    // legs that ticked since the last observation; the pair is sampled once both have
+    fresh: [bool; 2],
+    hedge: HedgeEstimator,
+    zscore: ZScore,
+    position: SpreadPosition,
+    // client ids of the legs sent on entry
+    entry_orders: Vec<u64>,
+}
+
+impl PairsStrategy {
+    pub fn from_params(params: PairsParams) -> Result<Self, Box<dyn std::error::Error>> {
+        if params.leg_a == params.leg_b {
+            return Err("leg_a and leg_b must be different instruments".into());
+        }
+        if params.quantity == 0 || params.ols_window < 2 || params.zscore_window < 2 {
+            return Err("quantity must be positive and windows at least 2".into());
+        }
+        if !(0.0 < params.kalman_delta && params.kalman_delta < 1.0 && params.observation_noise > 0.0) {
+            return Err("kalman_delta must be in (0, 1) and observation_noise positive".into());
+        }
+        if !(0.0 <= params.exit_z && params.exit_z < params.entry_z) {
+            return Err("exit_z must be non-negative and below entry_z".into());
+        }
+        let hedge = match params.hedge_model {
+            HedgeModel::Ols => HedgeEstimator::Ols(RollingOls::new(params.ols_window)),
+            HedgeModel::Kalman => HedgeEstimator::Kalman(KalmanHedge::new(params.kalman_delta, params.observation_noise)),
+        };
+        Ok(PairsStrategy {
+            leg_a: Symbol::new(&params.leg_a),
+            leg_b: Symbol::new(&params.leg_b),
+            last_a: None,
+            last_b: None,
+            fresh: [false, false],
+            hedge,
+            zscore: ZScore::new(params.zscore_window),
+            position: SpreadPosition::Flat,
+            entry_orders: Vec::new(),
+            params,
+        })
+    }
+
+    fn order(symbol: &str, price: f64, quantity: i64) -> Option<Order> {
+        (quantity != 0).then(|| Order {
+            symbol: symbol.to_string(),
+            price,
+            quantity: quantity.unsigned_abs(),
+            side: if quantity > 0 { OrderSide::Buy } else { OrderSide::Sell },
+        })
+    }
+
+    // `direction` +1 buys the spread (leg_a up, leg_b against it), -1 sells it
+    fn enter(&mut self, direction: i64, beta: f64, price_a: f64, price_b: f64, ctx: &mut StrategyContext) {
+        let quantity_a = direction * self.params.quantity as i64;
+        let quantity_b = -(beta * quantity_a as f64).round() as i64;
+        self.entry_orders = [
+            Self::order(&self.params.leg_a, price_a, quantity_a),
+            Self::order(&self.params.leg_b, price_b, quantity_b),
+        ]
+        .into_iter()
+        .flatten()
+        .map(|order| ctx.submit(order))
+        .collect();
+        self.position = if direction > 0 { SpreadPosition::Long } else { SpreadPosition::Short };
+    }
+
+    // unwinds whatever both legs actually hold
+    fn flatten(&mut self, ctx: &mut StrategyContext) {
+        let legs = [(self.params.leg_a.clone(), self.last_a), (self.params.leg_b.clone(), self.last_b)];
+        for (symbol, price) in legs {
+            let position = ctx.position(&symbol) as i64;
+            if let Some(order) = price.and_then(|price| Self::order(&symbol, price, -position)) {
+                ctx.submit(order);
+            }
+        }
+        self.position = SpreadPosition::Flat;
+        self.entry_orders.clear();
+    }
+}
+
+impl Strategy for PairsStrategy {
+    fn symbols(&self) -> Vec<String> {
+        vec![self.params.leg_a.clone(), self.params.leg_b.clone()]
+    }
+
+    fn on_tick(&mut self, tick: &Tick, ctx: &mut StrategyContext) {
+        if tick.symbol == self.leg_a {
+            self.last_a = Some(tick.price);
+            self.fresh[0] = true;
+        } else if tick.symbol == self.leg_b {
+            self.last_b = Some(tick.price);
+            self.fresh[1] = true;
+        } else {
+            return;
+        }
+        let (Some(price_a), Some(price_b)) = (self.last_a, self.last_b) else {
+            return;
+        };
+        if self.fresh != [true, true] {
+            return;
+        }
+        self.fresh = [false, false];
+        let Some((beta, alpha)) = self.hedge.update(price_b, price_a) else {
+            return;
+        };
+        let spread = price_a - (alpha + beta * price_b);
+        let Some(z) = self.zscore.push(spread) else {
+            return;
+        };
+        debug!("{}/{} beta {:.4} spread {:.4} z {:.2}", self.params.leg_a, self.params.leg_b, beta, spread, z);
+
+        match self.position {
+            SpreadPosition::Flat if z >= self.params.entry_z => {
+                info!("Selling spread {}/{} at z {:.2}, beta {:.4}", self.params.leg_a, self.params.leg_b, z, beta);
+                self.enter(-1, beta, price_a, price_b, ctx);
+            }
+            SpreadPosition::Flat if z <= -self.params.entry_z => {
+                info!("Buying spread {}/{} at z {:.2}, beta {:.4}", self.params.leg_a, self.params.leg_b, z, beta);
+                self.enter(1, beta, price_a, price_b, ctx);
+            }
+            SpreadPosition::Long | SpreadPosition::Short if z.abs() <= self.params.exit_z => {
+                info!("Closing spread {}/{} at z {:.2}", self.params.leg_a, self.params.leg_b, z);
+                self.flatten(ctx);
+            }
+            _ => {}
+        }
+    }
+
+    // a rejected entry leg leaves the pair unhedged, so the other leg is unwound
+    fn on_order_reject(&mut self, reject: &OrderReject, ctx: &mut StrategyContext) {
+        if reject.request == RequestKind::Place && self.entry_orders.contains(&reject.client_id) {
+            warn!("Entry leg {} rejected ({}), unwinding the pair", reject.client_id, reject.reason);
+            self.flatten(ctx);
+        }
+    }
+
+    fn on_stop(&mut self, ctx: &mut StrategyContext) {
+        if self.position != SpreadPosition::Flat {
+            self.flatten(ctx);
+        }
+    }
+
+    // an open spread is kept; an estimator whose settings change starts over
+    fn reconfigure(&mut self, params: &toml::Value) -> Result<(), Box<dyn std::error::Error>> {
+        let fresh = Self::from_params(params.clone().try_into()?)?;
+        let (old, new) = (&self.params, &fresh.params);
+        if (&new.leg_a, &new.leg_b) != (&old.leg_a, &old.leg_b) {
+            return Err("changing the legs needs a restart".into());
+        }
+        let hedge = (old.hedge_model, old.ols_window, old.kalman_delta, old.observation_noise);
+        if hedge != (new.hedge_model, new.ols_window, new.kalman_delta, new.observation_noise) {
+            self.hedge = fresh.hedge;
+        }
+        if new.zscore_window != old.zscore_window {
+            self.zscore = fresh.zscore;
+        }
+        self.params = fresh.params;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::strategy::context::ContextHarness;
+    use crate::strategy::StrategyAction;
+
+    fn params(hedge_model: HedgeModel) -> PairsParams {
+        PairsParams {
+            leg_a: "KO".to_string(),
+            leg_b: "PEP".to_string(),
+            quantity: 10,
+            hedge_model,
+            ols_window: 20,
+            kalman_delta: 1e-4,
+            observation_noise: 1e-3,
+            zscore_window: 20,
+            entry_z: 2.0,
+            exit_z: 1.0,
+        }
+    }
+
+    // leg_b oscillates, leg_a = 2 * leg_b + 1 with a small alternating residual
+    fn price_b(i: usize) -> f64 {
+        50.0 + (i as f64 * 0.7).sin() * 3.0
+    }
+
+    fn price_a(i: usize) -> f64 {
+        2.0 * price_b(i) + 1.0 + if i.is_multiple_of(2) { 0.01 } else { -0.01 }
+    }
+
+    #[test]
+    fn test_hedge_ratio_estimates() {
+        let mut ols = RollingOls::new(20);
+        let mut kalman = KalmanHedge::new(1e-4, 1e-3);
+        let (mut ols_fit, mut kalman_fit) = (None, None);
+        for i in 0..500 {
+            ols_fit = ols.update(price_b(i), price_a(i));
+            kalman_fit = kalman.update(price_b(i), price_a(i));
+        }
+        let (beta, alpha) = ols_fit.unwrap();
+        assert!((beta - 2.0).abs() < 0.01 && (alpha - 1.0).abs() < 0.5);
+        let (beta, _) = kalman_fit.unwrap();
+        assert!((beta - 2.0).abs() < 0.05, "kalman beta {}", beta);
+    }
+
+    #[test]
+    fn test_enter_and_exit_both_legs() {
+        let mut strategy = PairsStrategy::from_params(params(HedgeModel::Ols)).unwrap();
+        let mut harness = ContextHarness::default();
+        let tick = |symbol: &str, price: f64, timestamp: u64| Tick {
+            symbol: Symbol::new(symbol),
+            price,
+            volume: 1,
+            timestamp,
+            ..Default::default()
+        };
+
+        let mut actions = Vec::new();
+        for i in 0..40 {
+            let mut ctx = harness.ctx(i as u64);
+            strategy.on_tick(&tick("PEP", price_b(i), i as u64), &mut ctx);
+            strategy.on_tick(&tick("KO", price_a(i), i as u64), &mut ctx);
+            assert!(ctx.into_actions().is_empty(), "no signal while the pair is in line");
+        }
+
+        // leg_a jumps rich: sell KO, buy about two PEP per KO
+        let mut ctx = harness.ctx(40);
+        strategy.on_tick(&tick("PEP", price_b(40), 40), &mut ctx);
+        strategy.on_tick(&tick("KO", price_a(40) + 1.0, 40), &mut ctx);
+        actions.extend(ctx.into_actions());
+        let orders: Vec<Order> = actions
+            .iter()
+            .map(|action| match action {
+                StrategyAction::Place { order, .. } => order.clone(),
+                other => panic!("unexpected action {:?}", other),
+            })
+            .collect();
+        assert_eq!(orders.len(), 2);
+        assert_eq!((orders[0].symbol.as_str(), orders[0].side, orders[0].quantity), ("KO", OrderSide::Sell, 10));
+        assert_eq!((orders[1].symbol.as_str(), orders[1].side), ("PEP", OrderSide::Buy));
+        assert!((18..=22).contains(&orders[1].quantity));
+        assert_eq!(strategy.position, SpreadPosition::Short);
+        for order in &orders {
+            harness.risk.update_position(order);
+        }
+
+        // back in line: both legs are unwound from the actual positions
+        let mut ctx = harness.ctx(41);
+        strategy.on_tick(&tick("PEP", price_b(40), 41), &mut ctx);
+        strategy.on_tick(&tick("KO", price_a(40), 41), &mut ctx);
+        let exits = ctx.into_actions();
+        assert_eq!(exits.len(), 2);
+        assert!(matches!(&exits[0], StrategyAction::Place { order, .. } if order.symbol == "KO" && order.side == OrderSide::Buy && order.quantity == 10));
+        assert!(matches!(&exits[1], StrategyAction::Place { order, .. } if order.symbol == "PEP" && order.quantity == orders[1].quantity));
+        assert_eq!(strategy.position, SpreadPosition::Flat);
+    }
+
+    #[test]
+    fn test_invalid_params() {
+        let mut same_legs = params(HedgeModel::Kalman);
+        same_legs.leg_b = "KO".to_string();
+        assert!(PairsStrategy::from_params(same_legs).is_err());
+        let mut thresholds = params(HedgeModel::Kalman);
+        thresholds.exit_z = 2.5;
+        assert!(PairsStrategy::from_params(thresholds).is_err());
+    }
+}
@@ -0,0 +1,266 @@
+use super::strategy_1::SmaParams;
+use super::strategy_2::TrendParams;
+use super::avellaneda_stoikov::MarketMakerParams;
+use super::pairs::PairsParams;
+use super::mean_reversion::MeanReversionParams;
+use super::book_imbalance::BookImbalanceParams;
+use super::ensemble::{EnsembleParams, ENSEMBLE};
+use super::scripted::ScriptedParams;
+use super::routed::{ExecutionRoute, RouteParams, EXECUTION};
+use super::{
+    AvellanedaStoikovStrategy, BookImbalanceStrategy, EnsembleStrategy, MeanReversionStrategy, PairsStrategy, RoutedStrategy,
+    ScriptedStrategy, SimpleMovingAverageStrategy, Strategy, TargetPositionStrategy, TrendFollowingStrategy,
+};
+use crate::config::StrategyConfig;
+use serde::de::DeserializeOwned;
+use std::collections::BTreeMap;
+
+// builds a strategy from its `[strategy.<name>]` table
+pub type StrategyFactory = Box<dyn Fn(&toml::Value) -> Result<Box<dyn Strategy>, Box<dyn std::error::Error>>>;
+
+// strategies that can be selected by `strategy.name`, each configured by its own table
+pub struct StrategyRegistry {
+    factories: BTreeMap<String, StrategyFactory>,
+}
+
+impl Default for StrategyRegistry {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl StrategyRegistry {
+    /// registry with the built-in strategies
+    pub fn new() -> Self {
+        let mut registry = StrategyRegistry { factories: BTreeMap::new() };
+        registry
+            .register_typed("sma", |params: SmaParams| Ok(Box::new(SimpleMovingAverageStrategy::from_params(params)?)))
+            .expect("built-in names are unique");
+        registry
+            .register_typed("trend", |params: TrendParams| {
+                Ok(Box::new(TargetPositionStrategy::new(TrendFollowingStrategy::from_params(params)?)))
+            })
+            .expect("built-in names are unique");
+        registry
+            .register_typed("avellaneda_stoikov", |params: MarketMakerParams| {
+                Ok(Box::new(AvellanedaStoikovStrategy::from_params(params)?))
+            })
+            .expect("built-in names are unique");
+        registry
+            .register_typed("pairs", |params: PairsParams| Ok(Box::new(PairsStrategy::from_params(params)?)))
+            .expect("built-in names are unique");
+        registry
+            .register_typed("mean_reversion", |params: MeanReversionParams| {
+                Ok(Box::new(MeanReversionStrategy::from_params(params)?))
+            })
+            .expect("built-in names are unique");
+        registry
+            .register_typed("book_imbalance", |params: BookImbalanceParams| {
+                Ok(Box::new(BookImbalanceStrategy::from_params(params)?))
+            })
+            .expect("built-in names are unique");
+        registry
+            .register_typed("scripted", |params: ScriptedParams| Ok(Box::new(ScriptedStrategy::from_params(params)?)))
+            .expect("built-in names are unique");
+        registry
+    }
+
+    pub fn register(&mut self, name: &str, factory: StrategyFactory) -> Result<(), Box<dyn std::error::Error>> {
+        if self.factories.contains_key(name) || name == ENSEMBLE {
+            return Err(format!("Strategy {} is already registered", name).into());
+        }
+        self.factories.insert(name.to_string(), factory);
+        Ok(())
+    }
+
+    /// registers a strategy whose table deserializes into `P`
+    pub fn register_typed<P, F>(&mut self, name: &str, build: F) -> Result<(), Box<dyn std::error::Error>>
+    where
+        P: DeserializeOwned,
+        F: Fn(P) -> Result<Box<dyn Strategy>, Box<dyn std::error::Error>> + 'static,
+    {
+        self.register(name, Box::new(move |table: &toml::Value| build(table.clone().try_into::<P>()?)))
+    }
+
+    pub fn names(&self) -> impl Iterator<Item = &str> {
+        self.factories.keys().map(String::as_str)
+    }
+
+    pub fn build(&self, name: &str, table: &toml::Value) -> Result<Box<dyn Strategy>, Box<dyn std::error::Error>> {
+        let factory = self.factories.get(name).ok_or_else(|| self.unknown(name))?;
+        factory(table).map_err(|e| format!("Invalid [strategy.{}] parameters: {}", name, e).into())
+    }
+
+    /// builds the strategy selected by `strategy.name`; tables for unregistered strategies are errors too.
+    /// an `execution` route in its table hands the orders it submits to an execution algo.
+    pub fn build_from_config(&self, config: &StrategyConfig) -> Result<Box<dyn Strategy>, Box<dyn std::error::Error>> {
+        if let Some(unknown) = config.params.keys().find(|name| !self.factories.contains_key(*name) && *name != ENSEMBLE) {
+            return Err(self.unknown(unknown));
+        }
+        let mut table = config
+            .params
+            .get(&config.name)
+            .cloned()
+            .ok_or_else(|| format!("Missing [strategy.{}] table for strategy {}", config.name, config.name))?;
+        let route = match table.as_table_mut().and_then(|table| table.remove(EXECUTION)) {
+            Some(route) => {
+                let route = route
+                    .try_into::<RouteParams>()
+                    .map_err(Box::<dyn std::error::Error>::from)
+                    .and_then(ExecutionRoute::from_params)
+                    .map_err(|e| format!("Invalid [strategy.{}] execution route: {}", config.name, e))?;
+                Some(route)
+            }
+            None => None,
+        };
+        let strategy = if config.name == ENSEMBLE {
+            self.build_ensemble(&table, config)?
+        } else {
+            self.build(&config.name, &table)?
+        };
+        Ok(match route {
+            Some(route) => Box::new(RoutedStrategy::new(strategy, route)),
+            None => strategy,
+        })
+    }
+
+    // members are configured inline or by their own `[strategy.<name>]` table
+    fn build_ensemble(&self, table: &toml::Value, config: &StrategyConfig) -> Result<Box<dyn Strategy>, Box<dyn std::error::Error>> {
+        let params: EnsembleParams = table
+            .clone()
+            .try_into()
+            .map_err(|e| format!("Invalid [strategy.{}] parameters: {}", ENSEMBLE, e))?;
+        let mut members = Vec::new();
+        for member in &params.members {
+            let table = match member.params.as_ref() {
+                Some(table) => table,
+                None => config
+                    .params
+                    .get(&member.strategy)
+                    .filter(|_| member.strategy != ENSEMBLE)
+                    .ok_or_else(|| format!("Missing [strategy.{}] table for ensemble member {}", member.strategy, member.strategy))?,
+            };
+            members.push(self.build(&member.strategy, table)?);
+        }
+        let ensemble = EnsembleStrategy::new(params, members).map_err(|e| format!("Invalid [strategy.{}] parameters: {}", ENSEMBLE, e))?;
+        Ok(Box::new(ensemble))
+    }
+
+    fn unknown(&self, name: &str) -> Box<dyn std::error::Error> {
+        format!(
+            "Unknown strategy {} (registered: {})",
+            name,
+            self.names().collect::<Vec<_>>().join(", ")
+        )
+        .into()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::market_data::parser::Tick;
+    use crate::strategy::Order;
+
+    fn config(content: &str) -> StrategyConfig {
+        toml::from_str(content).unwrap()
+    }
+
+    struct Idle;
+
+    impl Strategy for Idle {
+        fn evaluate(&mut self, _tick: &Tick) -> Option<Order> {
+            None
+        }
+    }
+
+    #[test]
+    fn test_build_from_tables() {
+        let registry = StrategyRegistry::new();
+        let config = config(
+            r#"
+            name = "trend"
+            [sma]
+            symbol = "AAPL"
+            window_size = 20
+            [trend]
+            symbol = "MSFT"
+            quantity = 5
+            long_period = 20
+            short_period = 5
+            "#,
+        );
+        let strategy = registry.build_from_config(&config).unwrap();
+        assert_eq!(strategy.symbols(), vec!["MSFT".to_string()]);
+        assert_eq!(registry.names().collect::<Vec<_>>(), vec!["avellaneda_stoikov", "book_imbalance", "mean_reversion", "pairs", "scripted", "sma", "trend"]);
+    }
+
+    #[test]
+    fn test_config_errors() {
+        let registry = StrategyRegistry::new();
+        let error = |content: &str| registry.build_from_config(&config(content)).err().unwrap().to_string();
+
+        assert!(error("name = \"smaa\"\n[smaa]\nsymbol = \"AAPL\"").starts_with("Unknown strategy smaa"));
+        assert!(error("name = \"sma\"").starts_with("Missing [strategy.sma]"));
+        // typo in a table of a strategy that is not even selected
+        assert!(error("name = \"sma\"\n[sma]\nsymbol = \"AAPL\"\nwindow_size = 5\n[trnd]\nsymbol = \"AAPL\"")
+            .starts_with("Unknown strategy trnd"));
+        // unknown, missing and invalid parameters
+        assert!(error("name = \"sma\"\n[sma]\nsymbol = \"AAPL\"\nwindow = 5").contains("unknown field"));
+        assert!(error("name = \"sma\"\n[sma]\nsymbol = \"AAPL\"").contains("window_size"));
+        assert!(error("name = \"trend\"\n[trend]\nsymbol = \"AAPL\"\nlong_period = 5\nshort_period = 10")
+            .contains("shorter than long_period"));
+        // execution routes
+        let sma = "name = \"sma\"\n[sma]\nsymbol = \"AAPL\"\nwindow_size = 5\n";
+        assert!(registry.build_from_config(&config(&format!("{}execution = {{ algo = \"twap\", duration_secs = 60 }}", sma))).is_ok());
+        assert!(error(&format!("{}execution = {{ algo = \"twap\", duration_secs = 0 }}", sma)).contains("execution route"));
+        assert!(error(&format!("{}execution = {{ algo = \"twap\" }}", sma)).contains("duration_secs"));
+    }
+
+    #[test]
+    fn test_build_ensemble() {
+        let registry = StrategyRegistry::new();
+        let ensemble = registry
+            .build_from_config(&config(
+                r#"
+                name = "ensemble"
+                [ensemble]
+                symbol = "AAPL"
+                combine = "vote"
+                quantity = 5
+                members = [
+                    { strategy = "sma" },
+                    { strategy = "trend", weight = 2.0 },
+                    { strategy = "sma", params = { symbol = "AAPL", window_size = 50 } },
+                ]
+                [sma]
+                symbol = "AAPL"
+                window_size = 20
+                [trend]
+                symbol = "AAPL"
+                long_period = 20
+                short_period = 5
+                "#,
+            ))
+            .unwrap();
+        assert_eq!(ensemble.symbols(), vec!["AAPL".to_string()]);
+
+        let error = |content: &str| registry.build_from_config(&config(content)).err().unwrap().to_string();
+        let members = "name = \"ensemble\"\n[ensemble]\nsymbol = \"AAPL\"\nmembers = [{ strategy = \"trend\" }]";
+        assert!(error(members).starts_with("Missing [strategy.trend] table for ensemble member"));
+        assert!(error(&format!("{}\n[trend]\nsymbol = \"MSFT\"\nlong_period = 5\nshort_period = 2", members))
+            .contains("does not trade AAPL"));
+        assert!(error("name = \"ensemble\"\n[ensemble]\nsymbol = \"AAPL\"\nmembers = [{ strategy = \"ensemble\" }]")
+            .starts_with("Missing [strategy.ensemble] table for ensemble member"));
+    }
+
+    #[test]
+    fn test_register_custom_strategy() {
+        let mut registry = StrategyRegistry::new();
+        registry.register("idle", Box::new(|_| Ok(Box::new(Idle)))).unwrap();
+        assert!(registry.register("sma", Box::new(|_| Ok(Box::new(Idle)))).is_err());
+
+        let strategy = registry.build_from_config(&config("name = \"idle\"\n[idle]")).unwrap();
+        assert!(strategy.symbols().is_empty());
+    }
+}
@@ -1,13 +1,16 @@
 use crate::strategy::{Order, OrderSide};
 use log::{debug, info, warn};
+use std::collections::{HashMap, HashSet};
 
 pub struct RiskManager {
-    max_position_size: u32, // maximum total position size
+    max_position_size: u32, // maximum position size in each symbol
     max_loss_per_trade: f64, // maximum loss allowed for a single trade
     stop_loss_percentage: f64, // percentage below entry price to set stop loss
     initial_capital: f64, // starting capital
     current_capital: f64, // current capital after trades
     current_position: i32, // current position (+ for long, - for short)
+    positions: HashMap<String, i32>, // net position per symbol
+    halted_symbols: HashSet<String>, // symbols with bad market data, no new orders allowed
 }
 
 impl RiskManager {
@@ -24,26 +27,45 @@ impl RiskManager {
             initial_capital,
             current_capital: initial_capital,
             current_position: 0,
+            positions: HashMap::new(),
+            halted_symbols: HashSet::new(),
         }
     }
 
+    /// replaces the limits checked by `evaluate_order`; positions and capital are kept
+    pub fn set_limits(&mut self, max_position_size: u32, max_loss_per_trade: f64, stop_loss_percentage: f64) {
+        self.max_position_size = max_position_size;
+        self.max_loss_per_trade = max_loss_per_trade;
+        self.stop_loss_percentage = stop_loss_percentage;
+        info!(
+            "Risk limits: max position {}, max loss per trade {}, stop loss {}",
+            max_position_size, max_loss_per_trade, stop_loss_percentage
+        );
+    }
+
     /// evaluates an order and adjusts the quantity if necessary based on risk parameters
     pub fn evaluate_order(&self, order: &mut Order, current_price: f64) -> Option<Order> {
         info!("Evaluating order: {:?}", order);
 
+        if self.halted_symbols.contains(&order.symbol) {
+            warn!("Order rejected: trading in {} is halted", order.symbol);
+            return None;
+        }
+
         let mut adjusted_quantity = order.quantity;
 
-        // check max position size
+        // check max position size, in the order's symbol only
+        let position = self.get_position(&order.symbol);
         let potential_position = match order.side {
-            OrderSide::Buy => self.current_position + order.quantity as i32,
-            OrderSide::Sell => self.current_position - order.quantity as i32,
+            OrderSide::Buy => position + order.quantity as i32,
+            OrderSide::Sell => position - order.quantity as i32,
         };
 
         if potential_position.abs() as u32 > self.max_position_size {
             adjusted_quantity = if order.side == OrderSide::Buy {
-                (self.max_position_size as i32 - self.current_position).max(0) as u64 // how many can we buy?
+                (self.max_position_size as i32 - position).max(0) as u64 // how many can we buy?
             } else {
-                (self.max_position_size as i32 + self.current_position).max(0) as u64 // how many can we sell?
+                (self.max_position_size as i32 + position).max(0) as u64 // how many can we sell?
             };
 
             warn!("Order quantity reduced to {} due to max position size", adjusted_quantity);
@@ -67,12 +89,26 @@ impl RiskManager {
         Some(approved_order)
     }
 
+    /// halts or resumes new orders for a symbol, e.g. when its market data goes bad
+    pub fn set_trading_halted(&mut self, symbol: &str, halted: bool) {
+        let changed = if halted {
+            self.halted_symbols.insert(symbol.to_string())
+        } else {
+            self.halted_symbols.remove(symbol)
+        };
+        if changed {
+            info!("Trading in {} {}", symbol, if halted { "halted" } else { "resumed" });
+        }
+    }
+
     /// updates the current position based on the executed order
     pub fn update_position(&mut self, order: &Order) {
-        match order.side {
-            OrderSide::Buy => self.current_position += order.quantity as i32,
-            OrderSide::Sell => self.current_position -= order.quantity as i32,
-        }
+        let delta = match order.side {
+            OrderSide::Buy => order.quantity as i32,
+            OrderSide::Sell => -(order.quantity as i32),
+        };
+        self.current_position += delta;
+        *self.positions.entry(order.symbol.clone()).or_insert(0) += delta;
         info!("Position updated to: {}", self.current_position);
     }
 
@@ -86,7 +122,35 @@ impl RiskManager {
         self.current_position
     }
 
+    // net position in one symbol
+    pub fn get_position(&self, symbol: &str) -> i32 {
+        self.positions.get(symbol).copied().unwrap_or(0)
+    }
+
     pub fn get_current_capital(&self) -> f64 {
         self.current_capital
     }
-}
\ No newline at end of file
+}
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn order(symbol: &str, side: OrderSide, quantity: u64) -> Order {
+        Order { symbol: symbol.to_string(), price: 100.0, quantity, side }
+    }
+
+    #[test]
+    fn test_position_limit_is_per_symbol() {
+        let mut risk = RiskManager::new(10, 1e9, 0.0, 1e6);
+        // a pair: long one leg, short the other, flat net
+        risk.update_position(&order("AAPL", OrderSide::Buy, 8));
+        risk.update_position(&order("MSFT", OrderSide::Sell, 8));
+        assert_eq!(risk.get_current_position(), 0);
+
+        assert_eq!(risk.evaluate_order(&mut order("AAPL", OrderSide::Buy, 5), 100.0).unwrap().quantity, 2);
+        assert_eq!(risk.evaluate_order(&mut order("AAPL", OrderSide::Sell, 15), 100.0).unwrap().quantity, 15);
+        assert_eq!(risk.evaluate_order(&mut order("MSFT", OrderSide::Sell, 5), 100.0).unwrap().quantity, 2);
+        assert_eq!(risk.evaluate_order(&mut order("MSFT", OrderSide::Buy, 5), 100.0).unwrap().quantity, 5);
+        assert_eq!(risk.evaluate_order(&mut order("GOOG", OrderSide::Buy, 12), 100.0).unwrap().quantity, 10);
+    }
+}
@@ -0,0 +1,322 @@
+use super::{Fill, Order, OrderReject, OrderUpdate, Strategy, StrategyContext};
+use crate::calendar::PhaseChange;
+use crate::market_data::bar::Bar;
+use crate::market_data::parser::Tick;
+use crate::market_data::symbol::Symbol;
+use crate::order_execution::algo::{AlgoKind, AlgoParams, Urgency};
+use crate::order_execution::emulation::{EmulationParams, Peg, PegReference};
+use serde::Deserialize;
+
+// key of the route in a `[strategy.<name>]` table
+pub const EXECUTION: &str = "execution";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum RouteAlgo {
+    Twap,
+    Vwap,
+    Pov,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum RouteUrgency {
+    Low,
+    Medium,
+    High,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum RoutePeg {
+    Primary,
+    Mid,
+    Market,
+}
+
+// `execution = { algo = "twap", duration_secs = 300, urgency = "low" }` in a strategy's table,
+// or an emulated iceberg and/or peg such as `execution = { display_quantity = 10, peg = "primary" }`
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct RouteParams {
+    #[serde(default)]
+    pub algo: Option<RouteAlgo>,
+    #[serde(default)]
+    pub duration_secs: Option<f64>,
+    // share of the traded volume, POV only
+    #[serde(default)]
+    pub participation: Option<f64>,
+    // medium when unset
+    #[serde(default)]
+    pub urgency: Option<RouteUrgency>,
+    // the strategy's price caps the children: a buy is never worked above it
+    #[serde(default = "default_limit")]
+    pub limit: bool,
+    #[serde(default)]
+    pub min_clip: Option<u64>,
+    #[serde(default)]
+    pub display_quantity: Option<u64>,
+    #[serde(default)]
+    pub peg: Option<RoutePeg>,
+    #[serde(default)]
+    pub peg_offset: f64,
+}
+
+fn default_limit() -> bool {
+    true
+}
+
+// how the orders a strategy submits are worked
+#[derive(Debug, Clone, PartialEq)]
+pub enum ExecutionRoute {
+    Algo { algo: AlgoParams, limit: bool },
+    // the strategy's price is the limit of the emulated order
+    Emulate(EmulationParams),
+}
+
+impl ExecutionRoute {
+    pub fn from_params(params: RouteParams) -> Result<Self, Box<dyn std::error::Error>> {
+        let Some(algo) = params.algo else {
+            return Self::emulated(params);
+        };
+        if params.display_quantity.is_some() || params.peg.is_some() {
+            return Err("execution takes either an algo or display_quantity/peg, not both".into());
+        }
+        let duration_secs = params.duration_secs.ok_or("execution.duration_secs is required with an algo")?;
+        if !(duration_secs.is_finite() && duration_secs > 0.0) {
+            return Err("execution.duration_secs must be positive".into());
+        }
+        let min_clip = params.min_clip.unwrap_or(1);
+        if min_clip == 0 {
+            return Err("execution.min_clip must be positive".into());
+        }
+        let kind = match (algo, params.participation) {
+            (RouteAlgo::Pov, Some(participation)) if 0.0 < participation && participation <= 1.0 => {
+                AlgoKind::Pov { participation }
+            }
+            (RouteAlgo::Pov, _) => return Err("execution.participation must be in (0, 1] for pov".into()),
+            (_, Some(_)) => return Err("execution.participation only applies to pov".into()),
+            (RouteAlgo::Twap, None) => AlgoKind::Twap,
+            (RouteAlgo::Vwap, None) => AlgoKind::Vwap,
+        };
+        let urgency = match params.urgency.unwrap_or(RouteUrgency::Medium) {
+            RouteUrgency::Low => Urgency::Low,
+            RouteUrgency::Medium => Urgency::Medium,
+            RouteUrgency::High => Urgency::High,
+        };
+        let algo = AlgoParams { urgency, min_clip, ..AlgoParams::new(kind, duration_secs) };
+        Ok(ExecutionRoute::Algo { algo, limit: params.limit })
+    }
+
+    fn emulated(params: RouteParams) -> Result<Self, Box<dyn std::error::Error>> {
+        if params.display_quantity.is_none() && params.peg.is_none() {
+            return Err("execution needs an algo, a display_quantity or a peg".into());
+        }
+        let algo_only = params.duration_secs.is_some() || params.participation.is_some() || params.urgency.is_some();
+        if algo_only || params.min_clip.is_some() || !params.limit {
+            return Err("execution.duration_secs, participation, urgency, min_clip and limit only apply to algos".into());
+        }
+        if params.display_quantity == Some(0) {
+            return Err("execution.display_quantity must be positive".into());
+        }
+        if !params.peg_offset.is_finite() || (params.peg.is_none() && params.peg_offset != 0.0) {
+            return Err("execution.peg_offset must be finite and needs a peg".into());
+        }
+        let peg = params.peg.map(|reference| Peg {
+            reference: match reference {
+                RoutePeg::Primary => PegReference::Primary,
+                RoutePeg::Mid => PegReference::Mid,
+                RoutePeg::Market => PegReference::Market,
+            },
+            offset: params.peg_offset,
+        });
+        Ok(ExecutionRoute::Emulate(EmulationParams { display_quantity: params.display_quantity, peg }))
+    }
+}
+
+// runs a strategy whose submitted orders are handed to an execution algo or the order emulator instead of going
+// straight to the venue. fills of the children are reported under the id `submit` returned, so the strategy
+// needs no changes.
+pub struct RoutedStrategy {
+    strategy: Box<dyn Strategy>,
+    route: ExecutionRoute,
+}
+
+impl RoutedStrategy {
+    pub fn new(strategy: Box<dyn Strategy>, route: ExecutionRoute) -> Self {
+        RoutedStrategy { strategy, route }
+    }
+}
+
+impl Strategy for RoutedStrategy {
+    fn symbols(&self) -> Vec<String> {
+        self.strategy.symbols()
+    }
+
+    fn evaluate(&mut self, market_data: &Tick) -> Option<Order> {
+        self.strategy.evaluate(market_data)
+    }
+
+    fn on_start(&mut self, ctx: &mut StrategyContext) {
+        ctx.route(self.route.clone());
+        self.strategy.on_start(ctx);
+    }
+
+    fn on_tick(&mut self, tick: &Tick, ctx: &mut StrategyContext) {
+        ctx.route(self.route.clone());
+        self.strategy.on_tick(tick, ctx);
+    }
+
+    fn on_book_update(&mut self, symbol: &Symbol, ctx: &mut StrategyContext) {
+        ctx.route(self.route.clone());
+        self.strategy.on_book_update(symbol, ctx);
+    }
+
+    fn on_bar(&mut self, bar: &Bar, ctx: &mut StrategyContext) {
+        ctx.route(self.route.clone());
+        self.strategy.on_bar(bar, ctx);
+    }
+
+    fn on_fill(&mut self, fill: &Fill, ctx: &mut StrategyContext) {
+        ctx.route(self.route.clone());
+        self.strategy.on_fill(fill, ctx);
+    }
+
+    fn on_order_update(&mut self, update: &OrderUpdate, ctx: &mut StrategyContext) {
+        ctx.route(self.route.clone());
+        self.strategy.on_order_update(update, ctx);
+    }
+
+    fn on_order_reject(&mut self, reject: &OrderReject, ctx: &mut StrategyContext) {
+        ctx.route(self.route.clone());
+        self.strategy.on_order_reject(reject, ctx);
+    }
+
+    fn on_timer(&mut self, now: u64, ctx: &mut StrategyContext) {
+        ctx.route(self.route.clone());
+        self.strategy.on_timer(now, ctx);
+    }
+
+    fn on_stop(&mut self, ctx: &mut StrategyContext) {
+        ctx.route(self.route.clone());
+        self.strategy.on_stop(ctx);
+    }
+
+    fn on_phase_change(&mut self, change: &PhaseChange) {
+        self.strategy.on_phase_change(change);
+    }
+
+    fn snapshot(&self) -> Option<serde_json::Value> {
+        self.strategy.snapshot()
+    }
+
+    fn restore(&mut self, state: serde_json::Value) -> Result<(), Box<dyn std::error::Error>> {
+        self.strategy.restore(state)
+    }
+
+    // parents already working keep the route they were started with
+    fn reconfigure(&mut self, params: &toml::Value) -> Result<(), Box<dyn std::error::Error>> {
+        let mut params = params.clone();
+        let route = params
+            .as_table_mut()
+            .and_then(|table| table.remove(EXECUTION))
+            .ok_or("dropping the execution route needs a restart")?;
+        let route = ExecutionRoute::from_params(route.try_into()?)?;
+        self.strategy.reconfigure(&params)?;
+        self.route = route;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::ExecutionConfig;
+    use crate::market_data::consolidated_book::ConsolidatedBook;
+    use crate::market_data::order_book::OrderSide as BookSide;
+    use crate::order_execution::algo::AlgoEngine;
+    use crate::strategy::context::ContextHarness;
+    use crate::strategy::{OrderSide, StrategyAction};
+    use std::collections::HashMap;
+
+    struct Buyer;
+
+    impl Strategy for Buyer {
+        fn evaluate(&mut self, tick: &Tick) -> Option<Order> {
+            Some(Order { symbol: tick.symbol.to_string(), price: tick.price, quantity: 100, side: OrderSide::Buy })
+        }
+    }
+
+    fn route(content: &str) -> Result<ExecutionRoute, Box<dyn std::error::Error>> {
+        ExecutionRoute::from_params(toml::from_str(content)?)
+    }
+
+    #[test]
+    fn test_route_params() {
+        let twap = AlgoParams { urgency: Urgency::Medium, ..AlgoParams::new(AlgoKind::Twap, 60.0) };
+        assert_eq!(route("algo = \"twap\"\nduration_secs = 60").unwrap(), ExecutionRoute::Algo { algo: twap, limit: true });
+        let pov = AlgoParams::new(AlgoKind::Pov { participation: 0.1 }, 60.0);
+        assert_eq!(
+            route("algo = \"pov\"\nduration_secs = 60\nparticipation = 0.1\nlimit = false").unwrap(),
+            ExecutionRoute::Algo { algo: pov, limit: false }
+        );
+        let peg = Peg { reference: PegReference::Mid, offset: -0.01 };
+        assert_eq!(
+            route("display_quantity = 10\npeg = \"mid\"\npeg_offset = -0.01").unwrap(),
+            ExecutionRoute::Emulate(EmulationParams { display_quantity: Some(10), peg: Some(peg) })
+        );
+
+        assert!(route("").is_err());
+        assert!(route("algo = \"twap\"\nduration_secs = 60\npeg = \"mid\"").is_err());
+        assert!(route("display_quantity = 10\nurgency = \"low\"").is_err());
+        assert!(route("display_quantity = 10\npeg_offset = 0.01").is_err());
+        assert!(route("algo = \"pov\"\nduration_secs = 60").is_err());
+        assert!(route("algo = \"twap\"\nduration_secs = 60\nparticipation = 0.1").is_err());
+        assert!(route("algo = \"twap\"\nduration_secs = 0").is_err());
+        assert!(route("algo = \"twap\"\nduration_secs = 60\nurgency = \"asap\"").is_err());
+    }
+
+    #[test]
+    fn test_submitted_orders_are_pegged() {
+        let mut strategy = RoutedStrategy::new(Box::new(Buyer), route("peg = \"primary\"").unwrap());
+        let harness = ContextHarness::default();
+        let tick = Tick { symbol: "AAPL".into(), price: 101.0, volume: 1, ..Default::default() };
+        let mut ctx = harness.ctx(0);
+        strategy.on_tick(&tick, &mut ctx);
+        match ctx.into_actions().as_slice() {
+            [StrategyAction::Emulate { order, emulation, .. }] => {
+                assert_eq!(order.price, 101.0);
+                assert_eq!(emulation.peg, Some(Peg { reference: PegReference::Primary, offset: 0.0 }));
+            }
+            other => panic!("unexpected {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_submitted_orders_are_worked_from_the_book() {
+        let params = "algo = \"twap\"\nduration_secs = 10\nurgency = \"low\"";
+        let mut strategy = RoutedStrategy::new(Box::new(Buyer), route(params).unwrap());
+        let harness = ContextHarness::default();
+        let mut books = ConsolidatedBook::new();
+        books.update_level(Symbol::new("AAPL"), "XNAS", BookSide::Bid, 99.5, 10);
+        books.update_level(Symbol::new("AAPL"), "XNAS", BookSide::Ask, 100.5, 10);
+
+        let tick = Tick { symbol: "AAPL".into(), price: 101.0, volume: 1, ..Default::default() };
+        let mut ctx = harness.ctx_with_books(0, &books);
+        strategy.on_tick(&tick, &mut ctx);
+        let (client_id, order, algo) = match ctx.into_actions().as_slice() {
+            [StrategyAction::Execute { client_id, order, algo }] => (*client_id, order.clone(), algo.clone()),
+            other => panic!("unexpected {:?}", other),
+        };
+        assert_eq!(algo.limit_price, Some(101.0));
+
+        // low urgency joins the bid of the fed book, not the last trade
+        let mut engine = AlgoEngine::new(ExecutionConfig::default()).unwrap();
+        engine.start(client_id, order, algo, 0).unwrap();
+        let (actions, _) = engine.poll(1_000_000_000, &harness.open_orders, &books, &HashMap::from([(Symbol::new("AAPL"), 101.0)]));
+        match actions.as_slice() {
+            [StrategyAction::Place { order, .. }] => assert_eq!((order.price, order.quantity), (99.5, 10)),
+            other => panic!("unexpected {:?}", other),
+        }
+    }
+}
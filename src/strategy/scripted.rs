@@ -0,0 +1,487 @@
+use super::{Fill, Order, OrderReject, OrderSide, Strategy, StrategyContext};
+use crate::market_data::bar::Bar;
+use crate::market_data::parser::{AggressorSide, Tick};
+use crate::market_data::symbol::Symbol;
+use crate::market_data::trade_tape::TradePrint;
+use log::{debug, info, warn};
+use rhai::module_resolvers::DummyModuleResolver;
+use rhai::{Array, CallFnOptions, Dynamic, Engine, Map, Scope, AST};
+use serde::Deserialize;
+use std::cell::RefCell;
+use std::collections::{HashMap, HashSet};
+use std::path::PathBuf;
+use std::rc::Rc;
+
+// book levels per side a script can see
+const BOOK_LEVELS: usize = 10;
+// most recent trade prints a script can see
+const TRADE_PRINTS: usize = 50;
+
+// `[strategy.scripted]`
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct ScriptedParams {
+    // Rhai script, loaded once at startup
+    pub path: PathBuf,
+    pub symbols: Vec<String>,
+    // operations one callback may run before it is aborted
+    #[serde(default = "default_max_operations")]
+    pub max_operations: u64,
+}
+
+fn default_max_operations() -> u64 {
+    100_000
+}
+
+enum Request {
+    Submit(Order),
+    Cancel(u64),
+    CancelAll,
+}
+
+// what the script can read during a callback, copied from the context before the call,
+// and the orders it asks for, applied to the context afterwards
+#[derive(Default)]
+struct Host {
+    now: u64,
+    positions: HashMap<String, i32>,
+    indicators: HashMap<String, f64>,
+    bids: Vec<(f64, u64)>,
+    asks: Vec<(f64, u64)>,
+    // trade flow metrics and prints, oldest first, of the same instrument as the book
+    flow: Option<Map>,
+    trades: Array,
+    open_orders: Array,
+    requests: Vec<Request>,
+}
+
+// strategy logic in a Rhai script, so it can change without recompiling the bot.
+// the script defines any of `on_start()`, `on_tick(tick)`, `on_book_update(symbol)`, `on_bar(bar)`,
+// `on_fill(fill)`, `on_order_reject(reject)`, `on_timer(now)` and `on_stop()`, keeps its state in `this`,
+// and calls `buy`/`sell`/`cancel`/`cancel_all` to trade. book functions such as `best_bid` read the book of
+// the event's symbol, else of the first symbol, and so do `trade_flow()` and `trades(n)`. scripts cannot import modules or touch files,
+// and a callback running more than `max_operations` operations is aborted without sending anything.
+pub struct ScriptedStrategy {
+    engine: Engine,
+    ast: AST,
+    symbols: Vec<String>,
+    callbacks: HashSet<String>,
+    // the script's `this`
+    state: Dynamic,
+    host: Rc<RefCell<Host>>,
+}
+
+impl ScriptedStrategy {
+    pub fn new(source: &str, symbols: Vec<String>, max_operations: u64) -> Result<Self, Box<dyn std::error::Error>> {
+        if symbols.is_empty() {
+            return Err("symbols must not be empty".into());
+        }
+        if max_operations == 0 {
+            return Err("max_operations must be greater than zero".into());
+        }
+        let host = Rc::new(RefCell::new(Host::default()));
+        let engine = sandboxed_engine(max_operations, &host);
+        let ast = engine.compile(source).map_err(|e| format!("script does not compile: {}", e))?;
+        // top-level statements run once, e.g. to fail early on bad constants
+        engine.run_ast(&ast).map_err(|e| format!("script failed: {}", e))?;
+        host.borrow_mut().requests.clear();
+        let callbacks = ast.iter_functions().map(|function| function.name.to_string()).collect::<HashSet<_>>();
+        info!("Loaded strategy script with callbacks {:?}", callbacks);
+        Ok(ScriptedStrategy { engine, ast, symbols, callbacks, state: Dynamic::from_map(Map::new()), host })
+    }
+
+    pub fn from_params(params: ScriptedParams) -> Result<Self, Box<dyn std::error::Error>> {
+        let source = std::fs::read_to_string(&params.path)
+            .map_err(|e| format!("cannot read script {}: {}", params.path.display(), e))?;
+        Self::new(&source, params.symbols, params.max_operations)
+    }
+
+    // runs a script callback, if the script has it, and sends what it ordered
+    fn call(&mut self, name: &str, args: Vec<Dynamic>, symbol: Option<&str>, ctx: &mut StrategyContext) {
+        if !self.callbacks.contains(name) {
+            return;
+        }
+        self.snapshot(symbol, ctx);
+        let options = CallFnOptions::new().eval_ast(false).bind_this_ptr(&mut self.state);
+        let result = self.engine.call_fn_with_options::<Dynamic>(options, &mut Scope::new(), &self.ast, name, args);
+        let requests = std::mem::take(&mut self.host.borrow_mut().requests);
+        if let Err(e) = result {
+            warn!("Strategy script {} failed, {} orders dropped: {}", name, requests.len(), e);
+            return;
+        }
+        for request in requests {
+            match request {
+                Request::Submit(order) => {
+                    ctx.submit(order);
+                }
+                Request::Cancel(client_id) => ctx.cancel(client_id),
+                Request::CancelAll => ctx.cancel_all(None),
+            }
+        }
+    }
+
+    fn snapshot(&self, symbol: Option<&str>, ctx: &StrategyContext) {
+        let mut host = self.host.borrow_mut();
+        host.now = ctx.now();
+        host.positions = self.symbols.iter().map(|symbol| (symbol.clone(), ctx.position(symbol))).collect();
+        host.indicators = ctx.market_data().indicator_values().into_iter().collect();
+        let symbol = symbol.unwrap_or(&self.symbols[0]);
+        let book = ctx.book(symbol);
+        host.bids = book.map(|book| book.bid_levels().take(BOOK_LEVELS).collect()).unwrap_or_default();
+        host.asks = book.map(|book| book.ask_levels().take(BOOK_LEVELS).collect()).unwrap_or_default();
+        let flow = ctx.trade_flow(symbol);
+        host.flow = flow.map(|flow| {
+            let mut map = Map::new();
+            map.insert("buy_volume".into(), Dynamic::from_int(flow.buy_volume() as i64));
+            map.insert("sell_volume".into(), Dynamic::from_int(flow.sell_volume() as i64));
+            map.insert("imbalance".into(), optional(flow.imbalance()));
+            map.insert("intensity".into(), Dynamic::from_float(flow.intensity()));
+            map
+        });
+        host.trades = flow
+            .map(|flow| {
+                let mut trades = flow.prints().rev().take(TRADE_PRINTS).map(print_map).collect::<Array>();
+                trades.reverse();
+                trades
+            })
+            .unwrap_or_default();
+        host.open_orders = ctx
+            .open_orders()
+            .iter()
+            .map(|open| {
+                let mut order = order_map(&open.order);
+                order.insert("id".into(), Dynamic::from_int(open.client_id as i64));
+                Dynamic::from_map(order)
+            })
+            .collect();
+    }
+}
+
+fn sandboxed_engine(max_operations: u64, host: &Rc<RefCell<Host>>) -> Engine {
+    let mut engine = Engine::new();
+    engine
+        .set_max_operations(max_operations)
+        .set_max_call_levels(32)
+        .set_max_expr_depths(64, 32)
+        .set_max_string_size(10_000)
+        .set_max_array_size(10_000)
+        .set_max_map_size(10_000)
+        .set_module_resolver(DummyModuleResolver::new())
+        .on_print(|text| info!("[script] {}", text))
+        .on_debug(|text, _, position| debug!("[script] {} {}", position, text));
+
+    let shared = host.clone();
+    engine.register_fn("now", move || shared.borrow().now as i64);
+    let shared = host.clone();
+    engine.register_fn("position", move |symbol: &str| {
+        shared.borrow().positions.get(symbol).copied().map_or(0, i64::from)
+    });
+    let shared = host.clone();
+    engine.register_fn("indicator", move |key: &str| optional(shared.borrow().indicators.get(key).copied()));
+    let shared = host.clone();
+    engine.register_fn("best_bid", move || optional(shared.borrow().bids.first().map(|&(price, _)| price)));
+    let shared = host.clone();
+    engine.register_fn("best_ask", move || optional(shared.borrow().asks.first().map(|&(price, _)| price)));
+    let shared = host.clone();
+    engine.register_fn("mid", move || {
+        let host = shared.borrow();
+        optional(host.bids.first().zip(host.asks.first()).map(|(&(bid, _), &(ask, _))| (bid + ask) / 2.0))
+    });
+    let shared = host.clone();
+    engine.register_fn("bid_depth", move |levels: i64| depth(&shared.borrow().bids, levels));
+    let shared = host.clone();
+    engine.register_fn("ask_depth", move |levels: i64| depth(&shared.borrow().asks, levels));
+    let shared = host.clone();
+    engine.register_fn("trade_flow", move || shared.borrow().flow.clone().map_or(Dynamic::UNIT, Dynamic::from_map));
+    let shared = host.clone();
+    engine.register_fn("trades", move |count: i64| {
+        let host = shared.borrow();
+        host.trades[host.trades.len().saturating_sub(count.max(0) as usize)..].to_vec()
+    });
+    let shared = host.clone();
+    engine.register_fn("open_orders", move || shared.borrow().open_orders.clone());
+
+    for side in [OrderSide::Buy, OrderSide::Sell] {
+        let shared = host.clone();
+        let name = if side == OrderSide::Buy { "buy" } else { "sell" };
+        engine.register_fn(name, move |symbol: &str, price: f64, quantity: i64| -> Result<(), Box<rhai::EvalAltResult>> {
+            if !(price.is_finite() && price > 0.0 && quantity > 0) {
+                return Err(format!("{} {} {} @ {}: price and quantity must be positive", name, quantity, symbol, price).into());
+            }
+            let order = Order { symbol: symbol.to_string(), price, quantity: quantity as u64, side };
+            shared.borrow_mut().requests.push(Request::Submit(order));
+            Ok(())
+        });
+    }
+    let shared = host.clone();
+    engine.register_fn("cancel", move |client_id: i64| shared.borrow_mut().requests.push(Request::Cancel(client_id as u64)));
+    let shared = host.clone();
+    engine.register_fn("cancel_all", move || shared.borrow_mut().requests.push(Request::CancelAll));
+    engine
+}
+
+fn optional(value: Option<f64>) -> Dynamic {
+    value.map_or(Dynamic::UNIT, Dynamic::from_float)
+}
+
+fn depth(levels: &[(f64, u64)], count: i64) -> i64 {
+    levels.iter().take(count.max(0) as usize).map(|&(_, quantity)| quantity as i64).sum()
+}
+
+fn order_map(order: &Order) -> Map {
+    let mut map = Map::new();
+    map.insert("symbol".into(), order.symbol.clone().into());
+    map.insert("side".into(), side_name(order.side).into());
+    map.insert("price".into(), Dynamic::from_float(order.price));
+    map.insert("quantity".into(), Dynamic::from_int(order.quantity as i64));
+    map
+}
+
+fn print_map(print: &TradePrint) -> Dynamic {
+    let mut map = Map::new();
+    map.insert("price".into(), Dynamic::from_float(print.price));
+    map.insert("volume".into(), Dynamic::from_int(print.volume as i64));
+    map.insert("timestamp".into(), Dynamic::from_int(print.timestamp as i64));
+    let side = match print.aggressor {
+        Some(AggressorSide::Buy) => "buy".into(),
+        Some(AggressorSide::Sell) => "sell".into(),
+        None => Dynamic::UNIT,
+    };
+    map.insert("side".into(), side);
+    Dynamic::from_map(map)
+}
+
+fn side_name(side: OrderSide) -> &'static str {
+    match side {
+        OrderSide::Buy => "buy",
+        OrderSide::Sell => "sell",
+    }
+}
+
+impl Strategy for ScriptedStrategy {
+    fn symbols(&self) -> Vec<String> {
+        self.symbols.clone()
+    }
+
+    fn on_start(&mut self, ctx: &mut StrategyContext) {
+        self.call("on_start", Vec::new(), None, ctx);
+    }
+
+    fn on_tick(&mut self, tick: &Tick, ctx: &mut StrategyContext) {
+        let mut map = Map::new();
+        map.insert("symbol".into(), tick.symbol.as_str().into());
+        map.insert("price".into(), Dynamic::from_float(tick.price));
+        map.insert("volume".into(), Dynamic::from_int(tick.volume as i64));
+        map.insert("timestamp".into(), Dynamic::from_int(tick.timestamp as i64));
+        self.call("on_tick", vec![Dynamic::from_map(map)], Some(tick.symbol.as_str()), ctx);
+    }
+
+    fn on_book_update(&mut self, symbol: &Symbol, ctx: &mut StrategyContext) {
+        self.call("on_book_update", vec![symbol.as_str().into()], Some(symbol.as_str()), ctx);
+    }
+
+    fn on_bar(&mut self, bar: &Bar, ctx: &mut StrategyContext) {
+        let mut map = Map::new();
+        map.insert("symbol".into(), bar.symbol.as_str().into());
+        map.insert("start".into(), Dynamic::from_int(bar.start as i64));
+        for (field, value) in [("open", bar.open), ("high", bar.high), ("low", bar.low), ("close", bar.close)] {
+            map.insert(field.into(), Dynamic::from_float(value));
+        }
+        map.insert("volume".into(), Dynamic::from_int(bar.volume as i64));
+        self.call("on_bar", vec![Dynamic::from_map(map)], Some(bar.symbol.as_str()), ctx);
+    }
+
+    fn on_fill(&mut self, fill: &Fill, ctx: &mut StrategyContext) {
+        let order = Order { symbol: fill.symbol.clone(), price: fill.price, quantity: fill.quantity, side: fill.side };
+        let mut map = order_map(&order);
+        map.insert("id".into(), Dynamic::from_int(fill.client_id as i64));
+        self.call("on_fill", vec![Dynamic::from_map(map)], Some(&fill.symbol), ctx);
+    }
+
+    fn on_order_reject(&mut self, reject: &OrderReject, ctx: &mut StrategyContext) {
+        let mut map = Map::new();
+        map.insert("id".into(), Dynamic::from_int(reject.client_id as i64));
+        map.insert("reason".into(), reject.reason.clone().into());
+        let symbol = reject.order.as_ref().map(|order| order.symbol.as_str());
+        self.call("on_order_reject", vec![Dynamic::from_map(map)], symbol, ctx);
+    }
+
+    fn on_timer(&mut self, now: u64, ctx: &mut StrategyContext) {
+        self.call("on_timer", vec![Dynamic::from_int(now as i64)], None, ctx);
+    }
+
+    fn on_stop(&mut self, ctx: &mut StrategyContext) {
+        self.call("on_stop", Vec::new(), None, ctx);
+    }
+
+    // loads the script again, which keeps its `this`
+    fn reconfigure(&mut self, params: &toml::Value) -> Result<(), Box<dyn std::error::Error>> {
+        let fresh = Self::from_params(params.clone().try_into()?)?;
+        if fresh.symbols != self.symbols {
+            return Err("changing symbols needs a restart".into());
+        }
+        let state = std::mem::take(&mut self.state);
+        *self = ScriptedStrategy { state, ..fresh };
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::{IndicatorConfig, IndicatorKind};
+    use crate::market_data::aggregator::MarketDataAggregator;
+    use crate::market_data::consolidated_book::ConsolidatedBook;
+    use crate::market_data::order_book::OrderSide as BookSide;
+    use crate::market_data::trade_tape::TradeTape;
+    use crate::strategy::context::ContextHarness;
+    use crate::strategy::StrategyAction;
+
+    const SCRIPT: &str = r#"
+        fn on_start() {
+            this.ticks = 0;
+        }
+
+        fn on_tick(tick) {
+            this.ticks += 1;
+            let fast = indicator("fast");
+            if fast == () || position(tick.symbol) != 0 {
+                return;
+            }
+            // join the bid when the price is above its average and the bid side is deeper
+            if tick.price > fast && bid_depth(2) > ask_depth(2) {
+                buy(tick.symbol, best_bid(), 2);
+            } else if tick.price < fast {
+                sell(tick.symbol, best_ask(), this.ticks);
+            }
+        }
+
+        fn on_stop() {
+            for order in open_orders() {
+                cancel(order.id);
+            }
+        }
+    "#;
+
+    #[test]
+    fn test_script_reads_context_and_orders() {
+        let mut strategy = ScriptedStrategy::new(SCRIPT, vec!["AAPL".to_string()], 10_000).unwrap();
+        let mut harness = ContextHarness { market_data: MarketDataAggregator::new(2, None), ..Default::default() };
+        harness
+            .market_data
+            .add_indicators_from_config(&[IndicatorConfig { name: "fast".to_string(), symbol: None, kind: IndicatorKind::Sma { period: 2 } }])
+            .unwrap();
+        let mut book = ConsolidatedBook::new();
+        book.update_level(Symbol::new("AAPL"), "XNAS", BookSide::Bid, 99.0, 50);
+        book.update_level(Symbol::new("AAPL"), "XNAS", BookSide::Ask, 101.0, 20);
+
+        let mut actions = Vec::new();
+        for (step, price) in [100.0, 102.0, 98.0].into_iter().enumerate() {
+            let tick = Tick { symbol: "AAPL".into(), price, volume: 1, timestamp: step as u64, ..Default::default() };
+            harness.market_data.update(&tick);
+            let mut ctx = harness.ctx_with_books(tick.timestamp, &book);
+            if step == 0 {
+                strategy.on_start(&mut ctx);
+            }
+            strategy.on_tick(&tick, &mut ctx);
+            actions.extend(ctx.into_actions());
+        }
+        let placed = actions
+            .iter()
+            .map(|action| match action {
+                StrategyAction::Place { order, .. } => (order.side, order.price, order.quantity),
+                other => panic!("unexpected {:?}", other),
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(placed, vec![(OrderSide::Buy, 99.0, 2), (OrderSide::Sell, 101.0, 3)]);
+
+        harness.open_orders.insert(7, Order { symbol: "AAPL".to_string(), price: 99.0, quantity: 2, side: OrderSide::Buy });
+        let mut ctx = harness.ctx(3);
+        strategy.on_stop(&mut ctx);
+        assert_eq!(ctx.into_actions(), vec![StrategyAction::Cancel { client_id: 7 }]);
+    }
+
+    #[test]
+    fn test_script_reads_trade_flow() {
+        let script = r#"
+            fn on_tick(tick) {
+                let flow = trade_flow();
+                let last = trades(1);
+                if flow.imbalance > 0.0 && last[0].side == "buy" && trades(10).len() == 2 {
+                    buy(tick.symbol, last[0].price, flow.buy_volume);
+                }
+            }
+        "#;
+        let mut strategy = ScriptedStrategy::new(script, vec!["AAPL".to_string()], 10_000).unwrap();
+        let harness = ContextHarness::default();
+        let mut book = ConsolidatedBook::new();
+        book.update_level(Symbol::new("AAPL"), "XNAS", BookSide::Bid, 99.0, 50);
+        book.update_level(Symbol::new("AAPL"), "XNAS", BookSide::Ask, 101.0, 20);
+        let mut tape = TradeTape::new(10, std::time::Duration::from_secs(60));
+
+        // a print below the mid sells, one above buys
+        let mut actions = Vec::new();
+        for (step, (price, volume)) in [(99.0, 3), (101.0, 7)].into_iter().enumerate() {
+            let tick = Tick { symbol: "AAPL".into(), price, volume, timestamp: step as u64, ..Default::default() };
+            tape.record(&tick, book.book(&Symbol::new("AAPL")));
+            let mut ctx = harness.ctx_with_books(tick.timestamp, &book).with_trade_tape(&tape);
+            strategy.on_tick(&tick, &mut ctx);
+            actions.extend(ctx.into_actions());
+        }
+        match actions.as_slice() {
+            [StrategyAction::Place { order, .. }] => assert_eq!((order.side, order.price, order.quantity), (OrderSide::Buy, 101.0, 7)),
+            other => panic!("unexpected {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_runaway_script_is_aborted() {
+        let script = "fn on_tick(tick) { buy(tick.symbol, 1.0, 1); loop { } }";
+        let mut strategy = ScriptedStrategy::new(script, vec!["AAPL".to_string()], 1_000).unwrap();
+        let harness = ContextHarness::default();
+        let tick = Tick { symbol: "AAPL".into(), price: 1.0, volume: 1, ..Default::default() };
+        let mut ctx = harness.ctx(0);
+        strategy.on_tick(&tick, &mut ctx);
+        assert!(ctx.into_actions().is_empty());
+
+        assert!(ScriptedStrategy::new("fn on_tick(tick) {", vec!["AAPL".to_string()], 1_000).is_err());
+        assert!(ScriptedStrategy::new("import \"orders\" as o;", vec!["AAPL".to_string()], 1_000).is_err());
+    }
+
+    #[test]
+    fn test_example_script_loads() {
+        let params = ScriptedParams { path: "scripts/momentum.rhai".into(), symbols: vec!["AAPL".to_string()], max_operations: 1_000 };
+        assert!(ScriptedStrategy::from_params(params).is_ok());
+    }
+
+    #[test]
+    fn test_example_script_trades_with_and_without_depth() {
+        let mut deeper_asks = ConsolidatedBook::new();
+        deeper_asks.update_level(Symbol::new("AAPL"), "XNAS", BookSide::Bid, 101.0, 10);
+        deeper_asks.update_level(Symbol::new("AAPL"), "XNAS", BookSide::Ask, 103.0, 50);
+        let no_depth = ConsolidatedBook::new();
+
+        // the same breakout is confirmed by a missing book, not by one leaning against it
+        for (books, buys) in [(&no_depth, 1), (&deeper_asks, 0)] {
+            let params = ScriptedParams { path: "scripts/momentum.rhai".into(), symbols: vec!["AAPL".to_string()], max_operations: 10_000 };
+            let mut strategy = ScriptedStrategy::from_params(params).unwrap();
+            let mut harness = ContextHarness { market_data: MarketDataAggregator::new(2, None), ..Default::default() };
+            harness
+                .market_data
+                .add_indicators_from_config(&[IndicatorConfig { name: "ema_fast".to_string(), symbol: None, kind: IndicatorKind::Ema { period: 2 } }])
+                .unwrap();
+            let mut actions = Vec::new();
+            for (step, price) in [100.0, 100.0, 102.0].into_iter().enumerate() {
+                let tick = Tick { symbol: "AAPL".into(), price, volume: 1, timestamp: step as u64, ..Default::default() };
+                harness.market_data.update(&tick);
+                let mut ctx = harness.ctx_with_books(tick.timestamp, books);
+                if step == 0 {
+                    strategy.on_start(&mut ctx);
+                }
+                strategy.on_tick(&tick, &mut ctx);
+                actions.extend(ctx.into_actions());
+            }
+            assert_eq!(actions.iter().filter(|action| matches!(action, StrategyAction::Place { .. })).count(), buys);
+        }
+    }
+}
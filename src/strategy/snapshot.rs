@@ -0,0 +1,158 @@
+use super::Strategy;
+use crate::config::StrategyConfig;
+use log::{debug, info};
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+
+const NANOS_PER_SEC: u64 = 1_000_000_000;
+
+// bumped whenever the file layout changes; other versions are not restored
+pub const SNAPSHOT_VERSION: u32 = 1;
+
+// on-disk strategy state, one JSON file per bot
+#[derive(Debug, Serialize, Deserialize)]
+pub struct StrategySnapshot {
+    pub version: u32,
+    // `strategy.name` of the strategy that wrote it
+    pub strategy: String,
+    // wall clock in ns
+    pub saved_at: u64,
+    pub state: serde_json::Value,
+}
+
+// saves the strategy's state at intervals and on shutdown, and restores it on startup if it is recent enough
+pub struct SnapshotStore {
+    path: PathBuf,
+    strategy: String,
+    interval_ns: u64,
+    max_age_ns: u64,
+    last_saved: Option<u64>,
+}
+
+impl SnapshotStore {
+    pub fn new(path: &Path, strategy: &str, interval_secs: u64, max_age_secs: u64) -> Self {
+        SnapshotStore {
+            path: path.to_path_buf(),
+            strategy: strategy.to_string(),
+            interval_ns: interval_secs.saturating_mul(NANOS_PER_SEC),
+            max_age_ns: max_age_secs.saturating_mul(NANOS_PER_SEC),
+            last_saved: None,
+        }
+    }
+
+    /// store for `[strategy] snapshot_path`, `None` when snapshots are off
+    pub fn from_config(config: &StrategyConfig) -> Option<Self> {
+        let path = config.snapshot_path.as_ref()?;
+        Some(Self::new(path, &config.name, config.snapshot_interval_secs, config.snapshot_max_age_secs))
+    }
+
+    /// restores the last snapshot and returns when it was saved; `Ok(None)` when there is none or it is too old
+    pub fn restore(&self, strategy: &mut dyn Strategy, now: u64) -> Result<Option<u64>, Box<dyn std::error::Error>> {
+        if !self.path.exists() {
+            info!("No strategy snapshot at {}, starting cold", self.path.display());
+            return Ok(None);
+        }
+        let snapshot: StrategySnapshot = serde_json::from_str(&std::fs::read_to_string(&self.path)?)?;
+        if snapshot.version != SNAPSHOT_VERSION {
+            return Err(format!("snapshot version {} is not {}", snapshot.version, SNAPSHOT_VERSION).into());
+        }
+        if snapshot.strategy != self.strategy {
+            return Err(format!("snapshot is of strategy {}, not {}", snapshot.strategy, self.strategy).into());
+        }
+        let age = now.saturating_sub(snapshot.saved_at);
+        if age > self.max_age_ns {
+            info!("Strategy snapshot is {}s old, starting cold", age / NANOS_PER_SEC);
+            return Ok(None);
+        }
+        strategy.restore(snapshot.state)?;
+        info!("Restored strategy state saved {}s ago", age / NANOS_PER_SEC);
+        Ok(Some(snapshot.saved_at))
+    }
+
+    /// writes the strategy's state, replacing the previous snapshot in one rename
+    pub fn save(&mut self, strategy: &dyn Strategy, now: u64) -> Result<(), Box<dyn std::error::Error>> {
+        self.last_saved = Some(now);
+        let Some(state) = strategy.snapshot() else {
+            return Ok(());
+        };
+        let snapshot = StrategySnapshot { version: SNAPSHOT_VERSION, strategy: self.strategy.clone(), saved_at: now, state };
+        if let Some(dir) = self.path.parent().filter(|dir| !dir.as_os_str().is_empty()) {
+            std::fs::create_dir_all(dir)?;
+        }
+        let partial = self.path.with_extension("tmp");
+        std::fs::write(&partial, serde_json::to_vec(&snapshot)?)?;
+        std::fs::rename(&partial, &self.path)?;
+        debug!("Strategy state saved to {}", self.path.display());
+        Ok(())
+    }
+
+    /// saves if `snapshot_interval_secs` have passed since the last save
+    pub fn save_if_due(&mut self, strategy: &dyn Strategy, now: u64) -> Result<(), Box<dyn std::error::Error>> {
+        match self.last_saved {
+            Some(last) if now.saturating_sub(last) < self.interval_ns => Ok(()),
+            // the first interval counts from startup
+            None => {
+                self.last_saved = Some(now);
+                Ok(())
+            }
+            Some(_) => self.save(strategy, now),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::market_data::parser::Tick;
+    use crate::strategy::{SimpleMovingAverageStrategy, TargetPositionStrategy, TrendFollowingStrategy};
+
+    const SEC: u64 = NANOS_PER_SEC;
+
+    fn feed(strategy: &mut dyn Strategy, prices: &[f64]) -> Vec<Option<String>> {
+        prices
+            .iter()
+            .map(|&price| {
+                let tick = Tick { symbol: "AAPL".into(), price, volume: 1, ..Default::default() };
+                strategy.evaluate(&tick).map(|order| order.side.to_string())
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_restart_keeps_the_sma_window() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("state/sma.json");
+        let mut store = SnapshotStore::new(&path, "sma", 60, 300);
+
+        let mut running = SimpleMovingAverageStrategy::new("AAPL", 3, 1);
+        feed(&mut running, &[100.0, 101.0]);
+        store.save_if_due(&running, 0).unwrap();
+        assert!(!path.exists(), "nothing is due right at startup");
+        store.save_if_due(&running, 61 * SEC).unwrap();
+
+        // a restarted strategy picks up where the old one left off, a cold one still needs a full window
+        let mut restarted = SimpleMovingAverageStrategy::new("AAPL", 3, 1);
+        assert_eq!(store.restore(&mut restarted, 100 * SEC).unwrap(), Some(61 * SEC));
+        assert_eq!(feed(&mut restarted, &[105.0]), vec![Some("BUY".to_string())]);
+        let mut cold = SimpleMovingAverageStrategy::new("AAPL", 3, 1);
+        assert_eq!(store.restore(&mut cold, 400 * SEC).unwrap(), None);
+        assert_eq!(feed(&mut cold, &[105.0]), vec![None]);
+    }
+
+    #[test]
+    fn test_snapshot_mismatches() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("trend.json");
+        let mut trend = TargetPositionStrategy::new(TrendFollowingStrategy::new("AAPL", 3, 2, 5));
+        SnapshotStore::new(&path, "trend", 60, 300).save(&trend, 0).unwrap();
+        assert_eq!(SnapshotStore::new(&path, "trend", 60, 300).restore(&mut trend, SEC).unwrap(), Some(0));
+
+        // another strategy's file, another layout
+        let mut sma = SimpleMovingAverageStrategy::new("AAPL", 3, 1);
+        let error = SnapshotStore::new(&path, "sma", 60, 300).restore(&mut sma, SEC).unwrap_err();
+        assert!(error.to_string().contains("of strategy trend"));
+        let old = StrategySnapshot { version: 0, strategy: "sma".to_string(), saved_at: 0, state: serde_json::Value::Null };
+        std::fs::write(&path, serde_json::to_vec(&old).unwrap()).unwrap();
+        assert!(SnapshotStore::new(&path, "sma", 60, 300).restore(&mut sma, SEC).is_err());
+    }
+}
@@ -1,36 +1,70 @@
 use super::{Order, OrderSide, Strategy};
+use crate::indicators::{Indicator, Sma};
 use crate::market_data::parser::Tick;
 use log::{debug, info};
+use serde::{Deserialize, Serialize};
+
+// `[strategy.sma]`
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct SmaParams {
+    pub symbol: String,
+    #[serde(default = "default_quantity")]
+    pub quantity: u64,
+    pub window_size: usize,
+}
+
+fn default_quantity() -> u64 {
+    1
+}
+
+// what `snapshot` keeps: the SMA window, oldest first
+#[derive(Debug, Serialize, Deserialize)]
+struct SmaState {
+    prices: Vec<f64>,
+}
 
 pub struct SimpleMovingAverageStrategy {
     window_size: usize,
-    prices: Vec<f64>,
+    sma: Sma,
     symbol: String,
+    quantity: u64,
 }
 
 impl SimpleMovingAverageStrategy {
-    pub fn new() -> Self {
+    pub fn new(symbol: &str, window_size: usize, quantity: u64) -> Self {
         SimpleMovingAverageStrategy {
-            window_size: 10,
-            prices: Vec::new(),
-            symbol: "SYMBOL".to_string(),
+            window_size,
+            sma: Sma::new(window_size),
+            symbol: symbol.to_string(),
+            quantity,
         }
     }
-}
 
-impl Strategy for SimpleMovingAverageStrategy {
-    fn evaluate(&mut self, market_data: &Tick) -> Option<Order> {
-        self.prices.push(market_data.price);
-        if self.prices.len() > self.window_size {
-            self.prices.remove(0);
+    pub fn from_params(params: SmaParams) -> Result<Self, Box<dyn std::error::Error>> {
+        if params.window_size == 0 {
+            return Err("window_size must be greater than zero".into());
         }
-
-        if self.prices.len() < self.window_size {
-            debug!("Not enough data yet to calculate SMA. Need {} ticks.", self.window_size);
-            return None;
+        if params.quantity == 0 {
+            return Err("quantity must be greater than zero".into());
         }
+        Ok(Self::new(&params.symbol, params.window_size, params.quantity))
+    }
+}
+
+impl Strategy for SimpleMovingAverageStrategy {
+    fn symbols(&self) -> Vec<String> {
+        vec![self.symbol.clone()]
+    }
 
-        let sma: f64 = self.prices.iter().sum::<f64>() / self.prices.len() as f64;
+    fn evaluate(&mut self, market_data: &Tick) -> Option<Order> {
+        let sma = match self.sma.update(market_data) {
+            Some(sma) => sma,
+            None => {
+                debug!("Not enough data yet to calculate SMA. Need {} ticks.", self.window_size);
+                return None;
+            }
+        };
 
         debug!("Tick Price: {}, SMA: {}", market_data.price, sma);
 
@@ -39,7 +73,7 @@ impl Strategy for SimpleMovingAverageStrategy {
             Some(Order {
                 symbol: self.symbol.clone(),
                 price: market_data.price,
-                quantity: 1,
+                quantity: self.quantity,
                 side: OrderSide::Buy,
             })
         } else if market_data.price < sma {
@@ -47,7 +81,7 @@ impl Strategy for SimpleMovingAverageStrategy {
             Some(Order {
                 symbol: self.symbol.clone(),
                 price: market_data.price,
-                quantity: 1,
+                quantity: self.quantity,
                 side: OrderSide::Sell,
             })
         } else {
@@ -55,4 +89,17 @@ impl Strategy for SimpleMovingAverageStrategy {
             None
         }
     }
-}
\ No newline at end of file
+
+    fn snapshot(&self) -> Option<serde_json::Value> {
+        serde_json::to_value(SmaState { prices: self.sma.window().collect() }).ok()
+    }
+
+    fn restore(&mut self, state: serde_json::Value) -> Result<(), Box<dyn std::error::Error>> {
+        let state: SmaState = serde_json::from_value(state)?;
+        self.sma = Sma::new(self.window_size);
+        for price in state.prices {
+            self.sma.push(price);
+        }
+        Ok(())
+    }
+}
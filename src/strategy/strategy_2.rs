@@ -1,87 +1,99 @@
-use super::{Order, OrderSide, Strategy};
+use super::target::TargetStrategy;
+use super::StrategyContext;
+use crate::indicators::{Indicator, Sma};
 use crate::market_data::parser::Tick;
-use log::info;
+use log::debug;
+use serde::{Deserialize, Serialize};
+
+// `[strategy.trend]`
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct TrendParams {
+    pub symbol: String,
+    #[serde(default = "default_quantity")]
+    pub quantity: u64,
+    pub long_period: usize,
+    pub short_period: usize,
+}
+
+fn default_quantity() -> u64 {
+    10
+}
+
+// what `snapshot` keeps: the long SMA window, oldest first, which ends with the short one
+#[derive(Debug, Serialize, Deserialize)]
+struct TrendState {
+    prices: Vec<f64>,
+}
 
 pub struct TrendFollowingStrategy {
+    symbol: String,
+    quantity: u64,
     long_period: usize,
     short_period: usize,
-    long_sma: Vec<f64>,
-    short_sma: Vec<f64>,
-    long_prices: Vec<f64>,
-    short_prices: Vec<f64>,
-    position: i32,
+    long_sma: Sma,
+    short_sma: Sma,
 }
 
 impl TrendFollowingStrategy {
-    pub fn new(long_period: usize, short_period: usize) -> Self {
+    pub fn new(symbol: &str, long_period: usize, short_period: usize, quantity: u64) -> Self {
         TrendFollowingStrategy {
+            symbol: symbol.to_string(),
+            quantity,
             long_period,
             short_period,
-            long_sma: Vec::new(),
-            short_sma: Vec::new(),
-            long_prices: Vec::new(),
-            short_prices: Vec::new(),
-            position: 0,
+            long_sma: Sma::new(long_period),
+            short_sma: Sma::new(short_period),
         }
     }
 
-    fn update_sma(&mut self, price: f64) {
-        self.long_prices.push(price);
-        if self.long_prices.len() > self.long_period {
-            self.long_prices.remove(0);
-        }
-        self.long_sma.push(self.calculate_sma(&self.long_prices));
-        if self.long_sma.len() > self.long_period {
-            self.long_sma.remove(0);
-        }
-
-        self.short_prices.push(price);
-        if self.short_prices.len() > self.short_period {
-            self.short_prices.remove(0);
+    pub fn from_params(params: TrendParams) -> Result<Self, Box<dyn std::error::Error>> {
+        if params.short_period == 0 || params.short_period >= params.long_period {
+            return Err("short_period must be greater than zero and shorter than long_period".into());
         }
-        self.short_sma.push(self.calculate_sma(&self.short_prices));
-        if self.short_sma.len() > self.short_period {
-            self.short_sma.remove(0);
+        if params.quantity == 0 {
+            return Err("quantity must be greater than zero".into());
         }
-    }
-
-    fn calculate_sma(&self, prices: &[f64]) -> f64 {
-        let sum: f64 = prices.iter().sum();
-        sum / prices.len() as f64
+        Ok(Self::new(&params.symbol, params.long_period, params.short_period, params.quantity))
     }
 }
 
-impl Strategy for TrendFollowingStrategy {
-    fn evaluate(&mut self, market_data: &Tick) -> Option<Order> {
-        self.update_sma(market_data.price);
+// long `quantity` while the short average is above the long one, short while below
+impl TargetStrategy for TrendFollowingStrategy {
+    fn symbols(&self) -> Vec<String> {
+        vec![self.symbol.clone()]
+    }
 
-        if self.long_sma.len() < self.long_period || self.short_sma.len() < self.short_period {
+    fn target(&mut self, tick: &Tick, _ctx: &StrategyContext) -> Option<i32> {
+        if tick.symbol.as_str() != self.symbol {
             return None;
         }
+        let long_trend = self.long_sma.update(tick);
+        let short_trend = self.short_sma.update(tick);
+        let (long_trend, short_trend) = long_trend.zip(short_trend)?;
 
-        let long_trend = self.long_sma.last().unwrap();
-        let short_trend = self.short_sma.last().unwrap();
-
-        if short_trend > long_trend && self.position <= 0 {
-            self.position = 1;
-            info!("TrendFollowing: Buy signal");
-            Some(Order {
-                symbol: "AAPL".to_string(),
-                price: market_data.price,
-                quantity: 10,
-                side: OrderSide::Buy,
-            })
-        } else if short_trend < long_trend && self.position >= 0 {
-            self.position = -1;
-            info!("TrendFollowing: Sell signal");
-            Some(Order {
-                symbol: "AAPL".to_string(),
-                price: market_data.price,
-                quantity: 10,
-                side: OrderSide::Sell,
-            })
+        if short_trend > long_trend {
+            debug!("TrendFollowing: long signal");
+            Some(self.quantity as i32)
+        } else if short_trend < long_trend {
+            debug!("TrendFollowing: short signal");
+            Some(-(self.quantity as i32))
         } else {
             None
         }
     }
-}
\ No newline at end of file
+    fn snapshot(&self) -> Option<serde_json::Value> {
+        serde_json::to_value(TrendState { prices: self.long_sma.window().collect() }).ok()
+    }
+
+    fn restore(&mut self, state: serde_json::Value) -> Result<(), Box<dyn std::error::Error>> {
+        let state: TrendState = serde_json::from_value(state)?;
+        self.long_sma = Sma::new(self.long_period);
+        self.short_sma = Sma::new(self.short_period);
+        for price in state.prices {
+            self.long_sma.push(price);
+            self.short_sma.push(price);
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,223 @@
+use super::{Order, OrderSide, Strategy, StrategyContext};
+use crate::market_data::parser::Tick;
+use log::{debug, info};
+use std::collections::HashMap;
+
+// strategy that says what position it wants instead of which orders to send;
+// wrapped in a `TargetPositionStrategy`, which works out the orders
+pub trait TargetStrategy {
+    fn symbols(&self) -> Vec<String>;
+
+    /// desired net position in the tick's symbol, `None` keeps the current target
+    fn target(&mut self, tick: &Tick, ctx: &StrategyContext) -> Option<i32>;
+
+    /// see `Strategy::snapshot`
+    fn snapshot(&self) -> Option<serde_json::Value> {
+        None
+    }
+
+    fn restore(&mut self, _state: serde_json::Value) -> Result<(), Box<dyn std::error::Error>> {
+        Ok(())
+    }
+}
+
+// runs a `TargetStrategy`, reconciling its target with the actual position and open orders on every tick.
+// fills, partial fills and rejects are picked up from the real position, so nothing drifts.
+pub struct TargetPositionStrategy<S> {
+    strategy: S,
+    targets: HashMap<String, i32>,
+}
+
+impl<S: TargetStrategy> TargetPositionStrategy<S> {
+    pub fn new(strategy: S) -> Self {
+        TargetPositionStrategy { strategy, targets: HashMap::new() }
+    }
+}
+
+impl<S: TargetStrategy> Strategy for TargetPositionStrategy<S> {
+    fn symbols(&self) -> Vec<String> {
+        self.strategy.symbols()
+    }
+
+    fn on_tick(&mut self, tick: &Tick, ctx: &mut StrategyContext) {
+        let symbol = tick.symbol.as_str();
+        // ticks of other instruments neither move a target nor price an order
+        if !self.strategy.symbols().iter().any(|traded| traded == symbol) {
+            return;
+        }
+        if let Some(target) = self.strategy.target(tick, ctx) {
+            let previous = self.targets.insert(symbol.to_string(), target);
+            if previous != Some(target) {
+                info!("Target position for {}: {} -> {}", symbol, previous.unwrap_or(0), target);
+            }
+        }
+        if let Some(&target) = self.targets.get(symbol) {
+            translate(ctx, symbol, target, tick.price);
+        }
+    }
+
+    // targets are not kept: they follow from the restored state on the next tick
+    fn snapshot(&self) -> Option<serde_json::Value> {
+        self.strategy.snapshot()
+    }
+
+    fn restore(&mut self, state: serde_json::Value) -> Result<(), Box<dyn std::error::Error>> {
+        self.strategy.restore(state)
+    }
+}
+
+/// minimal orders that take the position in `symbol` to `target`: working orders towards the target are kept,
+/// the one that would overshoot is amended down, the rest are cancelled and any shortfall is placed at `price`
+pub fn translate(ctx: &mut StrategyContext, symbol: &str, target: i32, price: f64) {
+    let needed = i64::from(target) - i64::from(ctx.position(symbol));
+    let working = ctx
+        .working_orders(symbol)
+        .into_iter()
+        .map(|open| (open.client_id, signed(&open.order), open.order.price))
+        .collect::<Vec<_>>();
+    let mut covered = 0;
+    for (client_id, quantity, order_price) in working {
+        let room = needed - covered;
+        // algo and emulated parents cannot be amended, one that would overshoot is replaced
+        if room == 0 || quantity.signum() != room.signum() || (quantity.abs() > room.abs() && ctx.is_parent(client_id)) {
+            debug!("Cancelling order {} ({:+}) on the way to {} {}", client_id, quantity, symbol, target);
+            ctx.cancel(client_id);
+        } else if quantity.abs() > room.abs() {
+            debug!("Amending order {} from {:+} to {:+} on the way to {} {}", client_id, quantity, room, symbol, target);
+            ctx.amend(client_id, order_price, room.unsigned_abs());
+            covered = needed;
+        } else {
+            covered += quantity;
+        }
+    }
+    let rest = needed - covered;
+    if rest != 0 {
+        let side = if rest > 0 { OrderSide::Buy } else { OrderSide::Sell };
+        ctx.submit(Order { symbol: symbol.to_string(), price, quantity: rest.unsigned_abs(), side });
+    }
+}
+
+fn signed(order: &Order) -> i64 {
+    match order.side {
+        OrderSide::Buy => order.quantity as i64,
+        OrderSide::Sell => -(order.quantity as i64),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::order_execution::open_orders::OpenOrder;
+    use crate::order_execution::WorkingParent;
+    use crate::strategy::context::ContextHarness;
+    use crate::strategy::{StrategyAction, TrendFollowingStrategy};
+
+    fn order(side: OrderSide, quantity: u64, price: f64) -> Order {
+        Order { symbol: "AAPL".to_string(), price, quantity, side }
+    }
+
+    fn translated(position: i32, working: &[Order], target: i32) -> Vec<StrategyAction> {
+        let mut harness = ContextHarness::default();
+        harness.fill("AAPL", position);
+        for order in working {
+            let client_id = harness.open_orders.allocate_id();
+            harness.open_orders.insert(client_id, order.clone());
+        }
+        let mut ctx = harness.ctx(0);
+        translate(&mut ctx, "AAPL", target, 100.0);
+        ctx.into_actions()
+    }
+
+    #[test]
+    fn test_translate_minimal_orders() {
+        let place = |client_id, side, quantity| StrategyAction::Place { client_id, order: order(side, quantity, 100.0) };
+        assert_eq!(translated(0, &[], 10), vec![place(1, OrderSide::Buy, 10)]);
+        assert_eq!(translated(12, &[], 10), vec![place(1, OrderSide::Sell, 2)]);
+        assert!(translated(10, &[], 10).is_empty());
+        // working orders towards the target stay, the shortfall is topped up
+        let working = [order(OrderSide::Buy, 4, 99.0), order(OrderSide::Buy, 3, 99.5)];
+        assert_eq!(translated(0, &working, 10), vec![place(3, OrderSide::Buy, 3)]);
+        assert!(translated(3, &working, 10).is_empty());
+        // the order that overshoots is amended, later ones and wrong-way orders are cancelled
+        assert_eq!(
+            translated(4, &working, 10),
+            vec![StrategyAction::Amend { client_id: 2, price: 99.5, quantity: 2 }]
+        );
+        assert_eq!(
+            translated(0, &[order(OrderSide::Sell, 5, 101.0), order(OrderSide::Buy, 4, 99.0)], -3),
+            vec![
+                StrategyAction::Amend { client_id: 1, price: 101.0, quantity: 3 },
+                StrategyAction::Cancel { client_id: 2 },
+            ]
+        );
+        assert_eq!(translated(-2, &working, -2), vec![StrategyAction::Cancel { client_id: 1 }, StrategyAction::Cancel { client_id: 2 }]);
+    }
+
+    #[test]
+    fn test_translate_counts_parents_not_children() {
+        let mut harness = ContextHarness::default();
+        let (parent, child) = (harness.open_orders.allocate_id(), harness.open_orders.allocate_id());
+        harness.open_orders.insert(child, order(OrderSide::Buy, 2, 99.0));
+        let working = |quantity| WorkingParent {
+            remainder: OpenOrder { client_id: parent, order: order(OrderSide::Buy, quantity, 100.0) },
+            child: Some(child),
+        };
+        let translated = |quantity, target| {
+            let mut ctx = harness.ctx(0).with_parents(vec![working(quantity)]);
+            translate(&mut ctx, "AAPL", target, 100.0);
+            ctx.into_actions()
+        };
+
+        // the child is part of the parent's 6, not on top of it
+        assert_eq!(translated(6, 10), vec![StrategyAction::Place { client_id: 3, order: order(OrderSide::Buy, 4, 100.0) }]);
+        // a parent cannot be amended down: it is replaced
+        assert_eq!(
+            translated(6, 4),
+            vec![StrategyAction::Cancel { client_id: parent }, StrategyAction::Place { client_id: 4, order: order(OrderSide::Buy, 4, 100.0) }]
+        );
+    }
+
+    #[test]
+    fn test_trend_target_survives_rejects() {
+        let mut strategy = TargetPositionStrategy::new(TrendFollowingStrategy::new("AAPL", 3, 2, 5));
+        let mut harness = ContextHarness::default();
+        let mut tick = |harness: &ContextHarness, price: f64| {
+            let tick = Tick { symbol: "AAPL".into(), price, volume: 1, ..Default::default() };
+            let mut ctx = harness.ctx(0);
+            strategy.on_tick(&tick, &mut ctx);
+            ctx.into_actions()
+        };
+
+        assert!(tick(&harness, 100.0).is_empty() && tick(&harness, 101.0).is_empty());
+        let buy = tick(&harness, 102.0);
+        assert!(matches!(&buy[..], [StrategyAction::Place { order, .. }] if order.side == OrderSide::Buy && order.quantity == 5));
+        // the order never filled: the same target is ordered again on the next tick
+        let retry = tick(&harness, 103.0);
+        assert!(matches!(&retry[..], [StrategyAction::Place { order, .. }] if order.quantity == 5));
+        harness.risk.update_position(&order(OrderSide::Buy, 5, 103.0));
+        assert!(tick(&harness, 104.0).is_empty());
+        // trend turns: from +5 to -5
+        let sell = tick(&harness, 90.0);
+        assert!(matches!(&sell[..], [StrategyAction::Place { order, .. }] if order.side == OrderSide::Sell && order.quantity == 10));
+    }
+
+    #[test]
+    fn test_other_symbols_are_ignored() {
+        let mut strategy = TargetPositionStrategy::new(TrendFollowingStrategy::new("AAPL", 3, 2, 5));
+        let harness = ContextHarness::default();
+        let mut tick = |symbol: &str, price: f64| {
+            let tick = Tick { symbol: symbol.into(), price, volume: 1, ..Default::default() };
+            let mut ctx = harness.ctx(0);
+            strategy.on_tick(&tick, &mut ctx);
+            ctx.into_actions()
+        };
+
+        // MSFT prices interleaved with AAPL neither feed the averages nor get orders
+        for (symbol, price) in [("AAPL", 100.0), ("MSFT", 400.0), ("AAPL", 101.0), ("MSFT", 10.0)] {
+            assert!(tick(symbol, price).is_empty());
+        }
+        let buy = tick("AAPL", 102.0);
+        assert!(matches!(&buy[..], [StrategyAction::Place { order, .. }] if order.symbol == "AAPL" && order.price == 102.0));
+        assert!(tick("MSFT", 50.0).is_empty());
+    }
+}
@@ -0,0 +1,79 @@
+// the market data hot path must not allocate. counting allocations needs a global allocator,
+// so this runs as its own test binary instead of taking over the allocator of the unit tests.
+// the binary crate has no library target; the parser modules are compiled in by path,
+// and their warnings are reported against the binary.
+#![allow(dead_code, unused_imports, clippy::upper_case_acronyms)]
+
+use std::cell::Cell;
+
+#[path = "../src/exchange"]
+mod exchange {
+    pub mod binary;
+}
+
+#[path = "../src/market_data"]
+mod market_data {
+    pub mod fix_md;
+    pub mod parser;
+    pub mod scan;
+    pub mod symbol;
+}
+
+use byteorder::{ByteOrder, LittleEndian};
+use exchange::binary::{BinaryMessage, MARKET_DATA_TICK};
+use market_data::parser::{MarketDataFormat, MarketDataParser};
+
+// counts heap allocations made by the current thread
+struct CountingAllocator;
+
+thread_local! {
+    static ALLOCATIONS: Cell<usize> = const { Cell::new(0) };
+}
+
+unsafe impl std::alloc::GlobalAlloc for CountingAllocator {
+    unsafe fn alloc(&self, layout: std::alloc::Layout) -> *mut u8 {
+        let _ = ALLOCATIONS.try_with(|count| count.set(count.get() + 1));
+        std::alloc::System.alloc(layout)
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: std::alloc::Layout) {
+        std::alloc::System.dealloc(ptr, layout)
+    }
+}
+
+#[global_allocator]
+static GLOBAL: CountingAllocator = CountingAllocator;
+
+fn allocations_during(f: impl FnOnce()) -> usize {
+    let before = ALLOCATIONS.with(Cell::get);
+    f();
+    ALLOCATIONS.with(Cell::get) - before
+}
+
+fn binary_frame(price: f64, volume: u64, timestamp: u64, sequence: u64, symbol: &str) -> Vec<u8> {
+    let mut payload = vec![0u8; 33];
+    LittleEndian::write_f64(&mut payload[0..8], price);
+    LittleEndian::write_u64(&mut payload[8..16], volume);
+    LittleEndian::write_u64(&mut payload[16..24], timestamp);
+    LittleEndian::write_u64(&mut payload[24..32], sequence);
+    payload[32] = symbol.len() as u8;
+    payload.extend_from_slice(symbol.as_bytes());
+    BinaryMessage::new(MARKET_DATA_TICK, payload).serialize().unwrap()
+}
+
+#[test]
+fn test_hot_path_does_not_allocate() {
+    let csv = MarketDataParser::new(MarketDataFormat::CSV);
+    let binary = MarketDataParser::new(MarketDataFormat::Binary);
+    let frame = binary_frame(101.25, 40, 1_000, 9, "AAPL");
+    csv.parse_bytes(b"AAPL,100.5,10,1\n").unwrap();
+
+    assert_eq!(allocations_during(|| { csv.parse_bytes(b"AAPL,100.5,10,1\n").unwrap(); }), 0);
+    assert_eq!(allocations_during(|| { binary.parse_bytes(&frame).unwrap(); }), 0);
+    #[cfg(feature = "json")]
+    {
+        let json = MarketDataParser::new(MarketDataFormat::JSON);
+        let data = br#"{"symbol":"AAPL","price":100.5,"volume":10,"timestamp":1,"seq":2}"#;
+        assert_eq!(allocations_during(|| { json.parse_bytes(data).unwrap(); }), 0);
+    }
+}